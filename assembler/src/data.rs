@@ -0,0 +1,196 @@
+use rpc::ContentLocation;
+use rpc::lexer::TokenIterator;
+use crate::expression::{Expression, Symbols};
+use crate::generator::Generable;
+use crate::parser::{attempt, parse_identifier, parse_rest_of_line, parse_token, Parsable, ParseError};
+use crate::relative::{FieldKind, RelativeInstruction, Resolve, UnrelativiceError};
+use crate::Instruction;
+
+/// Raw bytes placed into the image by `.byte`, `.string` and `.fill`.
+pub struct Data(pub(crate) Vec<u8>);
+
+impl Instruction for Data {}
+
+impl Resolve for Data {
+    fn resolve(self: Box<Self>, _symbols: &Symbols) -> Result<Box<dyn Instruction>, UnrelativiceError> { Ok(self) }
+}
+
+impl Generable for Data {
+    fn generate(&self) -> Vec<u8> { self.0.clone() }
+    fn size(&self) -> usize { self.0.len() }
+}
+
+/// `.byte` entries, which may refer to labels that are only known after layout.
+pub struct Bytes(Vec<Expression>);
+
+/// `.word` entries, stored big-endian.
+pub struct Words(Vec<Expression>);
+
+/// `.fill count[, value]` repeats `value`, or 0, `count` times.
+/// The count may only refer to constants and labels defined before it, like `.org`.
+pub struct Fill {
+    count: Expression,
+    value: Option<Expression>
+}
+
+/// The most bytes a single `.fill` may emit, the whole address space.
+const MAX_FILL: usize = 0x10000;
+
+/// `.align n` pads with zeroes up to the next multiple of `n`, which has to be a power of two.
+/// Like the count of `.fill`, `n` may only refer to constants and labels defined before it.
+pub struct Align(Expression);
+
+pub(crate) fn parse_directive_word(tokens: &mut TokenIterator, name: &str) -> Result<(ContentLocation, ()), ParseError> {
+    let location = parse_token(tokens, ".")?;
+    let (word_location, word) = parse_identifier(tokens)?;
+    if word != name {
+        return Err(ParseError::FailedToMatchPattern { location: word_location, pattern_name: format!(".{}", name) })
+    }
+    Ok((location, ()))
+}
+
+pub(crate) fn parse_directive(tokens: &mut TokenIterator, name: &str) -> Result<(ContentLocation, String), ParseError> {
+    let location = parse_directive_word(tokens, name)?.0;
+    Ok((location, parse_rest_of_line(tokens)))
+}
+
+/// Parses `.name expr, expr, ...`.
+fn parse_expressions(tokens: &mut TokenIterator, name: &str) -> Result<(ContentLocation, Vec<Expression>), ParseError> {
+    let location = parse_directive_word(tokens, name)?.0;
+    let mut expressions = vec![Expression::parse(tokens)?.1];
+    while attempt(tokens, |tokens| {
+        while parse_token(tokens, " ").is_ok() {}
+        parse_token(tokens, ",")
+    }).is_ok() {
+        expressions.push(Expression::parse(tokens)?.1);
+    }
+    Ok((location, expressions))
+}
+
+pub(crate) fn invalid(location: &ContentLocation, pattern_name: &str) -> ParseError {
+    ParseError::FailedToMatchPattern { location: location.clone(), pattern_name: pattern_name.to_string() }
+}
+
+pub(crate) fn parse_string(location: &ContentLocation, text: &str) -> Result<Vec<u8>, ParseError> {
+    let text = text.trim().strip_prefix('"').and_then(|it| it.strip_suffix('"')).ok_or_else(|| invalid(location, "string literal"))?;
+    let mut result = vec![];
+    let mut chars = text.chars();
+    while let Some(char) = chars.next() {
+        let char = if char == '\\' {
+            match chars.next() {
+                Some('n') => '\n',
+                Some('t') => '\t',
+                Some('0') => '\0',
+                Some('\\') => '\\',
+                Some('"') => '"',
+                _ => return Err(invalid(location, "escape sequence"))
+            }
+        } else { char };
+        let mut buffer = [0; 4];
+        result.extend_from_slice(char.encode_utf8(&mut buffer).as_bytes());
+    }
+    Ok(result)
+}
+
+impl Parsable for Data {
+    fn parse(tokens: &mut TokenIterator) -> Result<(ContentLocation, Self), ParseError> {
+        let (location, rest) = parse_directive(tokens, "string")?;
+        let mut bytes = parse_string(&location, &rest)?;
+        bytes.push(0);
+        Ok((location, Data(bytes)))
+    }
+}
+
+impl Parsable for Fill {
+    fn parse(tokens: &mut TokenIterator) -> Result<(ContentLocation, Self), ParseError> {
+        let (location, mut expressions) = parse_expressions(tokens, "fill")?;
+        let value = match expressions.len() {
+            1 => None,
+            2 => expressions.pop(),
+            _ => return Err(invalid(&location, "`.fill count[, value]`"))
+        };
+        Ok((location, Fill { count: expressions.remove(0), value }))
+    }
+}
+
+impl Fill {
+    fn count(&self, symbols: &Symbols) -> Result<usize, UnrelativiceError> {
+        self.count.evaluate_in(symbols, 0..=MAX_FILL as i64, "a fill count (0 to 0x10000)").map(|it| it as usize)
+    }
+}
+
+impl RelativeInstruction for Fill {
+    fn unrelativice(self: Box<Self>, symbols: &Symbols, _address: u16) -> Result<Box<dyn Instruction>, UnrelativiceError> {
+        let value = match &self.value {
+            Some(value) => value.evaluate_in(symbols, -0x80..=0xFF, "a byte")? as u8,
+            None => 0
+        };
+        Ok(Box::new(Data(vec![value; self.count(symbols)?])))
+    }
+    fn size(&self) -> usize { 0 }
+    fn size_at(&self, symbols: &Symbols, _address: u16) -> Result<usize, UnrelativiceError> { self.count(symbols) }
+}
+
+impl Parsable for Bytes {
+    fn parse(tokens: &mut TokenIterator) -> Result<(ContentLocation, Self), ParseError> {
+        parse_expressions(tokens, "byte").map(|it| (it.0, Bytes(it.1)))
+    }
+}
+
+impl RelativeInstruction for Bytes {
+    fn unrelativice(self: Box<Self>, symbols: &Symbols, _address: u16) -> Result<Box<dyn Instruction>, UnrelativiceError> {
+        let bytes = self.0.iter()
+            .map(|it| it.evaluate_in(symbols, -0x80..=0xFF, "a byte").map(|it| it as u8))
+            .collect::<Result<_, _>>()?;
+        Ok(Box::new(Data(bytes)))
+    }
+    fn size(&self) -> usize { self.0.len() }
+    fn fields(&self) -> Vec<(usize, FieldKind, &Expression)> {
+        self.0.iter().enumerate().map(|(index, it)| (index, FieldKind::Byte, it)).collect()
+    }
+}
+
+impl Parsable for Words {
+    fn parse(tokens: &mut TokenIterator) -> Result<(ContentLocation, Self), ParseError> {
+        parse_expressions(tokens, "word").map(|it| (it.0, Words(it.1)))
+    }
+}
+
+impl RelativeInstruction for Words {
+    fn unrelativice(self: Box<Self>, symbols: &Symbols, _address: u16) -> Result<Box<dyn Instruction>, UnrelativiceError> {
+        let mut bytes = vec![];
+        for word in self.0 {
+            let value = word.evaluate_in(symbols, -0x8000..=0xFFFF, "a word")? as u16;
+            bytes.extend_from_slice(&value.to_be_bytes());
+        }
+        Ok(Box::new(Data(bytes)))
+    }
+    fn size(&self) -> usize { self.0.len() * 2 }
+    fn fields(&self) -> Vec<(usize, FieldKind, &Expression)> {
+        self.0.iter().enumerate().map(|(index, it)| (index * 2, FieldKind::Word, it)).collect()
+    }
+}
+
+impl Parsable for Align {
+    fn parse(tokens: &mut TokenIterator) -> Result<(ContentLocation, Self), ParseError> {
+        let location = parse_directive_word(tokens, "align")?.0;
+        Ok((location, Align(Expression::parse(tokens)?.1)))
+    }
+}
+
+impl RelativeInstruction for Align {
+    fn unrelativice(self: Box<Self>, symbols: &Symbols, address: u16) -> Result<Box<dyn Instruction>, UnrelativiceError> {
+        Ok(Box::new(Data(vec![0; self.size_at(symbols, address)?])))
+    }
+    fn size(&self) -> usize { 0 }
+    fn alignment(&self, symbols: &Symbols) -> Result<Option<u16>, UnrelativiceError> {
+        match self.0.evaluate(symbols)? {
+            value @ 1..=0x8000 if value.count_ones() == 1 => Ok(Some(value as u16)),
+            value => Err(UnrelativiceError::InvalidAlignment(value, self.0.location.clone()))
+        }
+    }
+    fn size_at(&self, symbols: &Symbols, address: u16) -> Result<usize, UnrelativiceError> {
+        let alignment = self.alignment(symbols)?.unwrap_or(1) as usize;
+        Ok((alignment - address as usize % alignment) % alignment)
+    }
+}
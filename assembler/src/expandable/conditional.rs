@@ -0,0 +1,118 @@
+use std::rc::Rc;
+use rpc::ContentLocation;
+use rpc::lexer::TokenIterator;
+use crate::data::{invalid, parse_directive, parse_directive_word, parse_string};
+use crate::expression::{Expression, Symbols};
+use crate::macros::{ExpandError, Macro};
+use crate::parser::{attempt, parse_identifier, parse_token, Parsable, ParseError};
+use crate::relative::Labels;
+use crate::sources::SourceLocation;
+use super::{Branches, ExpandableInstruction, ExpandableProgram, Expanded};
+
+pub enum Condition {
+    /// `.if expr` holds if `expr` is not 0, it may only use constants defined before it.
+    Expression(Expression),
+    /// `.ifdef NAME` and `.ifndef NAME` test whether a constant is defined so far.
+    Defined(String, bool)
+}
+
+impl Condition {
+    fn holds(&self, context: &ExpandableProgram<'_>) -> Result<bool, ExpandError> {
+        match self {
+            Condition::Expression(expression) => {
+                let symbols = Symbols { labels: Labels::new(), constants: context.constants.borrow().clone() };
+                expression.evaluate(&symbols).map(|it| it != 0).map_err(ExpandError::Condition)
+            },
+            Condition::Defined(name, defined) => Ok(context.constants.borrow().contains_key(name) == *defined)
+        }
+    }
+}
+
+/// A line that opens, continues or closes a conditional block.
+pub enum ConditionalLine {
+    If(Condition),
+    ElseIf(Condition),
+    Else,
+    EndIf
+}
+
+impl Parsable for ConditionalLine {
+    fn parse(tokens: &mut TokenIterator) -> Result<(ContentLocation, Self), ParseError> {
+        let defined = |tokens: &mut TokenIterator, word, defined| {
+            let location = parse_directive_word(tokens, word)?.0;
+            let name = parse_directive_argument(tokens)?;
+            Ok((location, ConditionalLine::If(Condition::Defined(name, defined))))
+        };
+        attempt(tokens, |tokens| defined(tokens, "ifdef", true))
+            .or_else(|_| attempt(tokens, |tokens| defined(tokens, "ifndef", false)))
+            .or_else(|_| attempt(tokens, |tokens| {
+                let location = parse_directive_word(tokens, "if")?.0;
+                Ok((location, ConditionalLine::If(Condition::Expression(Expression::parse(tokens)?.1))))
+            }))
+            .or_else(|_| attempt(tokens, |tokens| {
+                let location = parse_directive_word(tokens, "elif")?.0;
+                Ok((location, ConditionalLine::ElseIf(Condition::Expression(Expression::parse(tokens)?.1))))
+            }))
+            .or_else(|_| attempt(tokens, |tokens| parse_directive_word(tokens, "else").map(|it| (it.0, ConditionalLine::Else))))
+            .or_else(|_| attempt(tokens, |tokens| parse_directive_word(tokens, "endif").map(|it| (it.0, ConditionalLine::EndIf))))
+    }
+}
+
+fn parse_directive_argument(tokens: &mut TokenIterator) -> Result<String, ParseError> {
+    while parse_token(tokens, " ").is_ok() {}
+    Ok(parse_identifier(tokens)?.1)
+}
+
+/// `.if`, `.elif` and `.else` branches, only the first branch whose condition holds is expanded.
+/// Conditions are evaluated during expansion, so they see constants defined by earlier macro calls.
+pub struct Conditional {
+    pub branches: Branches
+}
+
+impl ExpandableInstruction for Conditional {
+    fn expand(self: Box<Self>, _location: &SourceLocation, context: &ExpandableProgram<'_>, depth: usize) -> Result<Expanded, ExpandError> {
+        for (condition, instructions) in self.branches {
+            if let Some(condition) = condition {
+                if !condition.holds(context)? { continue }
+            }
+            context.conditionals.set(context.conditionals.get() + 1);
+            let result = instructions.into_iter().try_fold(vec![], |mut result, (inner, instruction)| {
+                result.append(&mut context.expand_one(&inner, instruction, depth)?);
+                Ok(result)
+            });
+            context.conditionals.set(context.conditionals.get() - 1);
+            return result
+        }
+        Ok(vec![])
+    }
+}
+
+/// A macro defined inside a conditional block, it can only be called once its definition was expanded.
+pub struct Definition(pub Rc<Macro>);
+
+impl ExpandableInstruction for Definition {
+    fn expand(self: Box<Self>, _location: &SourceLocation, context: &ExpandableProgram<'_>, _depth: usize) -> Result<Expanded, ExpandError> {
+        context.macros.borrow_mut().push(self.0);
+        Ok(vec![])
+    }
+}
+
+/// `.error "message"` stops assembling when it is expanded, useful inside conditional blocks.
+pub struct UserError {
+    location: ContentLocation,
+    message: String
+}
+
+impl Parsable for UserError {
+    fn parse(tokens: &mut TokenIterator) -> Result<(ContentLocation, Self), ParseError> {
+        let (location, rest) = parse_directive(tokens, "error")?;
+        let message = String::from_utf8(parse_string(&location, &rest)?).map_err(|_| invalid(&location, "string literal"))?;
+        Ok((location.clone(), UserError { location, message }))
+    }
+}
+
+impl ExpandableInstruction for UserError {
+    fn expand(self: Box<Self>, _location: &SourceLocation, _context: &ExpandableProgram<'_>, _depth: usize) -> Result<Expanded, ExpandError> {
+        Err(ExpandError::User { message: self.message, location: self.location })
+    }
+}
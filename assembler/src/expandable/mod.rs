@@ -1,33 +1,332 @@
-use crate::parser::Parsable;
-use crate::relative::RelativeInstruction;
-use crate::macros::Macro;
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::rc::Rc;
+use rpc::ContentLocation;
+use rpc::lexer::TokenIterator;
+use crate::parser::{attempt, is_instruction_word, parse_identifier, parse_rest_of_line, parse_token, Parsable, ParseError};
+use crate::relative::{parse_relative_instruction, RelativeInstruction, RelativeProgram};
+use crate::macros::{split_arguments, ExpandError, Macro, MAX_EXPANSION_DEPTH};
+use crate::sources::{IncBin, Include, SourceLocation, Sources};
+use crate::data::{invalid, Data};
+use crate::expression::{Constant, Expression};
 
-pub trait ExpandableInstruction: Parsable {
-    fn expand(&self, context: ExpandableProgram) -> Vec<dyn RelativeInstruction>;
-}
+mod conditional;
+
+use conditional::{Condition, Conditional, ConditionalLine, Definition, UserError};
 
-pub trait MacroArgument {
-    fn macro_type(&self) -> MacroArgumentType;
+/// Relative instructions together with the location they are attributed to.
+pub type Expanded = Vec<(SourceLocation, Box<dyn RelativeInstruction>)>;
+
+pub trait ExpandableInstruction {
+    /// Expands the instruction written at `location`.
+    fn expand(self: Box<Self>, location: &SourceLocation, context: &ExpandableProgram<'_>, depth: usize) -> Result<Expanded, ExpandError>;
 }
 
+/// `name arg0, arg1, ...` on a line of its own.
 pub struct MacroCall {
     name: String,
-    arguments: Vec<dyn MacroArgument>
+    location: ContentLocation,
+    arguments: Vec<String>
+}
+
+impl Parsable for MacroCall {
+    fn parse(tokens: &mut TokenIterator) -> Result<(ContentLocation, Self), ParseError> {
+        let (location, name) = parse_identifier(tokens)?;
+        let arguments = split_arguments(&parse_rest_of_line(tokens));
+        Ok((location.clone(), MacroCall { name, location, arguments }))
+    }
 }
 
 impl ExpandableInstruction for MacroCall {
-    fn expand(&self, context: ExpandableProgram) -> Vec<dyn RelativeInstruction> {
-        let _macro = context.macros.iter().find(|it| it.name == self.name).ok_or(|| todo!("error"))?;
-        return _macro.expand(context, self.arguments).iter().map(|it| it.expand(context)); //add expanded to context
+    fn expand(self: Box<Self>, location: &SourceLocation, context: &ExpandableProgram<'_>, depth: usize) -> Result<Expanded, ExpandError> {
+        let _macro = context.macros.borrow().iter().find(|it| it.name == self.name).cloned()
+            .ok_or_else(|| ExpandError::UnknownMacro { name: self.name.clone(), call: self.location.clone() })?;
+        let in_expansion = |error| ExpandError::InExpansion {
+            error: Box::new(error),
+            name: self.name.clone(),
+            call: self.location.clone(),
+            definition: _macro.definition()
+        };
+        if depth >= MAX_EXPANSION_DEPTH {
+            return Err(ExpandError::RecursionLimit {
+                name: self.name.clone(),
+                call: self.location.clone(),
+                definition: _macro.definition()
+            })
+        }
+        let expansion = context.expansions.get();
+        context.expansions.set(expansion + 1);
+        let body = _macro.expand(&self.arguments, &self.location, expansion)?;
+        let instructions = parse_lines(&mut TokenIterator::new(&body), location.file).map_err(|mut errors| ExpandError::Parse {
+            error: errors.remove(0),
+            name: self.name.clone(),
+            call: self.location.clone(),
+            definition: _macro.definition()
+        })?;
+        let mut result = vec![];
+        for (_, instruction) in instructions {
+            // instructions from a macro are attributed to the line of the call
+            let expanded = context.expand_one(location, instruction, depth + 1).map_err(in_expansion)?;
+            result.extend(expanded.into_iter().map(|it| (location.clone(), it.1)));
+        }
+        Ok(result)
     }
 }
 
-impl<T> ExpandableInstruction for T where T: RelativeInstruction {
-    fn expand(&self, context: ExpandableProgram) -> Vec<dyn RelativeInstruction> { vec![self] }
+impl<T> ExpandableInstruction for T where T: RelativeInstruction + 'static {
+    fn expand(self: Box<Self>, location: &SourceLocation, _context: &ExpandableProgram<'_>, _depth: usize) -> Result<Expanded, ExpandError> {
+        Ok(vec![(location.clone(), self)])
+    }
 }
 
-pub struct ExpandableProgram {
+pub struct ExpandableProgram<'a> {
+    /// Macros defined outside of conditional blocks come first, the others are added as they are expanded.
+    macros: RefCell<Vec<Rc<Macro>>>,
+    instructions: Instructions,
+    /// Constants expanded so far, for the conditions of `.if`, `.elif`, `.ifdef` and `.ifndef`.
+    constants: RefCell<HashMap<String, Expression>>,
+    /// Number of macro expansions so far, used to make labels inside macros unique.
+    expansions: Cell<usize>,
+    /// Number of conditional blocks whose selected branch is being expanded, they may guard against include cycles.
+    conditionals: Cell<usize>,
+    /// Where `.include` and `.incbin` find their files once they are expanded, `None` if they are not allowed.
+    sources: Option<RefCell<&'a mut Sources>>
+}
+
+impl<'a> ExpandableProgram<'a> {
+    fn new(parsed: ParsedProgram, sources: Option<&'a mut Sources>) -> Self {
+        ExpandableProgram {
+            macros: RefCell::new(parsed.macros.into_iter().map(Rc::new).collect()),
+            instructions: parsed.instructions,
+            constants: RefCell::new(HashMap::new()),
+            expansions: Cell::new(0),
+            conditionals: Cell::new(0),
+            sources: sources.map(RefCell::new)
+        }
+    }
+
+    /// Expands `instruction` and records the constants it defines.
+    fn expand_one(&self, location: &SourceLocation, instruction: Box<dyn ExpandableInstruction>, depth: usize) -> Result<Expanded, ExpandError> {
+        let expanded = instruction.expand(location, self, depth)?;
+        let mut constants = self.constants.borrow_mut();
+        for (name, expression) in expanded.iter().filter_map(|it| it.1.constant()) {
+            constants.entry(name.clone()).or_insert_with(|| expression.clone());
+        }
+        Ok(expanded)
+    }
+
+    /// Expands every instruction, `defines` are constants set on the command line.
+    pub fn expand(mut self, defines: Vec<(String, Expression)>) -> Result<RelativeProgram, ExpandError> {
+        let instructions = std::mem::take(&mut self.instructions);
+        let mut result: Expanded = vec![];
+        for (name, expression) in defines {
+            self.constants.borrow_mut().insert(name.clone(), expression.clone());
+            result.push((SourceLocation { file: 0, location: expression.location.clone() }, Box::new(Constant::new(name, expression))));
+        }
+        for (location, instruction) in instructions {
+            let mut expanded = self.expand_one(&location, instruction, 0)
+                .map_err(|error| ExpandError::InFile { file: location.file, error: Box::new(error) })?;
+            result.append(&mut expanded);
+        }
+        Ok(RelativeProgram(result))
+    }
+}
+
+enum Line {
+    Definition(Macro),
+    Conditional(ContentLocation, ConditionalLine),
+    Instruction(ContentLocation, Box<dyn ExpandableInstruction>),
+    /// `.include` or `.incbin`, which read their file once they are expanded.
+    File(ContentLocation, Box<dyn ExpandableInstruction>)
+}
+
+fn parse_line(tokens: &mut TokenIterator) -> Result<Line, ParseError> {
+    attempt(tokens, Macro::parse).map(|it| Line::Definition(it.1))
+        .or_else(|_| attempt(tokens, ConditionalLine::parse).map(|it| Line::Conditional(it.0, it.1)))
+        .or_else(|_| attempt(tokens, UserError::parse).map(|it| Line::Instruction(it.0, Box::new(it.1))))
+        .or_else(|_| attempt(tokens, Include::parse).map(|it| Line::File(it.0, Box::new(it.1))))
+        .or_else(|_| attempt(tokens, IncBin::parse).map(|it| Line::File(it.0, Box::new(it.1))))
+        .or_else(|_| attempt(tokens, parse_relative_instruction).map(|it| Line::Instruction(it.0, Box::new(it.1))))
+        .or_else(|err| {
+            if let ParseError::NoTokensLeft = err { return Err(err) }
+            // a line starting with a mnemonic is a malformed instruction rather than a macro call
+            let call = attempt(tokens, |tokens| match MacroCall::parse(tokens) {
+                Ok((_, call)) if !is_instruction_word(&call.name) => Ok(call),
+                _ => Err(ParseError::NoTokensLeft)
+            });
+            call.map(|it| Line::Instruction(it.location.clone(), Box::new(it))).map_err(|_| err)
+        })
+}
+
+type Instructions = Vec<(SourceLocation, Box<dyn ExpandableInstruction>)>;
+
+/// The condition of every branch of a conditional block, `None` for `.else`.
+type Branches = Vec<(Option<Condition>, Instructions)>;
+
+#[derive(Default)]
+struct ParsedProgram {
     macros: Vec<Macro>,
-    instruction: Vec<dyn ExpandableInstruction>
+    instructions: Instructions,
+    /// The `.if` location and branches of every conditional block whose `.endif` was not reached yet, innermost last.
+    open: Vec<(ContentLocation, Branches)>
+}
+
+impl ParsedProgram {
+    fn push(&mut self, location: SourceLocation, instruction: Box<dyn ExpandableInstruction>) {
+        match self.open.last_mut() {
+            Some((_, branches)) => branches.last_mut().unwrap().1.push((location, instruction)),
+            None => self.instructions.push((location, instruction))
+        }
+    }
+
+    /// Macros inside a conditional block are only defined if their branch is expanded.
+    fn define(&mut self, definition: Macro) {
+        if self.open.is_empty() {
+            return self.macros.push(definition)
+        }
+        let location = SourceLocation { file: definition.file, location: definition.location.clone() };
+        self.push(location, Box::new(Definition(Rc::new(definition))))
+    }
+
+    fn conditional(&mut self, file: usize, location: ContentLocation, line: ConditionalLine) -> Result<(), ParseError> {
+        let expected = |pattern_name: &str| ParseError::FailedToMatchPattern { location: location.clone(), pattern_name: pattern_name.to_string() };
+        let branch = match line {
+            ConditionalLine::If(condition) => {
+                self.open.push((location, vec![(Some(condition), vec![])]));
+                return Ok(())
+            },
+            ConditionalLine::ElseIf(condition) => Some(Some(condition)),
+            ConditionalLine::Else => Some(None),
+            ConditionalLine::EndIf => None
+        };
+        let (start, mut branches) = self.open.pop().ok_or_else(|| expected("`.if` first"))?;
+        let Some(branch) = branch else {
+            self.push(SourceLocation { file, location: start }, Box::new(Conditional { branches }));
+            return Ok(())
+        };
+        let has_else = branches.last().is_some_and(|it| it.0.is_none());
+        branches.push((branch, vec![]));
+        self.open.push((start, branches));
+        if has_else { Err(expected("`.endif` after `.else`")) } else { Ok(()) }
+    }
+}
+
+impl ExpandableInstruction for Include {
+    fn expand(self: Box<Self>, location: &SourceLocation, context: &ExpandableProgram<'_>, depth: usize) -> Result<Expanded, ExpandError> {
+        let sources = context.sources.as_ref().expect("`.include` is only parsed together with its sources");
+        let file = {
+            let mut sources = sources.borrow_mut();
+            let error = |message| ExpandError::Include(vec![ParseError::Include { location: location.location.clone(), message }]);
+            sources.find(location.file, &self.0).and_then(|path| sources.include(path, context.conditionals.get())).map_err(error)?
+        };
+        let text = sources.borrow().text(file).to_string();
+        let result = parse_program(&mut TokenIterator::new(&text), file, true)
+            .map_err(|errors| ExpandError::Include(errors.into_iter().map(|it| it.in_file(file)).collect()))
+            .and_then(|included| {
+                // macros of the included file can be called from the point it is included on
+                context.macros.borrow_mut().extend(included.macros.into_iter().map(Rc::new));
+                let mut result = vec![];
+                for (inner, instruction) in included.instructions {
+                    let mut expanded = context.expand_one(&inner, instruction, depth)
+                        .map_err(|error| ExpandError::InFile { file, error: Box::new(error) })?;
+                    result.append(&mut expanded);
+                }
+                Ok(result)
+            });
+        sources.borrow_mut().close();
+        result
+    }
 }
 
+impl ExpandableInstruction for IncBin {
+    fn expand(self: Box<Self>, location: &SourceLocation, context: &ExpandableProgram<'_>, _depth: usize) -> Result<Expanded, ExpandError> {
+        let sources = context.sources.as_ref().expect("`.incbin` is only parsed together with its sources").borrow();
+        let path = sources.find(location.file, &self.name)
+            .map_err(|message| ExpandError::Include(vec![ParseError::Include { location: location.location.clone(), message }]))?;
+        let bytes = self.read(&path).map_err(|error| ExpandError::Include(vec![error]))?;
+        Ok(vec![(location.clone(), Box::new(Data(bytes)))])
+    }
+}
+
+/// Parses line after line, skipping past the line of an error so every error gets reported once.
+/// `.include` and `.incbin` are only allowed with `includes`, they are not allowed inside macros.
+fn parse_program(tokens: &mut TokenIterator, file: usize, includes: bool) -> Result<ParsedProgram, Vec<ParseError>> {
+    let mut errors = vec![];
+    let mut program = ParsedProgram::default();
+    loop {
+        while parse_token(tokens, "\n").is_ok() {}
+        match parse_line(tokens) {
+            Ok(Line::Definition(mut definition)) => {
+                definition.file = file;
+                program.define(definition)
+            },
+            Ok(Line::Conditional(location, line)) => if let Err(err) = program.conditional(file, location, line) {
+                errors.push(err)
+            },
+            Ok(Line::Instruction(location, instruction)) => program.push(SourceLocation { file, location }, instruction),
+            Ok(Line::File(location, instruction)) if includes => program.push(SourceLocation { file, location }, instruction),
+            Ok(Line::File(location, _)) => errors.push(invalid(&location, "instruction (files cannot be included inside macros)")),
+            Err(ParseError::NoTokensLeft) => break,
+            Err(err) => {
+                let line = err.location().map_or(0, |it| it.line());
+                errors.push(err);
+                for token in tokens.by_ref() {
+                    if token == "\n" && token.location().line() >= line { break }
+                }
+                continue
+            }
+        }
+        match parse_token(tokens, "\n") {
+            Ok(_) | Err(ParseError::NoTokensLeft) => {},
+            Err(err) => {
+                // the rest of the line is not parsed again
+                errors.push(err);
+                for token in tokens.by_ref() {
+                    if token == "\n" { break }
+                }
+            }
+        }
+    }
+    for (location, _) in program.open.drain(..) {
+        errors.push(ParseError::FailedToMatchPattern { location, pattern_name: "`.endif` for this `.if`".to_string() });
+    }
+    if errors.is_empty() {
+        Ok(program)
+    } else {
+        Err(errors)
+    }
+}
+
+/// Parses the lines of a macro body called from `file`, which may call but not define macros.
+fn parse_lines(tokens: &mut TokenIterator, file: usize) -> Result<Instructions, Vec<ParseError>> {
+    let program = parse_program(tokens, file, false)?;
+    match program.macros.into_iter().next() {
+        Some(definition) => Err(vec![ParseError::FailedToMatchPattern {
+            location: definition.location,
+            pattern_name: "instruction (macros cannot be defined inside macros)".to_string()
+        }]),
+        None => Ok(program.instructions)
+    }
+}
+
+impl<'a> ExpandableProgram<'a> {
+    pub fn parse_all(tokens: &mut TokenIterator) -> Result<Self, Vec<ParseError>> {
+        parse_program(tokens, 0, false).map(|it| Self::new(it, None))
+    }
+
+    /// Parses an opened file of `sources`, the files it includes are read from `sources` as they are expanded.
+    pub fn parse_file(sources: &'a mut Sources, file: usize) -> Result<Self, Vec<ParseError>> {
+        let text = sources.text(file).to_string();
+        parse_program(&mut TokenIterator::new(&text), file, true).map(|it| Self::new(it, Some(sources)))
+    }
+}
+
+impl Parsable for ExpandableProgram<'_> {
+    fn parse(tokens: &mut TokenIterator) -> Result<(ContentLocation, Self), ParseError> {
+        tokens.push();
+        let location = tokens.next().map(|it| it.location());
+        tokens.pop();
+        let location = location.ok_or(ParseError::NoTokensLeft)?;
+        Self::parse_all(tokens).map(|it| (location, it)).map_err(|mut errors| errors.remove(0))
+    }
+}
@@ -0,0 +1,428 @@
+use std::collections::HashMap;
+use std::ops::RangeInclusive;
+use rpc::ContentLocation;
+use rpc::lexer::{Token, TokenIterator};
+use linker::object::{RelocationKind, Target};
+use crate::data::{parse_directive_word, Data};
+use crate::parser::{attempt, parse_identifier, parse_token, Parsable, ParseError};
+use crate::relative::{FieldKind, LabelName, Labels, RelativeInstruction, UnrelativiceError};
+use crate::Instruction;
+
+/// How deep constants may refer to other constants, deeper chains are assumed to be cyclic.
+const MAX_CONSTANT_DEPTH: usize = 64;
+
+/// Labels and constants known while resolving expressions.
+#[derive(Default)]
+pub struct Symbols {
+    pub labels: Labels,
+    pub constants: HashMap<String, Expression>
+}
+
+#[derive(Clone, Copy)]
+enum UnaryOperator {
+    Negate,
+    Not,
+    /// `hi(x)`, bits 8 to 15
+    High,
+    /// `lo(x)`, bits 0 to 7
+    Low
+}
+
+#[derive(Clone, Copy)]
+enum BinaryOperator {
+    LogicalOr,
+    LogicalAnd,
+    Or,
+    Xor,
+    And,
+    ShiftLeft,
+    ShiftRight,
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Remainder,
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual
+}
+
+/// Binary operators from lowest to highest precedence.
+/// Comparisons and logical operators evaluate to 1 or 0.
+const PRECEDENCE: &[&[(&str, BinaryOperator)]] = &[
+    &[("||", BinaryOperator::LogicalOr)],
+    &[("&&", BinaryOperator::LogicalAnd)],
+    &[("|", BinaryOperator::Or)],
+    &[("^", BinaryOperator::Xor)],
+    &[("&", BinaryOperator::And)],
+    &[("==", BinaryOperator::Equal), ("!=", BinaryOperator::NotEqual)],
+    &[("<=", BinaryOperator::LessOrEqual), (">=", BinaryOperator::GreaterOrEqual), ("<", BinaryOperator::Less), (">", BinaryOperator::Greater)],
+    &[("<<", BinaryOperator::ShiftLeft), (">>", BinaryOperator::ShiftRight)],
+    &[("+", BinaryOperator::Add), ("-", BinaryOperator::Subtract)],
+    &[("*", BinaryOperator::Multiply), ("/", BinaryOperator::Divide), ("%", BinaryOperator::Remainder)]
+];
+
+#[derive(Clone)]
+enum Term {
+    Number(i64),
+    Label(LabelName),
+    Constant(String),
+    Unary(UnaryOperator, Box<Term>),
+    Binary(BinaryOperator, Box<Term>, Box<Term>)
+}
+
+/// A constant expression like `(@table + 2) | MASK`, evaluated once all labels are known.
+#[derive(Clone)]
+pub struct Expression {
+    pub location: ContentLocation,
+    term: Term
+}
+
+/// Parses decimal, `0x` hex, `0b` binary and `0o` octal numbers as well as character literals like `'a'`.
+pub fn parse_number(text: &str) -> Option<i64> {
+    let text = text.trim();
+    if let Some(char) = text.strip_prefix('\'').and_then(|it| it.strip_suffix('\'')) {
+        return parse_char(char)
+    }
+    let (negative, text) = match text.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, text)
+    };
+    let (radix, digits) = match text.get(..2) {
+        Some("0x") | Some("0X") => (16, &text[2..]),
+        Some("0b") | Some("0B") => (2, &text[2..]),
+        Some("0o") | Some("0O") => (8, &text[2..]),
+        _ => (10, text)
+    };
+    let value = i64::from_str_radix(&digits.replace('_', ""), radix).ok()?;
+    Some(if negative { -value } else { value })
+}
+
+fn parse_char(text: &str) -> Option<i64> {
+    let mut chars = text.chars();
+    let char = match (chars.next()?, chars.next()) {
+        ('\\', Some(escaped)) => match escaped {
+            'n' => '\n',
+            't' => '\t',
+            '0' => '\0',
+            '\\' => '\\',
+            '\'' => '\'',
+            _ => return None
+        },
+        (char, None) => return Some(char as i64).filter(|_| char.is_ascii()),
+        _ => return None
+    };
+    if chars.next().is_some() { return None }
+    Some(char as i64)
+}
+
+fn peek(tokens: &mut TokenIterator) -> Option<Token> {
+    tokens.push();
+    let token = tokens.next();
+    tokens.pop();
+    token
+}
+
+fn skip_spaces(tokens: &mut TokenIterator) {
+    while parse_token(tokens, " ").is_ok() {}
+}
+
+fn invalid(location: ContentLocation) -> ParseError {
+    ParseError::FailedToMatchPattern { location, pattern_name: "expression".to_string() }
+}
+
+/// Matches `operator` unless it is only the start of a longer one, like `|` of `||`.
+fn parse_operator(tokens: &mut TokenIterator, operator: &str) -> bool {
+    attempt(tokens, |tokens| {
+        skip_spaces(tokens);
+        for char in operator.chars() {
+            parse_token(tokens, char.to_string().as_str())?;
+        }
+        let next = peek(tokens).map(|it| it.value().to_string()).unwrap_or_default();
+        let longer = PRECEDENCE.iter().flat_map(|it| it.iter())
+            .any(|(symbol, _)| !next.is_empty() && symbol.strip_prefix(operator).is_some_and(|it| it.starts_with(&next)));
+        if longer { Err(ParseError::NoTokensLeft) } else { Ok(()) }
+    }).is_ok()
+}
+
+fn parse_binary(tokens: &mut TokenIterator, level: usize) -> Result<Term, ParseError> {
+    if level == PRECEDENCE.len() {
+        return parse_unary(tokens)
+    }
+    let mut term = parse_binary(tokens, level + 1)?;
+    'outer: loop {
+        for (symbol, operator) in PRECEDENCE[level] {
+            if parse_operator(tokens, symbol) {
+                term = Term::Binary(*operator, Box::new(term), Box::new(parse_binary(tokens, level + 1)?));
+                continue 'outer
+            }
+        }
+        return Ok(term)
+    }
+}
+
+/// Collects the tokens of a number, label or character literal.
+fn parse_word(tokens: &mut TokenIterator, accepts: impl Fn(char) -> bool) -> String {
+    let mut string = String::new();
+    while let Some(token) = peek(tokens) {
+        if !token.value().chars().all(&accepts) { break }
+        string.push_str(token.value());
+        tokens.next();
+    }
+    string
+}
+
+fn parse_unary(tokens: &mut TokenIterator) -> Result<Term, ParseError> {
+    skip_spaces(tokens);
+    let token = peek(tokens).ok_or(ParseError::NoTokensLeft)?;
+    let location = token.location();
+    if token.value().chars().all(|it| it.is_ascii_alphabetic() || it == '_') {
+        let (location, name) = parse_identifier(tokens)?;
+        let function = match name.as_str() {
+            "hi" => Some(UnaryOperator::High),
+            "lo" => Some(UnaryOperator::Low),
+            _ => None
+        };
+        return match function {
+            Some(function) if parse_token(tokens, "(").is_ok() => {
+                let term = parse_binary(tokens, 0)?;
+                skip_spaces(tokens);
+                parse_token(tokens, ")").map_err(|_| invalid(location))?;
+                Ok(Term::Unary(function, Box::new(term)))
+            },
+            _ => Ok(Term::Constant(name))
+        }
+    }
+    tokens.next();
+    if token == "(" {
+        let term = parse_binary(tokens, 0)?;
+        skip_spaces(tokens);
+        parse_token(tokens, ")")?;
+        Ok(term)
+    } else if token == "-" {
+        Ok(Term::Unary(UnaryOperator::Negate, Box::new(parse_unary(tokens)?)))
+    } else if token == "~" {
+        Ok(Term::Unary(UnaryOperator::Not, Box::new(parse_unary(tokens)?)))
+    } else if token == "+" {
+        parse_unary(tokens)
+    } else if token == "@" {
+        let name = parse_word(tokens, |it| it.is_ascii_alphanumeric() || it == '_' || it == '.');
+        if name.is_empty() { return Err(invalid(location)) }
+        Ok(Term::Label(name))
+    } else if token == "'" {
+        let mut text = String::new();
+        for token in tokens.by_ref() {
+            if token == "'" && !(text.ends_with('\\') && text.len() == 1) {
+                return parse_char(&text).map(Term::Number).ok_or_else(|| invalid(location))
+            }
+            if token == "\n" { break }
+            text.push_str(token.value());
+        }
+        Err(invalid(location))
+    } else if token.value().starts_with(|it: char| it.is_ascii_digit()) {
+        let text = token.value().to_string() + &parse_word(tokens, |it| it.is_ascii_alphanumeric() || it == '_');
+        parse_number(&text).map(Term::Number).ok_or_else(|| invalid(location))
+    } else {
+        Err(invalid(location))
+    }
+}
+
+impl Parsable for Expression {
+    fn parse(tokens: &mut TokenIterator) -> Result<(ContentLocation, Self), ParseError> {
+        skip_spaces(tokens);
+        let location = peek(tokens).ok_or(ParseError::NoTokensLeft)?.location();
+        let term = parse_binary(tokens, 0)?;
+        Ok((location.clone(), Expression { location, term }))
+    }
+}
+
+impl UnaryOperator {
+    fn apply(&self, value: i64) -> i64 {
+        match self {
+            UnaryOperator::Negate => value.wrapping_neg(),
+            UnaryOperator::Not => !value,
+            UnaryOperator::High => value >> 8 & 0xFF,
+            UnaryOperator::Low => value & 0xFF
+        }
+    }
+}
+
+impl BinaryOperator {
+    fn apply(&self, left: i64, right: i64, location: &ContentLocation) -> Result<i64, UnrelativiceError> {
+        let shift = u32::try_from(right).unwrap_or(u32::MAX);
+        Ok(match self {
+            BinaryOperator::LogicalOr => (left != 0 || right != 0) as i64,
+            BinaryOperator::LogicalAnd => (left != 0 && right != 0) as i64,
+            BinaryOperator::Or => left | right,
+            BinaryOperator::Xor => left ^ right,
+            BinaryOperator::And => left & right,
+            BinaryOperator::ShiftLeft => left.checked_shl(shift).unwrap_or(0),
+            BinaryOperator::ShiftRight => left.checked_shr(shift).unwrap_or(0),
+            BinaryOperator::Add => left.wrapping_add(right),
+            BinaryOperator::Subtract => left.wrapping_sub(right),
+            BinaryOperator::Multiply => left.wrapping_mul(right),
+            BinaryOperator::Divide | BinaryOperator::Remainder if right == 0 =>
+                return Err(UnrelativiceError::DivisionByZero(location.clone())),
+            BinaryOperator::Divide => left.wrapping_div(right),
+            BinaryOperator::Remainder => left.wrapping_rem(right),
+            BinaryOperator::Equal => (left == right) as i64,
+            BinaryOperator::NotEqual => (left != right) as i64,
+            BinaryOperator::Less => (left < right) as i64,
+            BinaryOperator::LessOrEqual => (left <= right) as i64,
+            BinaryOperator::Greater => (left > right) as i64,
+            BinaryOperator::GreaterOrEqual => (left >= right) as i64
+        })
+    }
+}
+
+impl Term {
+    fn constant<'a>(name: &String, symbols: &'a Symbols, location: &ContentLocation, depth: usize) -> Result<&'a Expression, UnrelativiceError> {
+        let expression = symbols.constants.get(name)
+            .ok_or_else(|| UnrelativiceError::UnknownConstant(name.clone(), location.clone()))?;
+        if depth == MAX_CONSTANT_DEPTH {
+            return Err(UnrelativiceError::RecursiveConstant(name.clone(), location.clone()))
+        }
+        Ok(expression)
+    }
+
+    fn evaluate(&self, symbols: &Symbols, location: &ContentLocation, depth: usize) -> Result<i64, UnrelativiceError> {
+        Ok(match self {
+            Term::Number(value) => *value,
+            Term::Label(name) => *symbols.labels.get(name)
+                .ok_or_else(|| UnrelativiceError::UnknownLabel(name.clone(), location.clone()))? as i64,
+            Term::Constant(name) => {
+                let expression = Term::constant(name, symbols, location, depth)?;
+                expression.term.evaluate(symbols, &expression.location, depth + 1)?
+            },
+            Term::Unary(operator, term) => operator.apply(term.evaluate(symbols, location, depth)?),
+            Term::Binary(operator, left, right) =>
+                operator.apply(left.evaluate(symbols, location, depth)?, right.evaluate(symbols, location, depth)?, location)?
+        })
+    }
+
+    /// The value relative to what it `target`s, labels in `sections` are relative to their section, all others are external.
+    /// Only adding to or subtracting from a target keeps it relocatable, the difference of two labels in one section is absolute.
+    fn relocate(
+        &self,
+        symbols: &Symbols,
+        sections: &HashMap<LabelName, String>,
+        location: &ContentLocation,
+        depth: usize
+    ) -> Result<(i64, Option<Target>), UnrelativiceError> {
+        let not_relocatable = || UnrelativiceError::NotRelocatable(location.clone());
+        Ok(match self {
+            Term::Number(value) => (*value, None),
+            Term::Label(name) => match sections.get(name) {
+                Some(section) => (symbols.labels[name] as i64, Some(Target::Section(section.clone()))),
+                None => (0, Some(Target::Symbol(name.clone())))
+            },
+            Term::Constant(name) => {
+                let expression = Term::constant(name, symbols, location, depth)?;
+                expression.term.relocate(symbols, sections, &expression.location, depth + 1)?
+            },
+            Term::Unary(operator, term) => match term.relocate(symbols, sections, location, depth)? {
+                (value, None) => (operator.apply(value), None),
+                _ => return Err(not_relocatable())
+            },
+            Term::Binary(operator, left, right) => {
+                let (left, left_target) = left.relocate(symbols, sections, location, depth)?;
+                let (right, right_target) = right.relocate(symbols, sections, location, depth)?;
+                let value = operator.apply(left, right, location)?;
+                match (operator, left_target, right_target) {
+                    (_, None, None) => (value, None),
+                    (BinaryOperator::Add, Some(target), None) | (BinaryOperator::Add, None, Some(target)) => (value, Some(target)),
+                    (BinaryOperator::Subtract, Some(target), None) => (value, Some(target)),
+                    (BinaryOperator::Subtract, Some(Target::Section(left)), Some(Target::Section(right))) if left == right => (value, None),
+                    _ => return Err(not_relocatable())
+                }
+            }
+        })
+    }
+}
+
+impl Expression {
+    /// Whether this is a plain, possibly negated, number like `-0x10` or `'a'`.
+    pub(crate) fn is_number(&self) -> bool {
+        match &self.term {
+            Term::Number(_) => true,
+            Term::Unary(UnaryOperator::Negate, term) => matches!(term.as_ref(), Term::Number(_)),
+            _ => false
+        }
+    }
+
+    pub fn evaluate(&self, symbols: &Symbols) -> Result<i64, UnrelativiceError> {
+        self.term.evaluate(symbols, &self.location, 0)
+    }
+
+    /// The relocation the linker has to apply to a field of `kind` holding this expression, `None` if it is absolute.
+    /// Bytes can only hold `hi(...)` or `lo(...)` of an address, words the address itself.
+    pub fn relocate(&self, symbols: &Symbols, sections: &HashMap<LabelName, String>, kind: FieldKind) -> Result<Option<(RelocationKind, Target, i64)>, UnrelativiceError> {
+        let (relocation, term) = match &self.term {
+            Term::Unary(UnaryOperator::High, term) => (RelocationKind::High, term.as_ref()),
+            Term::Unary(UnaryOperator::Low, term) => (RelocationKind::Low, term.as_ref()),
+            term => (RelocationKind::Word, term)
+        };
+        let (addend, target) = match term.relocate(symbols, sections, &self.location, 0)? {
+            (_, None) => return Ok(None),
+            (addend, Some(target)) => (addend, target)
+        };
+        let expected = match relocation {
+            RelocationKind::Word => FieldKind::Word,
+            RelocationKind::High | RelocationKind::Low => FieldKind::Byte
+        };
+        if kind != expected {
+            return Err(UnrelativiceError::NotRelocatable(self.location.clone()))
+        }
+        Ok(Some((relocation, target, addend)))
+    }
+
+    /// Evaluates the expression and reports an error at its location if the result is not in `range`.
+    pub fn evaluate_in(&self, symbols: &Symbols, range: RangeInclusive<i64>, expected: &'static str) -> Result<i64, UnrelativiceError> {
+        let value = self.evaluate(symbols)?;
+        if !range.contains(&value) {
+            return Err(UnrelativiceError::OutOfRange { value, expected, location: self.location.clone() })
+        }
+        Ok(value)
+    }
+}
+
+/// `.equ NAME, expr` or `NAME = expr`.
+pub struct Constant {
+    name: String,
+    expression: Expression
+}
+
+impl Constant {
+    pub fn new(name: String, expression: Expression) -> Self {
+        Constant { name, expression }
+    }
+}
+
+impl Parsable for Constant {
+    fn parse(tokens: &mut TokenIterator) -> Result<(ContentLocation, Self), ParseError> {
+        if let Ok((location, _)) = attempt(tokens, |tokens| parse_directive_word(tokens, "equ")) {
+            skip_spaces(tokens);
+            let name = parse_identifier(tokens)?.1;
+            skip_spaces(tokens);
+            parse_token(tokens, ",")?;
+            let expression = Expression::parse(tokens)?.1;
+            return Ok((location, Constant { name, expression }))
+        }
+        let (location, name) = parse_identifier(tokens)?;
+        skip_spaces(tokens);
+        parse_token(tokens, "=")?;
+        let expression = Expression::parse(tokens)?.1;
+        Ok((location, Constant { name, expression }))
+    }
+}
+
+impl RelativeInstruction for Constant {
+    fn unrelativice(self: Box<Self>, _symbols: &Symbols, _address: u16) -> Result<Box<dyn Instruction>, UnrelativiceError> {
+        Ok(Box::new(Data(vec![])))
+    }
+    fn size(&self) -> usize { 0 }
+    fn constant(&self) -> Option<(&String, &Expression)> { Some((&self.name, &self.expression)) }
+}
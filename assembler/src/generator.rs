@@ -1,163 +1,115 @@
-use crate::{NOP, With1Args, WithArg0, WithArg1, Address, Value};
+use isa::{opcode, Operands};
+use crate::{Address, AssemblyProgram, Flag, Register, Value, ADD, AND, BRK, CALL, CMP, HLT, INV, JMP, LDA, LDW, MOV, NOP, OR, POP, PSH, RET, RTI, SHL, SHR, STW, SUB};
 
 pub trait Generable {
-    fn generate(&self) -> &[u8];
+    fn generate(&self) -> Vec<u8>;
     fn size(&self) -> usize;
 }
 
-impl Generable for NOP {
-    fn generate(&self) -> &[u8] {
-        return &[0]
+impl Generable for AssemblyProgram {
+    fn generate(&self) -> Vec<u8> {
+        self.0.iter().flat_map(|it| it.generate()).collect()
     }
-}
-
-pub trait WithCode {
-    fn code(&self) -> u8;
-}
-
-pub trait WithFlag {
-    fn flag(&self) -> u8;
-}
-
-impl<T> Generable for NOP {
-    fn generate(&self) -> &[u8] {
-        &[0]
+    fn size(&self) -> usize {
+        self.0.iter().map(|it| it.size()).sum()
     }
-    fn size(&self) -> usize { 1 }
 }
 
-impl<T> Generable for T where T: WithArg0<Output = Register> + WithArg1<Output = Value> + WithCode + WithFlag {
-    fn generate(&self) -> &[u8] {
-        &[self.code()|self.flag()|self.arg0().generate()[0],self.arg1().generate()[0]]
-    }
-    fn size(&self) -> usize { 2 }
+/// Conversion of an operand into its `isa` counterpart.
+/// Unresolved expressions become a placeholder literal, they only matter for the size until they are resolved.
+trait ToIsa {
+    type Output;
+    fn to_isa(&self) -> Self::Output;
 }
 
-impl<T> Generable for T where T: WithArg0<Output = Register> + WithArg1<Output = Address> + WithCode + WithFlag {
-    fn generate(&self) -> &[u8] {
-        match self.arg1() {
-            Address::HL => &[self.code()|self.flag()|self.arg0().generate()[0]],
-            Address::Literal(address) => {
-                let high = (address >> 8) as u8;
-                let low = (address & 0b11111111) as u8;
-                &[self.code()|self.flag()|self.arg0().generate()[0],high,low]
-            }
-        }
-    }
-    fn size(&self) -> usize {
-        match self.arg1() {
-            Address::HL => 1,
-            Address::Literal(_) => 3,
-        }
-    }
+impl ToIsa for Register {
+    type Output = u8;
+    fn to_isa(&self) -> u8 { self.0 }
 }
 
-impl<T> Generable for T where T: With1Args + WithArg0<Output = Register> + WithCode {
-    fn generate(&self) -> &[u8] {
-        &[self.code()|self.arg0().generate()]
-    }
-    fn size(&self) -> usize { 1 }
+impl ToIsa for Flag {
+    type Output = u8;
+    fn to_isa(&self) -> u8 { self.0 }
 }
 
-impl<T> Generable for T where T: With1Args + WithArg0<Output = Address> + WithCode + WithFlag {
-    fn generate(&self) -> &[u8] {
-        match self.arg0() {
-            Address::HL => &[self.code()|self.flag()|self.arg0().generate()[0]],
-            Address::Literal(address) => {
-                let high = (address >> 8) as u8;
-                let low = (address & 0b11111111) as u8;
-                &[self.code()|self.flag()|self.arg0().generate()[0],high,low]
-            }
-        }
-    }
-    fn size(&self) -> usize {
-        match self.arg0() {
-            Address::HL => 1,
-            Address::Literal(_) => 3,
+impl ToIsa for Value {
+    type Output = isa::Value;
+    fn to_isa(&self) -> isa::Value {
+        match self {
+            Value::Register(register) => isa::Value::Register(register.0),
+            Value::Literal(literal) => isa::Value::Literal(*literal as u8),
+            Value::Expression(_) => isa::Value::Literal(0)
         }
     }
 }
 
-impl<T> Generable for T where T: WithArg0<Output = Flag> + WithArg1<Output = Address> + WithCode + WithFlag {
-    fn generate(&self) -> &[u8] {
-        match self.arg1() {
-            Address::HL => &[self.code()|self.flag()|self.arg0().generate()[0]],
-            Address::Literal(address) => {
-                let high = (address >> 8) as u8;
-                let low = (address & 0b11111111) as u8;
-                &[self.code()|self.flag()|self.arg0().generate()[0],high,low]
-            }
+impl ToIsa for Address {
+    type Output = isa::Address;
+    fn to_isa(&self) -> isa::Address {
+        match self {
+            Address::HL => isa::Address::HL,
+            Address::Literal(address) => isa::Address::Literal(*address),
+            Address::Expression(_) => isa::Address::Literal(0)
         }
     }
-    fn size(&self) -> usize {
-        match self.arg1() {
-            Address::HL => 1,
-            Address::Literal(_) => 3,
-        }
-    }
-}
-
-impl<T> WithFlag for T where T: WithArg1<Output = Value> {
-    fn flag(&self) -> u8 {
-        (match self.arg1() {
-            Value::Register(_) => 0,
-            Value::Literal(_) => 1,
-        }) << 3
-    }
 }
 
-impl<T> WithFlag for T where T: WithArg1<Output = Address> {
-    fn flag(&self) -> u8 {
-        (match self.arg1() {
-            Address::HL => 0,
-            Address::Literal(_) => 1,
-        }) << 3
-    }
-}
-
-impl<T> WithFlag for T where T: WithArg0<Output = Value> {
-    fn flag(&self) -> u8 {
-        (match self.arg0() {
-           Value::Register(_) => 0,
-           Value::Literal(_) => 1,
-        }) << 3
-    }
+macro_rules! isa_impls {
+    ($($struct:ident, $code:expr, $form:ident($($field:tt),*)),*) => {
+        $(
+            impl $struct {
+                fn to_isa(&self) -> isa::Instruction {
+                    isa::Instruction { code: $code, operands: Operands::$form($(self.$field.to_isa()),*) }
+                }
+            }
+            impl Generable for $struct {
+                fn generate(&self) -> Vec<u8> {
+                    isa::encode(&self.to_isa()).expect("operands are checked while parsing")
+                }
+                fn size(&self) -> usize { self.to_isa().size() }
+            }
+        )*
+    };
 }
 
-impl<T> WithFlag for T where T: WithArg0<Output = Address> {
-    fn flag(&self) -> u8 {
-        (match self.arg0() {
-            Address::HL => 0,
-            Address::Literal(_) => 1,
-        }) << 3
-    }
-}
+isa_impls!(
+    MOV, opcode::MOV, RegisterValue(0, 1),
+    LDW, opcode::LDW, RegisterAddress(0, 1),
+    STW, opcode::STW, RegisterAddress(0, 1),
+    LDA, opcode::LDA, Address(0),
+    PSH, opcode::PSH, Value(0),
+    POP, opcode::POP, Register(0),
+    JMP, opcode::JMP, FlagAddress(0, 1),
+    ADD, opcode::ADD, RegisterValue(0, 1),
+    SUB, opcode::SUB, RegisterValue(0, 1),
+    AND, opcode::AND, RegisterValue(0, 1),
+    OR , opcode::OR , RegisterValue(0, 1),
+    INV, opcode::INV, Register(0),
+    CMP, opcode::CMP, RegisterValue(0, 1),
+    SHL, opcode::SHL, RegisterValue(0, 1),
+    SHR, opcode::SHR, RegisterValue(0, 1),
+    CALL, opcode::CALL, Address(0)
+);
 
-macro_rules! code_impls {
-    ($($struct:expr,$code:expr),*) => {
+macro_rules! isa_impls_without_operands {
+    ($($struct:ident, $code:expr),*) => {
         $(
-            impl WithCode for $struct {
-                fn code(&self) -> u8 { $code << 4 }
+            impl Generable for $struct {
+                fn generate(&self) -> Vec<u8> {
+                    isa::encode(&isa::Instruction { code: $code, operands: Operands::None }).expect("no operands to check")
+                }
+                fn size(&self) -> usize {
+                    isa::Instruction { code: $code, operands: Operands::None }.size()
+                }
             }
         )*
     };
 }
 
-code_impls!(
-    MOV, 0x1,
-    LDW, 0x2,
-    STW, 0x3,
-    LDA, 0x4,
-    PSH, 0x5,
-    POP, 0x6,
-    JMP, 0x7,
-    ADD, 0x8,
-    SUB, 0x9,
-    AND, 0xA,
-    OR , 0xB,
-    INV, 0xC,
-    CMP, 0xD,
-    SHL, 0xE,
-    SHR, 0xF
+isa_impls_without_operands!(
+    NOP, opcode::NOP,
+    HLT, opcode::HLT,
+    RET, opcode::RET,
+    BRK, opcode::BRK,
+    RTI, opcode::RTI
 );
-
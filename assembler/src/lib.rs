@@ -0,0 +1,420 @@
+// instructions are named after their mnemonics and errors carry their locations
+#![allow(clippy::upper_case_acronyms, clippy::result_large_err)]
+
+use std::path::Path;
+use std::str::FromStr;
+use rpc::ContentLocation;
+use linker::object::ObjectFile;
+use linker::output::{image, Chunk};
+use crate::parser::{parse_str, Parsable, ParseError};
+use crate::generator::Generable;
+use crate::expandable::ExpandableProgram;
+use crate::macros::ExpandError;
+use crate::expression::Expression;
+use crate::relative::{Labels, RelativeProgram, Resolve, Segment, UnrelativiceError};
+use crate::sources::SourceLocation;
+
+pub use crate::sources::Sources;
+
+mod parser;
+mod generator;
+mod relative;
+mod expandable;
+mod macros;
+mod data;
+mod expression;
+mod sections;
+mod sources;
+
+struct Register(u8);
+
+enum Value {
+    Register(Register),
+    Literal(i8),
+    /// Parsed but not yet resolved literal.
+    Expression(Expression)
+}
+
+enum Address {
+    HL,
+    Literal(u16),
+    /// Parsed but not yet resolved address.
+    Expression(Expression)
+}
+
+struct Flag(u8);
+
+trait Instruction: Parsable + Generable + Resolve {}
+
+trait WithArg0 {
+    type Output: Parsable;
+}
+
+trait WithArg1 {
+    type Output: Parsable;
+}
+
+trait With1Args: WithArg0 + Sized {
+    fn new(arg0: <Self as WithArg0>::Output) -> Self;
+}
+
+trait With2Args: WithArg0 + WithArg1 + Sized {
+    fn new(arg0: <Self as WithArg0>::Output, arg1: <Self as WithArg1>::Output) -> Self;
+}
+
+struct NOP;
+impl Instruction for NOP {}
+
+struct MOV(Register, Value);
+impl Instruction for MOV {}
+impl WithArg0 for MOV {
+    type Output = Register;
+}
+impl WithArg1 for MOV {
+    type Output = Value;
+}
+impl With2Args for MOV {
+    fn new(arg0: <Self as WithArg0>::Output, arg1: <Self as WithArg1>::Output) -> Self {
+        MOV(arg0, arg1)
+    }
+}
+
+struct LDW(Register, Address);
+impl Instruction for LDW {}
+impl WithArg0 for LDW {
+    type Output = Register;
+}
+impl WithArg1 for LDW {
+    type Output = Address;
+}
+impl With2Args for LDW {
+    fn new(arg0: <Self as WithArg0>::Output, arg1: <Self as WithArg1>::Output) -> Self {
+        LDW(arg0, arg1)
+    }
+}
+
+struct STW(Register, Address);
+impl Instruction for STW {}
+impl WithArg0 for STW {
+    type Output = Register;
+}
+impl WithArg1 for STW {
+    type Output = Address;
+}
+impl With2Args for STW {
+    fn new(arg0: <Self as WithArg0>::Output, arg1: <Self as WithArg1>::Output) -> Self {
+        STW(arg0, arg1)
+    }
+}
+
+struct LDA(Address);
+impl Instruction for LDA {}
+impl WithArg0 for LDA {
+    type Output = Address;
+}
+impl With1Args for LDA {
+    fn new(arg0: <Self as WithArg0>::Output) -> Self { LDA(arg0) }
+}
+
+struct PSH(Value);
+impl Instruction for PSH {}
+impl WithArg0 for PSH {
+    type Output = Value;
+}
+impl With1Args for PSH {
+    fn new(arg0: <Self as WithArg0>::Output) -> Self { PSH(arg0) }
+}
+
+struct POP(Register);
+impl Instruction for POP {}
+impl WithArg0 for POP {
+    type Output = Register;
+}
+impl With1Args for POP {
+    fn new(arg0: <Self as WithArg0>::Output) -> Self { POP(arg0) }
+}
+
+struct JMP(Flag, Address);
+impl Instruction for JMP {}
+impl WithArg0 for JMP {
+    type Output = Flag;
+}
+impl WithArg1 for JMP {
+    type Output = Address;
+}
+impl With2Args for JMP {
+    fn new(arg0: <Self as WithArg0>::Output, arg1: <Self as WithArg1>::Output) -> Self {
+        JMP(arg0, arg1)
+    }
+}
+
+struct ADD(Register, Value);
+impl Instruction for ADD {}
+impl WithArg0 for ADD {
+    type Output = Register;
+}
+impl WithArg1 for ADD {
+    type Output = Value;
+}
+impl With2Args for ADD {
+    fn new(arg0: <Self as WithArg0>::Output, arg1: <Self as WithArg1>::Output) -> Self {
+        ADD(arg0, arg1)
+    }
+}
+
+struct SUB(Register, Value);
+impl Instruction for SUB {}
+impl WithArg0 for SUB {
+    type Output = Register;
+}
+impl WithArg1 for SUB {
+    type Output = Value;
+}
+impl With2Args for SUB {
+    fn new(arg0: <Self as WithArg0>::Output, arg1: <Self as WithArg1>::Output) -> Self {
+        SUB(arg0, arg1)
+    }
+}
+
+struct AND(Register, Value);
+impl Instruction for AND {}
+impl WithArg0 for AND {
+    type Output = Register;
+}
+impl WithArg1 for AND {
+    type Output = Value;
+}
+impl With2Args for AND {
+    fn new(arg0: <Self as WithArg0>::Output, arg1: <Self as WithArg1>::Output) -> Self {
+        AND(arg0, arg1)
+    }
+}
+
+struct OR(Register, Value);
+impl Instruction for OR {}
+impl WithArg0 for OR {
+    type Output = Register;
+}
+impl WithArg1 for OR {
+    type Output = Value;
+}
+impl With2Args for OR {
+    fn new(arg0: <Self as WithArg0>::Output, arg1: <Self as WithArg1>::Output) -> Self {
+        OR(arg0, arg1)
+    }
+}
+
+struct INV(Register);
+impl Instruction for INV {}
+impl WithArg0 for INV {
+    type Output = Register;
+}
+impl With1Args for INV {
+    fn new(arg0: <Self as WithArg0>::Output) -> Self { INV(arg0) }
+}
+
+struct CMP(Register, Value);
+impl Instruction for CMP {}
+impl WithArg0 for CMP {
+    type Output = Register;
+}
+impl WithArg1 for CMP {
+    type Output = Value;
+}
+impl With2Args for CMP {
+    fn new(arg0: <Self as WithArg0>::Output, arg1: <Self as WithArg1>::Output) -> Self {
+        CMP(arg0, arg1)
+    }
+}
+
+struct SHL(Register, Value);
+impl Instruction for SHL {}
+impl WithArg0 for SHL {
+    type Output = Register;
+}
+impl WithArg1 for SHL {
+    type Output = Value;
+}
+impl With2Args for SHL {
+    fn new(arg0: <Self as WithArg0>::Output, arg1: <Self as WithArg1>::Output) -> Self {
+        SHL(arg0, arg1)
+    }
+}
+
+struct SHR(Register, Value);
+impl Instruction for SHR {}
+impl WithArg0 for SHR {
+    type Output = Register;
+}
+impl WithArg1 for SHR {
+    type Output = Value;
+}
+impl With2Args for SHR {
+    fn new(arg0: <Self as WithArg0>::Output, arg1: <Self as WithArg1>::Output) -> Self {
+        SHR(arg0, arg1)
+    }
+}
+
+struct HLT;
+impl Instruction for HLT {}
+
+struct CALL(Address);
+impl Instruction for CALL {}
+impl WithArg0 for CALL {
+    type Output = Address;
+}
+impl With1Args for CALL {
+    fn new(arg0: <Self as WithArg0>::Output) -> Self { CALL(arg0) }
+}
+
+struct RET;
+impl Instruction for RET {}
+
+struct BRK;
+impl Instruction for BRK {}
+
+struct RTI;
+impl Instruction for RTI {}
+
+struct AssemblyProgram(Vec<Box<dyn Instruction>>);
+
+
+/// A constant given on the command line, `NAME=value` with a number as value or just `NAME` which defines it as 1.
+pub struct Define(String, Expression);
+
+impl FromStr for Define {
+    type Err = String;
+    fn from_str(text: &str) -> Result<Self, String> {
+        let (name, value) = text.split_once('=').unwrap_or((text, "1"));
+        let invalid = || format!("invalid define: {}", text);
+        if name.is_empty() || name.starts_with(|it: char| it.is_ascii_digit()) || !name.chars().all(|it| it.is_ascii_alphanumeric() || it == '_') {
+            return Err(invalid())
+        }
+        let value: Expression = parse_str(value.trim()).map_err(|_| invalid())?;
+        if !value.is_number() {
+            return Err(invalid())
+        }
+        Ok(Define(name.to_string(), value))
+    }
+}
+
+enum Error {
+    Parse(Vec<ParseError>),
+    Expand(ExpandError),
+    Unrelativice(UnrelativiceError)
+}
+
+/// Every error found while assembling, `report` formats them with the paths of `Sources`.
+pub struct AssembleError(Box<Error>);
+
+fn expand(sources: &mut Sources, defines: &[Define]) -> Result<RelativeProgram, AssembleError> {
+    let program = ExpandableProgram::parse_file(sources, 0).map_err(|it| AssembleError(Box::new(Error::Parse(it))))?;
+    let defines = defines.iter().map(|it| (it.0.clone(), it.1.clone())).collect();
+    program.expand(defines).map_err(|it| AssembleError(Box::new(Error::Expand(it))))
+}
+
+/// Assembles the main file of `sources` into an image.
+pub fn assemble(sources: &mut Sources, defines: &[Define]) -> Result<Assembly, AssembleError> {
+    let (segments, labels) = expand(sources, defines)?.unrelativice().map_err(|it| AssembleError(Box::new(Error::Unrelativice(it))))?;
+    Ok(Assembly { segments, labels })
+}
+
+/// Assembles the main file of `sources` into a relocatable object for the linker.
+pub fn assemble_object(sources: &mut Sources, defines: &[Define]) -> Result<ObjectFile, AssembleError> {
+    expand(sources, defines)?.object().map_err(|it| AssembleError(Box::new(Error::Unrelativice(it))))
+}
+
+pub struct Assembly {
+    segments: Vec<Segment>,
+    labels: Labels
+}
+
+impl Assembly {
+    /// The bytes of every loaded segment, sorted by address.
+    pub fn chunks(&self) -> Vec<Chunk> {
+        let mut chunks: Vec<Chunk> = self.segments.iter()
+            .filter(|it| it.is_loaded())
+            .map(|it| (it.origin, it.program.generate()))
+            .collect();
+        chunks.sort_by_key(|it| it.0);
+        chunks
+    }
+
+    /// One line per segment with its section, first and last address and size, sorted by address.
+    pub fn map(&self) -> String {
+        let mut segments: Vec<&Segment> = self.segments.iter().collect();
+        segments.sort_by_key(|it| it.start());
+        let mut result = format!("origin 0x{:04X}\n", image(&self.chunks()).0);
+        for segment in segments {
+            result += &format!("{:<8} 0x{:04X} 0x{:04X} {:>5}{}\n",
+                segment.section, segment.start(), segment.end() - 1, segment.size(),
+                if segment.is_loaded() { "" } else { " (not loaded)" });
+        }
+        result
+    }
+
+    /// Lets the emulator show source lines and labels instead of raw addresses, one entry per line:
+    /// `file <index> <path>` per source file, `label <address> <name>` per label sorted by address
+    /// and `line <address> <file index> <line> <source text>` per instruction that emits bytes.
+    pub fn debug_map(&self, sources: &Sources) -> String {
+        let mut result = String::new();
+        for (index, path) in sources.paths().enumerate() {
+            result += &format!("file {} {}\n", index, path.display());
+        }
+        let mut labels: Vec<(&String, &u16)> = self.labels.iter().collect();
+        labels.sort_by_key(|it| (*it.1, it.0));
+        for (name, address) in labels {
+            result += &format!("label 0x{:04X} {}\n", address, name);
+        }
+        let mut lines: Vec<&(u16, SourceLocation)> = self.segments.iter().flat_map(|it| &it.lines).collect();
+        lines.sort_by_key(|it| it.0);
+        for (address, SourceLocation { file, location }) in lines {
+            let text = sources.text(*file).lines().nth(location.line().saturating_sub(1)).unwrap_or("").trim();
+            result += &format!("line 0x{:04X} {} {} {}\n", address, file, location.line(), text);
+        }
+        result
+    }
+}
+
+fn format_location(path: &Path, location: &ContentLocation) -> String {
+    format!("{}:{}:{}", path.display(), location.line(), location.column())
+}
+
+/// `error` at `location` in `file`, or just with the path of the file if there is no location.
+fn report_at(sources: &Sources, file: Option<usize>, location: Option<&ContentLocation>, error: &dyn std::fmt::Display) -> String {
+    let path = sources.path(file.unwrap_or(0));
+    match location {
+        Some(location) => format!("{}: error: {}\n", format_location(path, location), error),
+        None => format!("{}: error: {}\n", path.display(), error)
+    }
+}
+
+fn report_expand_error(sources: &Sources, file: usize, error: &ExpandError) -> String {
+    match error {
+        ExpandError::InFile { file, error } => return report_expand_error(sources, *file, error),
+        ExpandError::InExpansion { error: inner, name, call, .. } => {
+            return report_expand_error(sources, file, inner)
+                + &format!("{}: note: in expansion of macro `{}`\n", format_location(sources.path(file), call), name)
+        },
+        ExpandError::Include(errors) => {
+            return errors.iter().map(|error| report_at(sources, Some(error.file().unwrap_or(file)), error.location(), error)).collect()
+        },
+        _ => {}
+    }
+    let mut result = report_at(sources, Some(file), error.call(), error);
+    if let Some(definition) = error.definition() {
+        result += &format!("{}: note: macro defined here\n", format_location(sources.path(definition.file), &definition.location));
+    }
+    result
+}
+
+impl AssembleError {
+    /// One line per error and note, prefixed with `path:line:column`.
+    pub fn report(&self, sources: &Sources) -> String {
+        match self.0.as_ref() {
+            Error::Parse(errors) => errors.iter().map(|error| report_at(sources, error.file(), error.location(), error)).collect(),
+            Error::Expand(error) => report_expand_error(sources, 0, error),
+            Error::Unrelativice(error) => report_at(sources, error.file(), error.location(), error)
+        }
+    }
+}
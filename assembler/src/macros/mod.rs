@@ -1,57 +1,349 @@
-use crate::{parser::Parsable, expandable::{ExpandableProgram, MacroArgument}, With2Args};
+use std::fmt::{Display, Formatter};
+use rpc::ContentLocation;
+use rpc::lexer::TokenIterator;
+use crate::parser::{parse_identifier, parse_rest_of_line, parse_str, parse_token, Parsable, ParseError};
+use crate::relative::{parse_relative_instruction, LabelName, UnrelativiceError};
+use crate::sources::SourceLocation;
+use crate::{Address, Flag, Register, Value};
 
+/// How deep macros may expand into each other before expansion is aborted.
+pub const MAX_EXPANSION_DEPTH: usize = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum MacroArgumentType {
-    Instruction()
+    Instruction,
+    Register,
+    Value,
+    Address,
+    Flag,
+    Label,
+    /// Parameters without a type annotation accept any text.
+    Any
+}
+
+impl MacroArgumentType {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "instr" => Some(MacroArgumentType::Instruction),
+            "reg" => Some(MacroArgumentType::Register),
+            "value" => Some(MacroArgumentType::Value),
+            "addr" => Some(MacroArgumentType::Address),
+            "flag" => Some(MacroArgumentType::Flag),
+            "label" => Some(MacroArgumentType::Label),
+            _ => None
+        }
+    }
+    pub fn accepts(&self, argument: &str) -> bool {
+        match self {
+            MacroArgumentType::Instruction => {
+                let mut tokens = TokenIterator::new(argument);
+                parse_relative_instruction(&mut tokens).is_ok() && tokens.next().is_none()
+            },
+            MacroArgumentType::Register => parse_str::<Register>(argument).is_ok(),
+            MacroArgumentType::Value => parse_str::<Value>(argument).is_ok(),
+            MacroArgumentType::Address => parse_str::<Address>(argument).is_ok(),
+            MacroArgumentType::Flag => parse_str::<Flag>(argument).is_ok(),
+            MacroArgumentType::Label => parse_str::<LabelName>(argument).is_ok(),
+            MacroArgumentType::Any => true
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ArgumentName(pub String);
+
+pub struct Parameter {
+    name: ArgumentName,
+    argument_type: MacroArgumentType
+}
+
+/// A `.macro name param[:type], ... .endm` definition, parameters are referred to as `\param` in the body.
+/// Labels defined in the body are local to each expansion, `@::label` refers to a label outside the macro.
+pub struct Macro {
+    pub name: String,
+    pub location: ContentLocation,
+    /// The file the macro is defined in.
+    pub file: usize,
+    parameters: Vec<Parameter>,
+    body: String
 }
 
 #[derive(Debug)]
-struct ArgumentName(String);
+pub enum ExpandError {
+    UnknownMacro { name: String, call: ContentLocation },
+    UnknownArgument { argument: ArgumentName, definition: SourceLocation },
+    InsufficientArgumentsSupplied { name: String, expected: usize, found: usize, call: ContentLocation, definition: SourceLocation },
+    WrongArgumentType { argument: ArgumentName, excpected: MacroArgumentType, found: String, call: ContentLocation, definition: SourceLocation },
+    RecursionLimit { name: String, call: ContentLocation, definition: SourceLocation },
+    /// The body did not parse after the arguments were substituted.
+    Parse { error: ParseError, name: String, call: ContentLocation, definition: SourceLocation },
+    /// An error inside the expansion of the macro `name` that was called at `call`.
+    InExpansion { error: Box<ExpandError>, name: String, call: ContentLocation, definition: SourceLocation },
+    /// An error expanding an instruction of an included file.
+    InFile { file: usize, error: Box<ExpandError> },
+    /// The file of an expanded `.include` or `.incbin` could not be read or does not parse.
+    Include(Vec<ParseError>),
+    /// The condition of `.if` or `.elif` could not be evaluated.
+    Condition(UnrelativiceError),
+    /// `.error "message"` was expanded.
+    User { message: String, location: ContentLocation }
+}
 
-pub trait MacroInstruction: Parsable {
-    fn expand(&self, m_context: &Macro, p_context: &ExpandableProgram, arguments: Vec<MacroArgument>) -> Result<dyn ExpandableInstruction, ExpandError>;
+impl ExpandError {
+    /// Where the macro that failed to expand was called.
+    pub fn call(&self) -> Option<&ContentLocation> {
+        match self {
+            ExpandError::UnknownArgument { .. } => None,
+            ExpandError::UnknownMacro { call, .. } |
+            ExpandError::InsufficientArgumentsSupplied { call, .. } |
+            ExpandError::WrongArgumentType { call, .. } |
+            ExpandError::RecursionLimit { call, .. } |
+            ExpandError::Parse { call, .. } |
+            ExpandError::InExpansion { call, .. } => Some(call),
+            ExpandError::InFile { error, .. } => error.call(),
+            ExpandError::Condition(error) => error.location(),
+            ExpandError::Include(errors) => errors.first().and_then(ParseError::location),
+            ExpandError::User { location, .. } => Some(location)
+        }
+    }
+    /// Where the macro that failed to expand was defined.
+    pub fn definition(&self) -> Option<&SourceLocation> {
+        match self {
+            ExpandError::UnknownMacro { .. } | ExpandError::Condition(_) | ExpandError::Include(_) | ExpandError::User { .. } => None,
+            ExpandError::UnknownArgument { definition, .. } |
+            ExpandError::InsufficientArgumentsSupplied { definition, .. } |
+            ExpandError::WrongArgumentType { definition, .. } |
+            ExpandError::RecursionLimit { definition, .. } |
+            ExpandError::Parse { definition, .. } |
+            ExpandError::InExpansion { definition, .. } => Some(definition),
+            ExpandError::InFile { error, .. } => error.definition()
+        }
+    }
 }
 
-impl MacroInstruction for ArgumentName {
-    fn expand(&self, m_context: &Macro, p_context: &ExpandableProgram, arguments: Vec<MacroArgument>) -> Result<dyn ExpandableInstruction, ExpandError> {
-        let index = m_context.argument_index(self);
-        let argument = arguments[index];
-        let excpected = MacroArgumentType::Instruction();
-        let found = argument.macro_type();
-        if found == excpected {
-            return Ok(argument as ExpandableInstruction);
+impl Display for ExpandError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ExpandError::UnknownMacro { name, .. } => write!(f, "unknown macro `{}`", name),
+            ExpandError::UnknownArgument { argument, .. } => write!(f, "unknown macro argument `\\{}`", argument.0),
+            ExpandError::InsufficientArgumentsSupplied { name, expected, found, .. } =>
+                write!(f, "macro `{}` takes {} arguments but {} were supplied", name, expected, found),
+            ExpandError::WrongArgumentType { argument, excpected, found, .. } =>
+                write!(f, "argument `{}` expects {:?} but found `{}`", argument.0, excpected, found),
+            ExpandError::RecursionLimit { name, .. } =>
+                write!(f, "expanding `{}` exceeded the nesting limit of {}", name, MAX_EXPANSION_DEPTH),
+            ExpandError::Parse { error, name, .. } => write!(f, "expansion of `{}` does not parse: {}", name, error),
+            ExpandError::InExpansion { error, .. } |
+            ExpandError::InFile { error, .. } => write!(f, "{}", error),
+            ExpandError::Condition(error) => write!(f, "condition cannot be evaluated: {}", error),
+            ExpandError::Include(errors) => write!(f, "{}", errors[0]),
+            ExpandError::User { message, .. } => write!(f, "{}", message)
+        }
+    }
+}
+
+impl Macro {
+    pub fn definition(&self) -> SourceLocation {
+        SourceLocation { file: self.file, location: self.location.clone() }
+    }
+
+    pub fn argument_index(&self, name: &ArgumentName) -> Result<usize, ExpandError> {
+        self.parameters.iter().position(|it| &it.name == name)
+            .ok_or_else(|| ExpandError::UnknownArgument { argument: name.clone(), definition: self.definition() })
+    }
+
+    /// Checks `arguments` against the parameters and returns the body with every `\param` replaced.
+    /// Labels defined in the body are renamed using `expansion`, which has to be unique per call.
+    pub fn expand(&self, arguments: &[String], call: &ContentLocation, expansion: usize) -> Result<String, ExpandError> {
+        if arguments.len() != self.parameters.len() {
+            return Err(ExpandError::InsufficientArgumentsSupplied {
+                name: self.name.clone(),
+                expected: self.parameters.len(),
+                found: arguments.len(),
+                call: call.clone(),
+                definition: self.definition()
+            })
         }
-        return Err(ExpandError::WrongArgumentType { excpected, found });
+        for (parameter, argument) in self.parameters.iter().zip(arguments) {
+            if !parameter.argument_type.accepts(argument) {
+                return Err(ExpandError::WrongArgumentType {
+                    argument: parameter.name.clone(),
+                    excpected: parameter.argument_type,
+                    found: argument.clone(),
+                    call: call.clone(),
+                    definition: self.definition()
+                })
+            }
+        }
+        self.substitute(&mangle_labels(&self.body, expansion), arguments)
+    }
+
+    /// Replaces every `\param` outside of string and character literals, whose escapes are left alone.
+    fn substitute(&self, body: &str, arguments: &[String]) -> Result<String, ExpandError> {
+        let mut result = String::new();
+        let mut chars = body.chars().peekable();
+        while let Some(char) = chars.next() {
+            match char {
+                '"' | '\'' => {
+                    result.push(char);
+                    while let Some(next) = chars.next() {
+                        result.push(next);
+                        if next == '\\' {
+                            result.extend(chars.next());
+                        } else if next == char || next == '\n' {
+                            break
+                        }
+                    }
+                },
+                '\\' if chars.peek().is_some_and(|it| it.is_ascii_alphabetic() || *it == '_') => {
+                    let mut name = String::new();
+                    while let Some(next) = chars.next_if(|it| it.is_ascii_alphanumeric() || *it == '_') {
+                        name.push(next);
+                    }
+                    result += &arguments[self.argument_index(&ArgumentName(name))?];
+                },
+                _ => result.push(char)
+            }
+        }
+        Ok(result)
     }
 }
 
-impl<T> MacroInstruction for T where T: ExpandableInstruction {
-    fn expand(&self, m_context: &Macro, p_context: &ExpandableProgram, arguments: Vec<MacroArgument>) -> Result<dyn ExpandableInstruction, ExpandError> {
-        Ok(self)
+/// Splits the arguments of a macro call at commas that are not inside quotes or parentheses.
+pub fn split_arguments(text: &str) -> Vec<String> {
+    if text.trim().is_empty() {
+        return vec![]
     }
+    let mut arguments = vec![];
+    let mut argument = String::new();
+    let mut depth = 0usize;
+    let mut chars = text.chars();
+    while let Some(char) = chars.next() {
+        match char {
+            '"' | '\'' => {
+                argument.push(char);
+                while let Some(next) = chars.next() {
+                    argument.push(next);
+                    if next == '\\' {
+                        argument.extend(chars.next());
+                    } else if next == char {
+                        break
+                    }
+                }
+                continue
+            },
+            '(' => depth += 1,
+            ')' => depth = depth.saturating_sub(1),
+            ',' if depth == 0 => {
+                arguments.push(argument.trim().to_string());
+                argument.clear();
+                continue
+            },
+            _ => {}
+        }
+        argument.push(char);
+    }
+    arguments.push(argument.trim().to_string());
+    arguments
 }
 
-pub struct Macro {
-    name: String,
-    arguments: Vec<ArgumentName>,
-    instructions: Vec<dyn MacroInstruction>
+fn is_label_char(char: char) -> bool {
+    char.is_ascii_alphanumeric() || char == '_' || char == '.'
 }
 
-#[derive(Debug)]
-pub enum ExpandError {
-    UnknownArgument(ArgumentName),
-    InsufficientArgumentsSupplied(),
-    WrongArgumentType { excpected: MacroArgumentType, found: MacroArgumentType }
+/// Length of a string or character literal opened by `quote` that continues with `text`,
+/// up to and including the closing quote or the end of the line. Escaped quotes do not close it.
+fn literal_length(text: &str, quote: char) -> usize {
+    let mut chars = text.char_indices();
+    while let Some((index, char)) = chars.next() {
+        if char == '\\' {
+            chars.next();
+        } else if char == quote || char == '\n' {
+            return index + char.len_utf8()
+        }
+    }
+    text.len()
 }
 
-impl Macro {
-    fn expand(&self, context: &ExpandableProgram, arguments: Vec<MacroArgument>) -> Result<dyn RelativeInstruction, ExpandError> {
-        self.instructions.iter().map(|it| it.expand(self, context, arguments)?);
-        todo!("handle recursive macro expantion")
+/// Renames every label defined in `body` to `label.expansion` so repeated expansions do not clash.
+/// `@::label` refers to the label outside of the macro and is left as `@label`, literals are left alone.
+fn mangle_labels(body: &str, expansion: usize) -> String {
+    let local: Vec<&str> = body.lines()
+        .filter_map(|it| it.trim_start().strip_prefix('@'))
+        .filter_map(|it| it.split_once(':').map(|it| it.0))
+        .filter(|it| !it.is_empty() && it.chars().all(is_label_char))
+        .collect();
+    let mut result = String::new();
+    let mut rest = body;
+    while let Some(index) = rest.find(['@', '"', '\'']) {
+        result += &rest[..=index];
+        let quote = rest.as_bytes()[index] as char;
+        rest = &rest[index + 1..];
+        if quote != '@' {
+            let length = literal_length(rest, quote);
+            result += &rest[..length];
+            rest = &rest[length..];
+            continue
+        }
+        if let Some(global) = rest.strip_prefix("::") {
+            rest = global;
+            continue
+        }
+        let length = rest.find(|it: char| !is_label_char(it)).unwrap_or(rest.len());
+        result += &rest[..length];
+        if local.contains(&&rest[..length]) {
+            result += &format!(".{}", expansion);
+        }
+        rest = &rest[length..];
     }
-    fn argument_index(&self, name: ArgumentName) -> Result<usize, ExpandError> {
-        let index = self.arguments.iter().enumerate()
-            .find(|it| it.0 == name.0).ok_or(|| Err(ExpandError::UnknownArgument(name)))?.0;
-        return Ok(index);
+    result += rest;
+    result
+}
+
+fn parse_parameter(tokens: &mut TokenIterator) -> Result<Parameter, ParseError> {
+    let name = ArgumentName(parse_identifier(tokens)?.1);
+    if parse_token(tokens, ":").is_err() {
+        return Ok(Parameter { name, argument_type: MacroArgumentType::Any })
     }
+    let (location, type_name) = parse_identifier(tokens)?;
+    let argument_type = MacroArgumentType::from_name(&type_name).ok_or(ParseError::FailedToMatchPattern {
+        location,
+        pattern_name: "argument type (instr, reg, value, addr, flag or label)".to_string()
+    })?;
+    Ok(Parameter { name, argument_type })
 }
 
+fn skip_spaces(tokens: &mut TokenIterator) {
+    while parse_token(tokens, " ").is_ok() {}
+}
+
+impl Parsable for Macro {
+    fn parse(tokens: &mut TokenIterator) -> Result<(ContentLocation, Self), ParseError> {
+        let location = parse_token(tokens, ".")?;
+        for char in "macro".chars() {
+            parse_token(tokens, char.to_string().as_str())?;
+        }
+        skip_spaces(tokens);
+        let name = parse_identifier(tokens)?.1;
+        skip_spaces(tokens);
+        let mut parameters = vec![];
+        while parse_token(tokens, "\n").is_err() {
+            parameters.push(parse_parameter(tokens)?);
+            skip_spaces(tokens);
+            if parse_token(tokens, ",").is_ok() {
+                skip_spaces(tokens);
+            }
+        }
+        let mut body = String::new();
+        loop {
+            let line = parse_rest_of_line(tokens);
+            if line.trim() == ".endm" {
+                break
+            }
+            parse_token(tokens, "\n")?;
+            body += &line;
+            body += "\n";
+        }
+        Ok((location.clone(), Macro { name, location, file: 0, parameters, body }))
+    }
+}
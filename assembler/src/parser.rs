@@ -1,232 +1,356 @@
-use std::process::Output;
-use std::str::FromStr;
-use rpc::{ContentLocation, WithContentLocation};
+use std::fmt::{Display, Formatter};
+use rpc::ContentLocation;
 use rpc::lexer::{TokenIterator, Token};
-use crate::{ADD, AND, AssemblyProgram, CMP, Flag, Instruction, INV, JMP, LDA, LDW, MOV, NOP, OR, POP, PSH, Register, SHL, SHR, STW, SUB, Value, With1Args, With2Args, WithArg0, WithArg1};
+use isa::{flag, opcode, register};
+use crate::expression::Expression;
+use crate::{ADD, Address, AND, AssemblyProgram, BRK, CALL, CMP, Flag, HLT, Instruction, INV, JMP, LDA, LDW, MOV, NOP, OR, POP, PSH, Register, RET, RTI, SHL, SHR, STW, SUB, Value, With1Args, With2Args, WithArg0, WithArg1};
 
 #[derive(Debug)]
 pub enum ParseError {
     NoTokensLeft,
     UnexpectedToken { location: ContentLocation, expected: String },
-    FailedToMatchPattern { location: ContentLocation, pattern_name: String }
+    FailedToMatchPattern { location: ContentLocation, pattern_name: String },
+    /// A register or flag number that does not exist, like `reg9`.
+    OutOfRange { location: ContentLocation, found: String, expected: String },
+    /// `.include` or `.incbin` could not read its file.
+    Include { location: ContentLocation, message: String },
+    /// An error in an included file.
+    InFile { file: usize, error: Box<ParseError> }
 }
 
-pub trait Pattern {
-    type Output;
-    fn match_pattern(tokens: &mut TokenIterator) -> Result<(ContentLocation, Self::Output), ParseError>;
+impl ParseError {
+    pub fn location(&self) -> Option<&ContentLocation> {
+        match self {
+            ParseError::NoTokensLeft => None,
+            ParseError::UnexpectedToken { location, .. } => Some(location),
+            ParseError::FailedToMatchPattern { location, .. } => Some(location),
+            ParseError::OutOfRange { location, .. } => Some(location),
+            ParseError::Include { location, .. } => Some(location),
+            ParseError::InFile { error, .. } => error.location(),
+        }
+    }
+    /// The file the error is in, `None` for the main file.
+    pub fn file(&self) -> Option<usize> {
+        match self {
+            ParseError::InFile { file, .. } => Some(*file),
+            _ => None
+        }
+    }
+    /// Attributes the error to `file` unless it already belongs to a file included from there.
+    pub fn in_file(self, file: usize) -> Self {
+        match self {
+            ParseError::InFile { .. } => self,
+            error => ParseError::InFile { file, error: Box::new(error) }
+        }
+    }
 }
 
-pub trait Parsable {
-    fn parse(tokens: &mut TokenIterator) -> Result<(ContentLocation, Self), ParseError>;
+impl Display for ParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseError::NoTokensLeft => write!(f, "unexpected end of input"),
+            ParseError::UnexpectedToken { expected, .. } => write!(f, "expected `{}`", expected.escape_debug()),
+            ParseError::FailedToMatchPattern { pattern_name, .. } => write!(f, "expected {}", pattern_name),
+            ParseError::OutOfRange { found, expected, .. } => write!(f, "`{}` does not exist, expected {}", found, expected),
+            ParseError::Include { message, .. } => write!(f, "{}", message),
+            ParseError::InFile { error, .. } => write!(f, "{}", error),
+        }
+    }
 }
 
-impl<T> Pattern for T where T: Parsable {
-    type Output = Self;
-    fn match_pattern(tokens: &mut TokenIterator) -> Result<(ContentLocation, Self::Output), ParseError> {
-        <Self as Parsable>::parse(tokens)
-    }
+pub trait Parsable {
+    fn parse(tokens: &mut TokenIterator) -> Result<(ContentLocation, Self), ParseError> where Self: Sized;
 }
 
 pub fn parse_token(tokens: &mut TokenIterator, token_definition: &str) -> Result<ContentLocation, ParseError> {
+    tokens.push();
     let next_token: Option<Token> = tokens.next();
     if let Some(token) = next_token {
         if token == token_definition {
             tokens.spop();
-            token.location();
+            return Ok(token.location());
         } else {
             tokens.pop();
-            Err(ParseError::UnexpectedToken {
+            return Err(ParseError::UnexpectedToken {
                 location: token.location(),
                 expected: token_definition.to_string()
             })
         }
     }
     tokens.pop();
-    return Err(ParseError::NoTokensLeft)
+    Err(ParseError::NoTokensLeft)
 }
 
-impl Parsable for Register {
-    fn parse(tokens: &mut TokenIterator) -> Result<(ContentLocation, Self), ParseError> {
+/// Runs `parse` and rewinds `tokens` to where it started if it fails.
+pub fn attempt<T>(tokens: &mut TokenIterator, parse: impl FnOnce(&mut TokenIterator) -> Result<T, ParseError>) -> Result<T, ParseError> {
+    tokens.push();
+    let result = parse(tokens);
+    if result.is_ok() { tokens.spop() } else { tokens.pop() }
+    result
+}
+
+/// Falls back to `parse` after a failed alternative, unless that alternative matched but named an operand that
+/// does not exist, which is reported as is.
+pub fn attempt_instead<T>(error: ParseError, tokens: &mut TokenIterator, parse: impl FnOnce(&mut TokenIterator) -> Result<T, ParseError>) -> Result<T, ParseError> {
+    match error {
+        ParseError::OutOfRange { .. } => Err(error),
+        _ => attempt(tokens, parse)
+    }
+}
+
+/// Parses a name made of ascii letters, digits and underscores that does not start with a digit.
+pub fn parse_identifier(tokens: &mut TokenIterator) -> Result<(ContentLocation, String), ParseError> {
+    let mut location = None;
+    let mut string = String::new();
+    loop {
         tokens.push();
-        let next = tokens.next().ok_or(ParseError::NoTokensLeft)?;
-        fn map_error(_: ParseError) -> ParseError {
-            ParseError::FailedToMatchPattern {
-                location: next.content_location(),
-                pattern_name: "register".to_string()?
+        match tokens.next() {
+            Some(token) if token.value().chars().all(|it| it.is_ascii_alphanumeric() || it == '_')
+                && !(string.is_empty() && token.value().starts_with(|it: char| it.is_ascii_digit())) => {
+                location.get_or_insert(token.location());
+                string.push_str(token.value());
+                tokens.spop();
+            },
+            Some(token) => {
+                tokens.pop();
+                if string.is_empty() {
+                    return Err(ParseError::FailedToMatchPattern { location: token.location(), pattern_name: "identifier".to_string() })
+                }
+                break
+            },
+            None => {
+                tokens.pop();
+                if string.is_empty() { return Err(ParseError::NoTokensLeft) }
+                break
             }
         }
-        if next == "r" {
-            parse_token(tokens, "e").map_err(map_error);
-            parse_token(tokens, "g").map_err(map_error);
-            let num_token = tokens.next().ok_or(ParseError::NoTokensLeft)?;
-            Ok((next.content_location(), Register(u8::from_str(num_token.value().as_str())?)))
-            //TODO: impl aliases
-        } else { Err(ParseError::FailedToMatchPattern {
-            location: next.content_location(),
-            pattern_name: "register".to_string()
-        }) }
     }
+    Ok((location.unwrap(), string))
 }
 
-fn parse_i8_literal(tokens: &mut TokenIterator) -> Result<(ContentLocation, i8), ParseError> {
-    let mut content_location = Option::None;
+/// Returns the text up to, but not including, the next line break.
+pub fn parse_rest_of_line(tokens: &mut TokenIterator) -> String {
     let mut string = String::new();
-    while let Some(token) = tokens.next() {
-        if content_location == None { content_location = token.content_location() }
-        if token == " " { break }
-        string += token.value();
+    loop {
+        tokens.push();
+        match tokens.next() {
+            Some(token) if token != "\n" => {
+                string.push_str(token.value());
+                tokens.spop();
+            },
+            _ => {
+                tokens.pop();
+                return string
+            }
+        }
+    }
+}
+
+/// Parses `T` from the whole of `text`, failing if anything is left over.
+pub fn parse_str<T: Parsable>(text: &str) -> Result<T, ParseError> {
+    let mut tokens = TokenIterator::new(text);
+    let (location, result) = T::parse(&mut tokens)?;
+    match tokens.next() {
+        None => Ok(result),
+        Some(token) => Err(ParseError::UnexpectedToken { location: token.location(), expected: "end of operand".to_string() })
+    }.map_err(|err| match err {
+        ParseError::NoTokensLeft => ParseError::FailedToMatchPattern { location, pattern_name: text.to_string() },
+        _ => err
+    })
+}
+
+/// Parses `<prefix><N>` with `N < count` or one of `aliases`, the index into `aliases` is the number.
+fn parse_numbered(tokens: &mut TokenIterator, prefix: &str, count: usize, aliases: &[Option<&str>], kind: &str) -> Result<(ContentLocation, u8), ParseError> {
+    let (location, name) = parse_identifier(tokens)?;
+    if let Some(index) = aliases.iter().position(|it| *it == Some(name.as_str())) {
+        return Ok((location, index as u8))
+    }
+    match name.strip_prefix(prefix).filter(|it| !it.is_empty() && it.chars().all(|it| it.is_ascii_digit())) {
+        Some(number) => match number.parse::<u8>() {
+            Ok(number) if (number as usize) < count => Ok((location, number)),
+            _ => Err(ParseError::OutOfRange { location, found: name, expected: format!("{}0 to {}{}", prefix, prefix, count - 1) })
+        },
+        None => Err(ParseError::FailedToMatchPattern { location, pattern_name: kind.to_string() })
+    }
+}
+
+impl Parsable for Register {
+    fn parse(tokens: &mut TokenIterator) -> Result<(ContentLocation, Self), ParseError> {
+        let aliases = register::ALIASES;
+        parse_numbered(tokens, "reg", register::NAMES.len(), &aliases, "register").map(|it| (it.0, Register(it.1)))
     }
-    return Ok((
-        content_location.ok_or(|_| panic!())?,
-        i8::from_str(string.as_str()).map_err(|_| ParseError::FailedToMatchPattern {
-            location: content_location.ok_or(|_| panic!())?,
-            pattern_name: "i8 literal".to_string()
-        })?
-    ))
 }
 
 impl Parsable for Value {
     fn parse(tokens: &mut TokenIterator) -> Result<(ContentLocation, Self), ParseError> {
-        Register::parse(tokens).map(|it| (it.0, Value::Register(it.1)))
-            .or_else(|_| parse_i8_literal(tokens).map(|it| (it.0, Value::Literal(it.1))))
+        attempt(tokens, Register::parse).map(|it| (it.0, Value::Register(it.1)))
+            .or_else(|error| match error {
+                ParseError::OutOfRange { .. } => Err(error),
+                _ => Expression::parse(tokens).map(|it| (it.0, Value::Expression(it.1)))
+            })
             .map_err(|error| match error {
                 ParseError::FailedToMatchPattern { location, .. } =>
                     ParseError::FailedToMatchPattern { location, pattern_name: "value".to_string() },
-                _ => panic!()
+                _ => error
             })
     }
 }
 
-impl Parsable for Flag {
+impl Parsable for Address {
     fn parse(tokens: &mut TokenIterator) -> Result<(ContentLocation, Self), ParseError> {
-        tokens.push();
-        let next = tokens.next().ok_or(ParseError::NoTokensLeft)?;
-        fn map_error(_: ParseError) -> ParseError {
-            ParseError::FailedToMatchPattern {
-                location: next.content_location(),
-                pattern_name: "flag".to_string()?
-            }
+        let hl = attempt(tokens, |tokens| match parse_identifier(tokens)? {
+            (location, name) if name == "hl" => Ok(location),
+            (location, _) => Err(ParseError::FailedToMatchPattern { location, pattern_name: "hl".to_string() })
+        });
+        if let Ok(location) = hl {
+            return Ok((location, Address::HL))
         }
-        if next == "f" {
-            parse_token(tokens, "l").map_err(map_error);
-            parse_token(tokens, "a").map_err(map_error);
-            parse_token(tokens, "g").map_err(map_error);
-            let num_token = tokens.next().ok_or(ParseError::NoTokensLeft)?;
-            Ok((next.content_location(), Flag(u8::from_str(num_token.value().as_str())?)))
-            //TODO: impl aliases
-        } else { Err(ParseError::FailedToMatchPattern {
-            location: next.content_location(),
-            pattern_name: "register".to_string()
-        }) }
+        Expression::parse(tokens).map(|it| (it.0, Address::Expression(it.1)))
+            .map_err(|error| match error {
+                ParseError::FailedToMatchPattern { location, .. } =>
+                    ParseError::FailedToMatchPattern { location, pattern_name: "address".to_string() },
+                _ => error
+            })
+    }
+}
+
+impl Parsable for Flag {
+    fn parse(tokens: &mut TokenIterator) -> Result<(ContentLocation, Self), ParseError> {
+        let aliases = flag::ALIASES.map(Some);
+        parse_numbered(tokens, "flag", flag::COUNT as usize, &aliases, "flag").map(|it| (it.0, Flag(it.1)))
     }
 }
 
-trait ParseInstructionWord {
+pub trait ParseInstructionWord {
     fn parse_instruction_word(tokens: &mut TokenIterator) -> Result<ContentLocation, ParseError>;
 }
 
-trait InstructionWord {
-    fn instruction_word() -> &str;
+pub trait InstructionWord {
+    fn instruction_word() -> &'static str;
 }
 
 impl<T> ParseInstructionWord for T where T: InstructionWord {
     fn parse_instruction_word(tokens: &mut TokenIterator) -> Result<ContentLocation, ParseError> {
         let word = Self::instruction_word();
-        let location = parse_token(tokens, word[0]).map_err(|err| {
-            if let ParseError::UnexpectedToken { location, .. } = err {
-                return ParseError::FailedToMatchPattern { location, pattern_name: word.to_string() }
-            } else { return ParseError::NoTokensLeft }
-        })?;
-        for i in 1..word.len() {
-            parse_token(tokens, word[i]).map_err(|err| {
+        let mut location = None;
+        for char in word.chars() {
+            let char_location = parse_token(tokens, char.to_string().as_str()).map_err(|err| {
                 if let ParseError::UnexpectedToken { location, .. } = err {
-                    return ParseError::FailedToMatchPattern { location, pattern_name: word.to_string() }
-                } else { return ParseError::NoTokensLeft }
+                    ParseError::FailedToMatchPattern { location, pattern_name: word.to_string() }
+                } else { ParseError::NoTokensLeft }
             })?;
+            location.get_or_insert(char_location);
         }
-        return Ok(location)
+        Ok(location.unwrap())
     }
 }
 
-impl<T> Parsable for T where T: With1Args + ParseInstructionWord {
-    fn parse(tokens: &mut TokenIterator) -> Result<(ContentLocation, Self), ParseError> {
-        let content_location = Self::parse_instruction_word(tokens)?;
-        parse_token(tokens, " ")?;
-        let arg0 = <Self as WithArg0>::Output::parse(tokens)?.1;
-        return Ok((content_location, Self::new(arg0)))
-    }
+fn parse_1_args<T: With1Args + ParseInstructionWord>(tokens: &mut TokenIterator) -> Result<(ContentLocation, T), ParseError> {
+    let content_location = T::parse_instruction_word(tokens)?;
+    parse_token(tokens, " ")?;
+    let arg0 = <T as WithArg0>::Output::parse(tokens)?.1;
+    Ok((content_location, T::new(arg0)))
 }
 
-impl<T> Parsable for T where T: With2Args + ParseInstructionWord {
-    fn parse(tokens: &mut TokenIterator) -> Result<(ContentLocation, Self), ParseError> {
-        let content_location = Self::parse_instruction_word(tokens)?;
-        parse_token(tokens, " ")?;
-        let arg0 = <Self as WithArg0>::Output::parse(tokens)?.1;
-        parse_token(tokens, " ")?;
-        let arg1 = <Self as WithArg1>::Output::parse(tokens)?.1;
-        return Ok((content_location, Self::new(arg0, arg1)))
-    }
+fn parse_2_args<T: With2Args + ParseInstructionWord>(tokens: &mut TokenIterator) -> Result<(ContentLocation, T), ParseError> {
+    let content_location = T::parse_instruction_word(tokens)?;
+    parse_token(tokens, " ")?;
+    let arg0 = <T as WithArg0>::Output::parse(tokens)?.1;
+    parse_token(tokens, " ")?;
+    let arg1 = <T as WithArg1>::Output::parse(tokens)?.1;
+    Ok((content_location, T::new(arg0, arg1)))
 }
 
-impl InstructionWord for NOP {
-    fn instruction_word() -> &str { "nop" }
+macro_rules! parsable_impls {
+    ($parse:ident: $($struct:ident),*) => {
+        $(
+            impl Parsable for $struct {
+                fn parse(tokens: &mut TokenIterator) -> Result<(ContentLocation, Self), ParseError> { $parse(tokens) }
+            }
+        )*
+    };
 }
 
-impl Parsable for NOP {
-    fn parse(tokens: &mut TokenIterator) -> Result<(ContentLocation, Self), ParseError> {
-        let content_location = Self::parse_instruction_word(tokens)?;
-        Ok((content_location, NOP))
-    }
+parsable_impls!(parse_1_args: LDA, PSH, POP, INV, CALL);
+parsable_impls!(parse_2_args: MOV, LDW, STW, JMP, ADD, SUB, AND, OR, CMP, SHL, SHR);
+
+macro_rules! parsable_without_operands {
+    ($($struct:ident),*) => {
+        $(
+            impl Parsable for $struct {
+                fn parse(tokens: &mut TokenIterator) -> Result<(ContentLocation, Self), ParseError> {
+                    let content_location = Self::parse_instruction_word(tokens)?;
+                    Ok((content_location, $struct))
+                }
+            }
+        )*
+    };
 }
 
+parsable_without_operands!(NOP, HLT, RET, BRK, RTI);
+
 macro_rules! instruction_words {
-    ($($struct:expr,$string:expr),*) => {
+    ($($struct:ident,$code:expr),*) => {
         $(
             impl InstructionWord for $struct {
-                fn instruction_word() -> &str { $string }
+                fn instruction_word() -> &'static str { isa::Opcode::from_code($code).expect("every code has an opcode").mnemonic }
             }
         )*
     };
 }
 
 instruction_words!(
-    MOV, "mov",
-    LDW, "ldw",
-    STW, "stw",
-    LDA, "ldw",
-    PSH, "psh",
-    POP, "pop",
-    JMP, "jmp",
-    ADD, "add",
-    SUB, "sub",
-    AND, "and",
-    OR , "or" ,
-    INV, "inv",
-    CMP, "cmp",
-    SHL, "shl",
-    SHR, "shr"
+    NOP, opcode::NOP,
+    MOV, opcode::MOV,
+    LDW, opcode::LDW,
+    STW, opcode::STW,
+    LDA, opcode::LDA,
+    PSH, opcode::PSH,
+    POP, opcode::POP,
+    JMP, opcode::JMP,
+    ADD, opcode::ADD,
+    SUB, opcode::SUB,
+    AND, opcode::AND,
+    OR , opcode::OR ,
+    INV, opcode::INV,
+    CMP, opcode::CMP,
+    SHL, opcode::SHL,
+    SHR, opcode::SHR,
+    HLT, opcode::HLT,
+    CALL, opcode::CALL,
+    RET, opcode::RET,
+    BRK, opcode::BRK,
+    RTI, opcode::RTI
 );
 
+/// Whether `word` is a mnemonic, lines starting with one are never macro calls.
+pub fn is_instruction_word(word: &str) -> bool {
+    isa::Opcode::from_mnemonic(word).is_some()
+}
+
 macro_rules! parse_instruction_m {
-    ($tokens:expr,$head:expr,$($tail:expr),*) => {
-        $head::parse($tokens).map(|it| it as (ContentLocation, &dyn Instruction))
-            $(.or_else(|_| $tail::parse($tokens).map(|it| it as (ContentLocation, &dyn Instruction))))*
+    ($tokens:expr,$head:ident,$($tail:ident),*) => {
+        attempt($tokens, $head::parse).map(|it| (it.0, Box::new(it.1) as Box<dyn Instruction>))
+            $(.or_else(|error| attempt_instead(error, $tokens, $tail::parse).map(|it| (it.0, Box::new(it.1) as Box<dyn Instruction>))))*
     };
 }
 
+pub fn parse_instruction(tokens: &mut TokenIterator) -> Result<(ContentLocation, Box<dyn Instruction>), ParseError> {
+    parse_instruction_m!(tokens, NOP, MOV, LDW, STW, LDA, PSH, POP, JMP, ADD, SUB, AND, OR, INV, CMP, SHL, SHR, HLT, CALL, RET, BRK, RTI)
+}
+
 impl Parsable for AssemblyProgram {
     fn parse(tokens: &mut TokenIterator) -> Result<(ContentLocation, Self), ParseError> {
         let mut content_location: Option<ContentLocation> = None;
-        let mut result: Vec<&dyn Instruction> = vec![];
+        let mut result: Vec<Box<dyn Instruction>> = vec![];
         loop {
-            let next =
-                parse_instruction_m!(tokens, NOP, MOV, LDW, STW, LDA, PSH, POP, JMP, ADD, SUB, AND, OR, INV, CMP, SHL, SHR);
+            let next = parse_instruction(tokens);
             match next {
                 Ok(instruction) => {
-                    if let None() = content_location {
+                    if content_location.is_none() {
                         content_location = Some(instruction.0)
                     }
-                    result += instruction.1
+                    result.push(instruction.1)
                 },
                 Err(err) => match err {
                     ParseError::NoTokensLeft => break,
@@ -234,14 +358,10 @@ impl Parsable for AssemblyProgram {
                 }
             }
             match parse_token(tokens, "\n") {
-                Err(err) => match err {
-                    ParseError::NoTokensLeft => {},
-                    _ => return Err(err)
-                }
-                _ => {}
+                Ok(_) | Err(ParseError::NoTokensLeft) => {},
+                Err(err) => return Err(err)
             }
         }
-        return Ok((content_location.ok_or(|| ParseError::NoTokensLeft)?, AssemblyProgram(result)))
+        Ok((content_location.ok_or(ParseError::NoTokensLeft)?, AssemblyProgram(result)))
     }
 }
-
@@ -1,99 +1,429 @@
-use crate::parser::Parsable;
-use crate::{Flag, Register, Instruction, Register, With2Args, WithArg1, WithArg0};
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use rpc::ContentLocation;
+use crate::expression::{Expression, Symbols};
+use crate::generator::Generable;
+use crate::sources::SourceLocation;
+use crate::{Address, AssemblyProgram, Flag, Instruction, Register, Value, ADD, AND, BRK, CALL, CMP, HLT, INV, JMP, LDA, LDW, MOV, NOP, OR, POP, PSH, RET, RTI, SHL, SHR, STW, SUB};
 
 mod parser;
+mod object;
 
-pub trait RelativeInstructionType {
-    type Output: Instruction + Sized;
-}
+pub use parser::parse_relative_instruction;
+pub use object::Global;
 
-pub trait RelativeInstruction: Parsable + RelativeInstruction {
-    fn is_label(label_name: LabelName) -> bool { false }
-    fn unrelativice(&self, context: &RelativeProgram) -> Self::Output;
+pub trait RelativeInstruction {
+    fn as_labeled(&self) -> Option<&LabeledInstruction> { None }
+    fn unrelativice(self: Box<Self>, symbols: &Symbols, address: u16) -> Result<Box<dyn Instruction>, UnrelativiceError>;
     fn size(&self) -> usize;
+    /// Size when placed at `address`, only differs from `size` for directives like `.align` and `.fill`.
+    /// `symbols` holds the labels defined before the instruction.
+    fn size_at(&self, _symbols: &Symbols, _address: u16) -> Result<usize, UnrelativiceError> { Ok(self.size()) }
+    /// `.section` and `.org` move the location counter instead of emitting bytes.
+    fn layout_directive(&self) -> Option<&LayoutDirective> { None }
+    /// `.equ NAME, expr` and `NAME = expr` define a constant instead of emitting bytes.
+    fn constant(&self) -> Option<(&String, &Expression)> { None }
+    /// `.global` makes labels visible to other objects.
+    fn as_global(&self) -> Option<&Global> { None }
+    /// The byte offset, width and expression of every operand that may need a relocation.
+    fn fields(&self) -> Vec<(usize, FieldKind, &Expression)> { vec![] }
+    /// `.align n` needs its section to start at a multiple of `n`.
+    fn alignment(&self, _symbols: &Symbols) -> Result<Option<u16>, UnrelativiceError> { Ok(None) }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum FieldKind {
+    Byte,
+    Word
 }
 
-type LabelName = String;
+pub enum LayoutDirective {
+    Section(String),
+    Org(Expression)
+}
 
-struct LabeledInstruction<T: RelativeInstruction>(LabelName, T);
+pub type LabelName = String;
 
-impl<T> RelativeInstructionType for LabeledInstruction<T> {
-    type Output = T;
+pub type Labels = HashMap<LabelName, u16>;
+
+#[derive(Debug)]
+pub enum UnrelativiceError {
+    UnknownLabel(LabelName, ContentLocation),
+    /// The location is the second definition.
+    DuplicateLabel(LabelName, ContentLocation),
+    UnknownConstant(String, ContentLocation),
+    DuplicateConstant(String, ContentLocation),
+    RecursiveConstant(String, ContentLocation),
+    OutOfRange { value: i64, expected: &'static str, location: ContentLocation },
+    DivisionByZero(ContentLocation),
+    /// `.align` with a value that is not a power of two up to 0x8000.
+    InvalidAlignment(i64, ContentLocation),
+    AddressSpaceExceeded(String),
+    OverlappingSegments(String, String),
+    /// Only `@label + n`, `hi(...)` and `lo(...)` can be relocated by the linker.
+    NotRelocatable(ContentLocation),
+    OrgInObject(ContentLocation),
+    /// Section starts or `.align` padding kept changing between layout passes.
+    UnstableLayout,
+    /// An error at an instruction of an included file.
+    InFile { file: usize, error: Box<UnrelativiceError> }
 }
 
-impl<T> RelativeInstruction for LabeledInstruction<T> {
-    fn is_label(label_name: LabelName) -> bool {
-        self.0 == label_name
+impl UnrelativiceError {
+    pub fn location(&self) -> Option<&ContentLocation> {
+        match self {
+            UnrelativiceError::UnknownLabel(_, location) |
+            UnrelativiceError::UnknownConstant(_, location) |
+            UnrelativiceError::DuplicateConstant(_, location) |
+            UnrelativiceError::RecursiveConstant(_, location) |
+            UnrelativiceError::OutOfRange { location, .. } |
+            UnrelativiceError::DivisionByZero(location) |
+            UnrelativiceError::InvalidAlignment(_, location) |
+            UnrelativiceError::NotRelocatable(location) |
+            UnrelativiceError::DuplicateLabel(_, location) |
+            UnrelativiceError::OrgInObject(location) => Some(location),
+            UnrelativiceError::AddressSpaceExceeded(_) |
+            UnrelativiceError::OverlappingSegments(_, _) |
+            UnrelativiceError::UnstableLayout => None,
+            UnrelativiceError::InFile { error, .. } => error.location()
+        }
+    }
+    /// The file the error is in, `None` for the main file or errors without a location.
+    pub fn file(&self) -> Option<usize> {
+        match self {
+            UnrelativiceError::InFile { file, .. } => Some(*file),
+            _ => None
+        }
     }
-    fn unrelativice(&self, context: &RelativeProgram) -> Self::Output {
-        self.1.unrelativice(context)
+    /// Attributes the error to the file of the instruction it happened at.
+    fn in_file(self, file: usize) -> Self {
+        match self {
+            UnrelativiceError::InFile { .. } => self,
+            error => UnrelativiceError::InFile { file, error: Box::new(error) }
+        }
     }
-    fn size(&self) -> usize { self.1.size() }
 }
 
-fn unrelativice(context: &RelativeProgram, label_name: LabelName) -> u16 {
-    let index = context.0.iter().enumerate().find(|it| it.is_label(label_name))?.0;
-    let mut size = 0;
-    for i in 0..index {
-        size += context.0[i].size();
+impl Display for UnrelativiceError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            UnrelativiceError::UnknownLabel(name, _) => write!(f, "unknown label `@{}`", name),
+            UnrelativiceError::DuplicateLabel(name, _) => write!(f, "label `@{}` is defined more than once", name),
+            UnrelativiceError::UnknownConstant(name, _) => write!(f, "unknown constant `{}`", name),
+            UnrelativiceError::DuplicateConstant(name, _) => write!(f, "constant `{}` is defined more than once", name),
+            UnrelativiceError::RecursiveConstant(name, _) => write!(f, "constant `{}` is defined in terms of itself", name),
+            UnrelativiceError::OutOfRange { value, expected, .. } => write!(f, "{} does not fit into {}", value, expected),
+            UnrelativiceError::DivisionByZero(_) => write!(f, "division by zero"),
+            UnrelativiceError::InvalidAlignment(value, _) => write!(f, "alignment {} is not a power of two from 1 to 0x8000", value),
+            UnrelativiceError::AddressSpaceExceeded(section) => write!(f, "section `{}` extends past 0xFFFF", section),
+            UnrelativiceError::OverlappingSegments(first, second) => write!(f, "sections `{}` and `{}` overlap", first, second),
+            UnrelativiceError::NotRelocatable(_) => write!(f, "expression cannot be relocated, only `@label + n`, `hi(...)` and `lo(...)` can"),
+            UnrelativiceError::OrgInObject(_) => write!(f, "`.org` cannot be used in a relocatable object"),
+            UnrelativiceError::UnstableLayout => write!(f, "section addresses do not settle, check .org and .align"),
+            UnrelativiceError::InFile { error, .. } => write!(f, "{}", error)
+        }
     }
-    return size as u16;
 }
 
-impl<T, A0> RelativeInstruction for T where T: 
-    With2Args + 
-    WithArg0<Output = A0> + 
-    WithArg1<Output = LabelName> + 
-    RelativeInstructionType<Output = With2Args + WithArg0<A0> + WithArg1<u16>>
-{
-    fn unrelativice(&self, context: &RelativeProgram) -> Self::Output {
-        Self::Output::new(self.arg0(), unrelativice(context, self.arg1()))
-    }
-    fn size(&self) -> usize { 3 }
-} 
+/// `@name: instruction`, with the location of the label.
+pub struct LabeledInstruction(LabelName, ContentLocation, Box<dyn RelativeInstruction>);
 
-impl<T> RelativeInstruction for T where T:
-    WithArg1 +
-    WithArg0<Output = LabelName> +
-    RelativeInstructionType<Output = With2Args + WithArg0<u16>>
-{
-    fn unrelativice(&self, context: &RelativeProgram) -> Self::Output {
-        Self::Output::new(self.arg0(), unrelativice(context, self.arg1()))
+impl RelativeInstruction for LabeledInstruction {
+    fn as_labeled(&self) -> Option<&LabeledInstruction> { Some(self) }
+    fn unrelativice(self: Box<Self>, symbols: &Symbols, address: u16) -> Result<Box<dyn Instruction>, UnrelativiceError> {
+        self.2.unrelativice(symbols, address)
     }
-    fn size(&self) -> usize { 3 }
+    fn size(&self) -> usize { self.2.size() }
+    fn size_at(&self, symbols: &Symbols, address: u16) -> Result<usize, UnrelativiceError> { self.2.size_at(symbols, address) }
+    fn layout_directive(&self) -> Option<&LayoutDirective> { self.2.layout_directive() }
+    fn constant(&self) -> Option<(&String, &Expression)> { self.2.constant() }
+    fn as_global(&self) -> Option<&Global> { self.2.as_global() }
+    fn fields(&self) -> Vec<(usize, FieldKind, &Expression)> { self.2.fields() }
+    fn alignment(&self, symbols: &Symbols) -> Result<Option<u16>, UnrelativiceError> { self.2.alignment(symbols) }
 }
 
-pub struct LDW(Register, LabelName);
+/// Resolves the expressions in an instruction once all symbols are known.
+pub trait Resolve {
+    fn resolve(self: Box<Self>, symbols: &Symbols) -> Result<Box<dyn Instruction>, UnrelativiceError>;
+    /// Operand expressions, they are always encoded right after the opcode byte, which is the second one on extended pages.
+    fn fields(&self) -> Vec<(usize, FieldKind, &Expression)> { vec![] }
+}
 
-pub struct STW(Register, LabelName);
+/// Operands with expressions that have to be evaluated before generating.
+trait ResolveOperand: Sized {
+    fn resolve(self, symbols: &Symbols) -> Result<Self, UnrelativiceError>;
+    fn field(&self) -> Option<(FieldKind, &Expression)> { None }
+}
 
-pub struct LDA(LabelName);
+impl ResolveOperand for Register {
+    fn resolve(self, _symbols: &Symbols) -> Result<Self, UnrelativiceError> { Ok(self) }
+}
 
-pub struct JMP(Flag, LabelName);
+impl ResolveOperand for Flag {
+    fn resolve(self, _symbols: &Symbols) -> Result<Self, UnrelativiceError> { Ok(self) }
+}
 
-macro_rules! relative_instruction_type_impl {
-    ($($relative:expr, $other:expr),*) => {
-        $(impl RelativeInstructionType $relative {
-            type Output = $other;
-        })*
+impl ResolveOperand for Value {
+    fn resolve(self, symbols: &Symbols) -> Result<Self, UnrelativiceError> {
+        Ok(match self {
+            Value::Expression(expression) => Value::Literal(expression.evaluate_in(symbols, -0x80..=0xFF, "a byte")? as u8 as i8),
+            _ => self
+        })
+    }
+    fn field(&self) -> Option<(FieldKind, &Expression)> {
+        match self {
+            Value::Expression(expression) => Some((FieldKind::Byte, expression)),
+            _ => None
+        }
+    }
+}
+
+impl ResolveOperand for Address {
+    fn resolve(self, symbols: &Symbols) -> Result<Self, UnrelativiceError> {
+        Ok(match self {
+            Address::Expression(expression) => Address::Literal(expression.evaluate_in(symbols, 0..=0xFFFF, "an address")? as u16),
+            _ => self
+        })
+    }
+    fn field(&self) -> Option<(FieldKind, &Expression)> {
+        match self {
+            Address::Expression(expression) => Some((FieldKind::Word, expression)),
+            _ => None
+        }
+    }
+}
+
+macro_rules! resolve_impls {
+    ($offset:expr; $($struct:ident($($field:tt),*)),*) => {
+        $(
+            impl Resolve for $struct {
+                fn resolve(self: Box<Self>, _symbols: &Symbols) -> Result<Box<dyn Instruction>, UnrelativiceError> {
+                    Ok(Box::new($struct($(self.$field.resolve(_symbols)?),*)))
+                }
+                fn fields(&self) -> Vec<(usize, FieldKind, &Expression)> {
+                    [$(self.$field.field()),*].into_iter().flatten().map(|(kind, expression)| ($offset, kind, expression)).collect()
+                }
+            }
+        )*
     };
 }
 
-relative_instruction_type_impl!(
-    LDW, crate::LDW,
-    STW, crate::STW,
-    LDA, crate::LDA,
-    JMP, crate::JMP
+resolve_impls!(1;
+    MOV(0, 1),
+    LDW(0, 1),
+    STW(0, 1),
+    LDA(0),
+    PSH(0),
+    POP(0),
+    JMP(0, 1),
+    ADD(0, 1),
+    SUB(0, 1),
+    AND(0, 1),
+    OR(0, 1),
+    INV(0),
+    CMP(0, 1),
+    SHL(0, 1),
+    SHR(0, 1)
 );
 
-impl<T> RelativeInstructionType for T where T: Instruction {
-    type Output = T;
+resolve_impls!(2;
+    CALL(0)
+);
+
+macro_rules! resolve_without_operands {
+    ($($struct:ident),*) => {
+        $(
+            impl Resolve for $struct {
+                fn resolve(self: Box<Self>, _symbols: &Symbols) -> Result<Box<dyn Instruction>, UnrelativiceError> { Ok(self) }
+            }
+        )*
+    };
 }
 
-impl<T> RelativeInstruction for T where T: Instruction {
-    fn unrelativice(&self, context: &RelativeProgram) -> &dyn Instruction { self }
-    fn size(&self) -> usize { <self as Generable>.size() }
+resolve_without_operands!(NOP, HLT, RET, BRK, RTI);
+
+impl<T> RelativeInstruction for T where T: Instruction + 'static {
+    fn unrelativice(self: Box<Self>, symbols: &Symbols, _address: u16) -> Result<Box<dyn Instruction>, UnrelativiceError> {
+        self.resolve(symbols)
+    }
+    fn size(&self) -> usize { <Self as Generable>::size(self) }
+    fn fields(&self) -> Vec<(usize, FieldKind, &Expression)> { Resolve::fields(self) }
 }
 
-pub struct RelativeProgram(Vec<dyn RelativeInstruction>);
+impl RelativeInstruction for Box<dyn Instruction> {
+    fn unrelativice(self: Box<Self>, symbols: &Symbols, _address: u16) -> Result<Box<dyn Instruction>, UnrelativiceError> {
+        (*self).resolve(symbols)
+    }
+    fn size(&self) -> usize { self.as_ref().size() }
+    fn fields(&self) -> Vec<(usize, FieldKind, &Expression)> { Resolve::fields(self.as_ref()) }
+}
+
+/// Every instruction together with the source location it was written at.
+pub struct RelativeProgram(pub Vec<(SourceLocation, Box<dyn RelativeInstruction>)>);
+
+/// A run of instructions that is loaded at `origin` as part of `section`.
+pub struct Segment {
+    pub section: String,
+    pub origin: u16,
+    pub program: AssemblyProgram,
+    /// Address and source location of every instruction that emits bytes.
+    pub lines: Vec<(u16, SourceLocation)>
+}
+
+impl Segment {
+    pub fn size(&self) -> usize { self.program.size() }
+    pub fn start(&self) -> usize { self.origin as usize }
+    /// First address after the segment, may be 0x10000.
+    pub fn end(&self) -> usize { self.start() + self.size() }
+    /// `bss` only reserves memory, it is not part of the image.
+    pub fn is_loaded(&self) -> bool { self.section != "bss" }
+}
+
+pub const DEFAULT_SECTION: &str = "code";
+
+/// How often the layout is repeated to let section starts and `.align` padding settle.
+const MAX_LAYOUT_PASSES: usize = 16;
+
+/// Tracks the location counter of every section while walking the program.
+struct Layout<'a> {
+    sections: Vec<(String, usize)>,
+    current: usize,
+    /// Where each section starts unless it is moved with `.org`.
+    starts: &'a HashMap<String, usize>
+}
+
+impl<'a> Layout<'a> {
+    fn new(starts: &'a HashMap<String, usize>) -> Self {
+        Layout { sections: vec![(DEFAULT_SECTION.to_string(), 0)], current: 0, starts }
+    }
+    fn address(&self) -> usize { self.sections[self.current].1 }
+    fn section(&self) -> &str { &self.sections[self.current].0 }
+    fn apply(&mut self, directive: &LayoutDirective, symbols: &Symbols) -> Result<(), UnrelativiceError> {
+        match directive {
+            LayoutDirective::Section(name) => {
+                self.current = match self.sections.iter().position(|it| &it.0 == name) {
+                    Some(index) => index,
+                    None => {
+                        self.sections.push((name.clone(), self.starts.get(name).copied().unwrap_or(0)));
+                        self.sections.len() - 1
+                    }
+                };
+            },
+            LayoutDirective::Org(address) => self.sections[self.current].1 = address.evaluate_in(symbols, 0..=0xFFFF, "an address")? as usize
+        }
+        Ok(())
+    }
+    fn advance(&mut self, size: usize) -> Result<(), UnrelativiceError> {
+        let section = &mut self.sections[self.current];
+        section.1 += size;
+        if section.1 > 1 << 16 {
+            return Err(UnrelativiceError::AddressSpaceExceeded(section.0.clone()))
+        }
+        Ok(())
+    }
+    /// Sections without an `.org` start where the section that was first used before them ends.
+    fn next_starts(&self) -> HashMap<String, usize> {
+        self.sections.windows(2).map(|it| (it[1].0.clone(), it[0].1)).collect()
+    }
+}
+
+impl RelativeProgram {
+    fn constants(&self) -> Result<HashMap<String, Expression>, UnrelativiceError> {
+        let mut constants = HashMap::new();
+        for (location, instruction) in &self.0 {
+            if let Some((name, expression)) = instruction.constant() {
+                if constants.insert(name.clone(), expression.clone()).is_some() {
+                    return Err(UnrelativiceError::DuplicateConstant(name.clone(), expression.location.clone()).in_file(location.file))
+                }
+            }
+        }
+        Ok(constants)
+    }
+    /// Walks the program once, placing every label at the current address of its section and passing it to `visit`.
+    fn pass<'a>(
+        &self,
+        constants: &HashMap<String, Expression>,
+        starts: &'a HashMap<String, usize>,
+        mut visit: impl FnMut(&LabelName, &str)
+    ) -> Result<(Symbols, Layout<'a>), UnrelativiceError> {
+        let mut symbols = Symbols { labels: Labels::new(), constants: constants.clone() };
+        let mut layout = Layout::new(starts);
+        for (location, instruction) in &self.0 {
+            let mut current = instruction.as_ref();
+            while let Some(labeled) = current.as_labeled() {
+                if symbols.labels.insert(labeled.0.clone(), layout.address() as u16).is_some() {
+                    return Err(UnrelativiceError::DuplicateLabel(labeled.0.clone(), labeled.1.clone()).in_file(location.file));
+                }
+                visit(&labeled.0, layout.section());
+                current = labeled.2.as_ref();
+            }
+            match current.layout_directive() {
+                Some(directive) => layout.apply(directive, &symbols),
+                None => instruction.size_at(&symbols, layout.address() as u16).and_then(|it| layout.advance(it))
+            }.map_err(|it| it.in_file(location.file))?
+        }
+        Ok((symbols, layout))
+    }
+    /// Every constant, the absolute address of every label and where each section starts.
+    /// `.org` may only refer to constants and labels defined before it.
+    fn layout(&self) -> Result<(Symbols, HashMap<String, usize>), UnrelativiceError> {
+        let constants = self.constants()?;
+        let mut starts = HashMap::new();
+        for _ in 0..MAX_LAYOUT_PASSES {
+            let (symbols, layout) = self.pass(&constants, &starts, |_, _| {})?;
+            let next = layout.next_starts();
+            if next == starts {
+                return Ok((symbols, starts))
+            }
+            starts = next;
+        }
+        Err(UnrelativiceError::UnstableLayout)
+    }
+    /// The placed segments and the address of every label.
+    pub fn unrelativice(self) -> Result<(Vec<Segment>, Labels), UnrelativiceError> {
+        let (symbols, starts) = self.layout()?;
+        let mut layout = Layout::new(&starts);
+        let mut segments = vec![Segment { section: DEFAULT_SECTION.to_string(), origin: 0, program: AssemblyProgram(vec![]), lines: vec![] }];
+        for (location, instruction) in self.0 {
+            if let Some(directive) = instruction.layout_directive() {
+                layout.apply(directive, &symbols).map_err(|it| it.in_file(location.file))?;
+                segments.push(Segment {
+                    section: layout.sections[layout.current].0.clone(),
+                    origin: layout.address() as u16,
+                    program: AssemblyProgram(vec![]),
+                    lines: vec![]
+                });
+                continue
+            }
+            let address = layout.address() as u16;
+            let size = instruction.size_at(&symbols, address).map_err(|it| it.in_file(location.file))?;
+            let segment = segments.last_mut().unwrap();
+            segment.program.0.push(instruction.unrelativice(&symbols, address).map_err(|it| it.in_file(location.file))?);
+            if size > 0 {
+                segment.lines.push((address, location));
+            }
+            layout.advance(size)?;
+        }
+        segments.retain(|it| it.size() > 0);
+        for (index, segment) in segments.iter().enumerate() {
+            if let Some(other) = segments[index + 1..].iter().find(|it| it.start() < segment.end() && segment.start() < it.end()) {
+                return Err(UnrelativiceError::OverlappingSegments(segment.section.clone(), other.section.clone()))
+            }
+        }
+        Ok((segments, symbols.labels))
+    }
+}
 
+impl RelativeInstruction for Box<dyn RelativeInstruction> {
+    fn as_labeled(&self) -> Option<&LabeledInstruction> { self.as_ref().as_labeled() }
+    fn unrelativice(self: Box<Self>, symbols: &Symbols, address: u16) -> Result<Box<dyn Instruction>, UnrelativiceError> {
+        (*self).unrelativice(symbols, address)
+    }
+    fn size(&self) -> usize { self.as_ref().size() }
+    fn size_at(&self, symbols: &Symbols, address: u16) -> Result<usize, UnrelativiceError> { self.as_ref().size_at(symbols, address) }
+    fn layout_directive(&self) -> Option<&LayoutDirective> { self.as_ref().layout_directive() }
+    fn constant(&self) -> Option<(&String, &Expression)> { self.as_ref().constant() }
+    fn as_global(&self) -> Option<&Global> { self.as_ref().as_global() }
+    fn fields(&self) -> Vec<(usize, FieldKind, &Expression)> { self.as_ref().fields() }
+    fn alignment(&self, symbols: &Symbols) -> Result<Option<u16>, UnrelativiceError> { self.as_ref().alignment(symbols) }
+}
@@ -0,0 +1,115 @@
+use std::collections::HashMap;
+use rpc::ContentLocation;
+use rpc::lexer::TokenIterator;
+use linker::object::{ObjectFile, Relocation, Section, Symbol, Target};
+use crate::data::{invalid, parse_directive, Data};
+use crate::expression::Symbols;
+use crate::parser::{Parsable, ParseError};
+use crate::Instruction;
+use super::{LabelName, Layout, LayoutDirective, RelativeInstruction, RelativeProgram, UnrelativiceError};
+
+/// `.global @label, ...` exports labels to other objects, it has no effect when assembling an image.
+pub struct Global {
+    location: ContentLocation,
+    labels: Vec<LabelName>
+}
+
+impl Parsable for Global {
+    fn parse(tokens: &mut TokenIterator) -> Result<(ContentLocation, Self), ParseError> {
+        let (location, rest) = parse_directive(tokens, "global")?;
+        let mut labels = vec![];
+        for label in rest.split(',') {
+            let label = label.trim();
+            let label = label.strip_prefix('@').unwrap_or(label);
+            if label.is_empty() || !label.chars().all(|it| it.is_ascii_alphanumeric() || it == '_' || it == '.') {
+                return Err(invalid(&location, "label"))
+            }
+            labels.push(label.to_string());
+        }
+        Ok((location.clone(), Global { location, labels }))
+    }
+}
+
+impl RelativeInstruction for Global {
+    fn unrelativice(self: Box<Self>, _symbols: &Symbols, _address: u16) -> Result<Box<dyn Instruction>, UnrelativiceError> {
+        Ok(Box::new(Data(vec![])))
+    }
+    fn size(&self) -> usize { 0 }
+    fn as_global(&self) -> Option<&Global> { Some(self) }
+}
+
+impl RelativeProgram {
+    /// A relocatable object, every section starts at 0 and labels that are not defined are external.
+    pub fn object(self) -> Result<ObjectFile, UnrelativiceError> {
+        for (location, instruction) in &self.0 {
+            if let Some(LayoutDirective::Org(address)) = instruction.layout_directive() {
+                return Err(UnrelativiceError::OrgInObject(address.location.clone()).in_file(location.file))
+            }
+        }
+        let constants = self.constants()?;
+        let starts = HashMap::new();
+        let mut sections = HashMap::new();
+        let mut order = vec![];
+        let (mut symbols, _) = self.pass(&constants, &starts, |label, section| {
+            sections.insert(label.clone(), section.to_string());
+            order.push(label.clone());
+        })?;
+        let mut object = ObjectFile::default();
+        let mut globals = vec![];
+        let mut layout = Layout::new(&starts);
+        for (location, instruction) in self.0 {
+            let in_file = |error: UnrelativiceError| error.in_file(location.file);
+            if let Some(global) = instruction.as_global() {
+                globals.extend(global.labels.iter().map(|it| (it.clone(), global.location.clone(), location.file)));
+            }
+            if let Some(directive) = instruction.layout_directive() {
+                layout.apply(directive, &symbols).map_err(in_file)?;
+                continue
+            }
+            let address = layout.address();
+            let name = layout.section().to_string();
+            if object.section(&name).is_none() {
+                object.sections.push(Section { name: name.clone(), size: 0, align: 1, data: vec![] });
+            }
+            for (offset, kind, expression) in instruction.fields() {
+                let Some((kind, target, addend)) = expression.relocate(&symbols, &sections, kind).map_err(in_file)? else { continue };
+                if let Target::Symbol(name) = &target {
+                    if !object.externs.contains(name) {
+                        object.externs.push(name.clone());
+                        // only a placeholder, the linker overwrites the field
+                        symbols.labels.insert(name.clone(), 0);
+                    }
+                }
+                object.relocations.push(Relocation { section: name.clone(), offset: (address + offset) as u16, kind, target, addend });
+            }
+            let alignment = instruction.alignment(&symbols).map_err(in_file)?;
+            let size = instruction.size_at(&symbols, address as u16).map_err(in_file)?;
+            let bytes = instruction.unrelativice(&symbols, address as u16).map_err(in_file)?.generate();
+            let section = object.sections.iter_mut().find(|it| it.name == name).unwrap();
+            section.align = section.align.max(alignment.unwrap_or(1));
+            if name != "bss" {
+                section.data.extend(bytes);
+            }
+            layout.advance(size)?;
+            section.size = layout.address();
+        }
+        object.sections.retain(|section| section.size > 0 || sections.values().any(|it| *it == section.name));
+        for name in order.iter().map(|it| &sections[it]) {
+            if object.section(name).is_none() {
+                object.sections.push(Section { name: name.clone(), size: 0, align: 1, data: vec![] });
+            }
+        }
+        for (label, location, file) in &globals {
+            if !sections.contains_key(label) {
+                return Err(UnrelativiceError::UnknownLabel(label.clone(), location.clone()).in_file(*file))
+            }
+        }
+        object.symbols = order.into_iter().map(|name| Symbol {
+            section: sections[&name].clone(),
+            offset: symbols.labels[&name],
+            global: globals.iter().any(|it| it.0 == name),
+            name
+        }).collect();
+        Ok(object)
+    }
+}
@@ -1,40 +1,59 @@
-use crate::{generator::Generable, parser::{parse_token, Parsable}, With2Args, WithArg0, WithArg1};
+use rpc::ContentLocation;
+use rpc::lexer::TokenIterator;
+use crate::parser::{attempt_instead, parse_instruction, parse_token, Parsable, ParseError};
 
-use super::{LabeledInstruction, RelativeInstruction};
+use crate::data::{Align, Bytes, Data, Fill, Words};
+use crate::expression::Constant;
+use crate::sections::Directive;
+use super::{Global, LabeledInstruction, LabelName, RelativeInstruction};
 
-impl<T> Parsable for LabeledInstruction<T> where T: RelativeInstruction {
-    fn parse(tokens: &mut rpc::lexer::TokenIterator) -> Result<(rpc::ContentLocation, Self), crate::parser::ParseError> {
+impl Parsable for LabeledInstruction {
+    fn parse(tokens: &mut TokenIterator) -> Result<(ContentLocation, Self), ParseError> {
         tokens.push();
-        let location = parse_token(tokens, "@").map_err(|err| { tokens.pop(); err })?;
+        let location = parse_token(tokens, "@").inspect_err(|_| tokens.pop())?;
         let mut string = String::new();
         loop {
-            let next = tokens.next().map_err(|err| { tokens.pop(); err })?;
-            if (next == ":") {
+            let next = tokens.next().ok_or_else(|| { tokens.pop(); ParseError::NoTokensLeft })?;
+            if next == ":" {
                 break;
             }
-            string += next;
+            string += next.value();
         }
-        parse_token(tokens, " ").or_else(|_| parse_token(tokens, "\n")).map_err(|err| { tokens.pop(); err })?;
-        let instruction = parse_relative_instruction(tokens).map_err(|err| { tokens.pop(); err })?;
+        parse_token(tokens, " ").or_else(|_| parse_token(tokens, "\n")).inspect_err(|_| tokens.pop())?;
+        let instruction = parse_relative_instruction(tokens).inspect_err(|_| tokens.pop())?.1;
         tokens.spop();
-        return Ok((location, LabeledInstruction(string, instruction)));
+        Ok((location.clone(), LabeledInstruction(string, location, instruction)))
     }
 }
 
 impl Parsable for LabelName {
-    fn parse(tokens: &mut rpc::lexer::TokenIterator) -> Result<(rpc::ContentLocation, Self), crate::parser::ParseError> {
+    fn parse(tokens: &mut TokenIterator) -> Result<(ContentLocation, Self), ParseError> {
         tokens.push();
-        let location = parse_token(tokens, "@")?;
+        let location = parse_token(tokens, "@").inspect_err(|_| tokens.pop())?;
         let mut string = String::new();
-        loop {
-            let next = tokens.next().map_err(|err| { tokens.pop(); err })?;
+        tokens.push();
+        while let Some(next) = tokens.next() {
             if next == " " || next == "\n" {
                 break;
             }
-            string += next;
+            string += next.value();
+            tokens.spop();
+            tokens.push();
         }
+        tokens.pop();
         tokens.spop();
-        return Ok((location, string));
+        Ok((location, string))
     }
 }
 
+macro_rules! parse_relative_instruction_m {
+    ($tokens:expr,$($relative:ident),*) => {
+        Err(ParseError::NoTokensLeft)
+            $(.or_else(|error| attempt_instead(error, $tokens, $relative::parse).map(|it| (it.0, Box::new(it.1) as Box<dyn RelativeInstruction>))))*
+    };
+}
+
+pub fn parse_relative_instruction(tokens: &mut TokenIterator) -> Result<(ContentLocation, Box<dyn RelativeInstruction>), ParseError> {
+    parse_relative_instruction_m!(tokens, LabeledInstruction, Bytes, Data, Fill, Words, Align, Directive, Global, Constant)
+        .or_else(|error| attempt_instead(error, tokens, parse_instruction).map(|it| (it.0, Box::new(it.1) as Box<dyn RelativeInstruction>)))
+}
@@ -0,0 +1,39 @@
+use rpc::ContentLocation;
+use rpc::lexer::TokenIterator;
+use crate::data::{invalid, parse_directive, parse_directive_word, Data};
+use crate::expression::{Expression, Symbols};
+use crate::parser::{attempt, Parsable, ParseError};
+use crate::relative::{LayoutDirective, RelativeInstruction, UnrelativiceError};
+use crate::Instruction;
+
+/// `.org expr`, `.section name` or one of the shorthands `.code`, `.data` and `.bss`.
+pub struct Directive(LayoutDirective);
+
+impl Parsable for Directive {
+    fn parse(tokens: &mut TokenIterator) -> Result<(ContentLocation, Self), ParseError> {
+        if let Ok((location, _)) = attempt(tokens, |tokens| parse_directive_word(tokens, "org")) {
+            let address = Expression::parse(tokens)?.1;
+            return Ok((location, Directive(LayoutDirective::Org(address))))
+        }
+        if let Ok((location, rest)) = attempt(tokens, |tokens| parse_directive(tokens, "section")) {
+            let name = rest.trim();
+            if name.is_empty() || !name.chars().all(|it| it.is_ascii_alphanumeric() || it == '_') {
+                return Err(invalid(&location, "section name"))
+            }
+            return Ok((location, Directive(LayoutDirective::Section(name.to_string()))))
+        }
+        Err(ParseError::NoTokensLeft)
+            .or_else(|_| attempt(tokens, |tokens| parse_directive_word(tokens, "code").map(|it| (it.0, "code"))))
+            .or_else(|_| attempt(tokens, |tokens| parse_directive_word(tokens, "data").map(|it| (it.0, "data"))))
+            .or_else(|_| attempt(tokens, |tokens| parse_directive_word(tokens, "bss").map(|it| (it.0, "bss"))))
+            .map(|(location, name)| (location, Directive(LayoutDirective::Section(name.to_string()))))
+    }
+}
+
+impl RelativeInstruction for Directive {
+    fn unrelativice(self: Box<Self>, _symbols: &Symbols, _address: u16) -> Result<Box<dyn Instruction>, UnrelativiceError> {
+        Ok(Box::new(Data(vec![])))
+    }
+    fn size(&self) -> usize { 0 }
+    fn layout_directive(&self) -> Option<&LayoutDirective> { Some(&self.0) }
+}
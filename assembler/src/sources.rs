@@ -0,0 +1,151 @@
+use std::path::{Path, PathBuf};
+use rpc::ContentLocation;
+use rpc::lexer::TokenIterator;
+use crate::data::{invalid, parse_directive, parse_string};
+use crate::expression::parse_number;
+use crate::parser::{Parsable, ParseError};
+
+/// A location in one of the files of `Sources`, the main file is 0.
+#[derive(Debug, Clone)]
+pub struct SourceLocation {
+    pub file: usize,
+    pub location: ContentLocation
+}
+
+/// How many files can be open at once, the main file included. Only reached by guarded cycles whose guard keeps holding.
+pub const MAX_INCLUDE_DEPTH: usize = 64;
+
+/// Every file read while assembling, indexed in the order they were first opened.
+pub struct Sources {
+    /// Searched in order after the directory of the including file.
+    search_paths: Vec<PathBuf>,
+    files: Vec<(PathBuf, String)>,
+    /// Files that are currently being expanded with the number of conditional blocks they were included in, innermost last.
+    open: Vec<(PathBuf, usize)>
+}
+
+impl Sources {
+    pub fn new(search_paths: Vec<PathBuf>) -> Self {
+        Sources { search_paths, files: vec![], open: vec![] }
+    }
+
+    pub fn path(&self, file: usize) -> &Path { &self.files[file].0 }
+
+    pub fn text(&self, file: usize) -> &str { &self.files[file].1 }
+
+    pub fn paths(&self) -> impl Iterator<Item = &Path> {
+        self.files.iter().map(|it| it.0.as_path())
+    }
+
+    /// Reads `path` and marks it as open until `close`.
+    pub fn open(&mut self, path: PathBuf) -> Result<usize, String> {
+        self.include(path, 0)
+    }
+
+    /// Like `open` for an `.include` expanded inside `conditionals` conditional blocks.
+    /// Including a file that is still open is a cycle, unless a conditional block was entered since it was opened,
+    /// which is expected to guard against including it forever.
+    pub fn include(&mut self, path: PathBuf, conditionals: usize) -> Result<usize, String> {
+        let canonical = path.canonicalize().unwrap_or_else(|_| path.clone());
+        let open = self.open.iter().rposition(|it| it.0 == canonical);
+        if open.is_some_and(|index| self.open[index].1 == conditionals) || self.open.len() >= MAX_INCLUDE_DEPTH {
+            return Err(match open {
+                Some(index) => {
+                    let chain: Vec<String> = self.open[index..].iter().map(|it| &it.0).chain([&canonical])
+                        .map(|it| it.display().to_string()).collect();
+                    format!("recursive include: {}", chain.join(" -> "))
+                },
+                None => format!("includes are nested deeper than {}", MAX_INCLUDE_DEPTH)
+            })
+        }
+        let text = std::fs::read_to_string(&path).map_err(|err| format!("failed to read {}: {}", path.display(), err))?;
+        self.open.push((canonical, conditionals));
+        self.files.push((path, text));
+        Ok(self.files.len() - 1)
+    }
+
+    /// Like `open` with `text` as content instead of reading `path`, includes are still searched next to it.
+    pub fn add(&mut self, path: PathBuf, text: String) -> usize {
+        self.open.push((path.canonicalize().unwrap_or_else(|_| path.clone()), 0));
+        self.files.push((path, text));
+        self.files.len() - 1
+    }
+
+    pub fn close(&mut self) {
+        self.open.pop();
+    }
+
+    /// Looks for `name` next to `from`, then in the search paths.
+    pub fn find(&self, from: usize, name: &str) -> Result<PathBuf, String> {
+        let directory = self.path(from).parent().map(Path::to_path_buf).unwrap_or_default();
+        std::iter::once(&directory).chain(&self.search_paths)
+            .map(|it| it.join(name))
+            .find(|it| it.is_file())
+            .ok_or_else(|| format!("`{}` not found", name))
+    }
+}
+
+/// Splits `"text", rest` into the string literal and whatever follows the comma.
+fn split_string<'a>(location: &ContentLocation, text: &'a str) -> Result<(&'a str, Option<&'a str>), ParseError> {
+    let text = text.trim();
+    let end = text.strip_prefix('"').and_then(|it| it.find('"')).ok_or_else(|| invalid(location, "string literal"))? + 2;
+    match text[end..].trim() {
+        "" => Ok((&text[..end], None)),
+        rest => rest.strip_prefix(',').map(|it| (&text[..end], Some(it))).ok_or_else(|| invalid(location, "`,`"))
+    }
+}
+
+fn parse_path(location: &ContentLocation, text: &str) -> Result<String, ParseError> {
+    String::from_utf8(parse_string(location, text)?).map_err(|_| invalid(location, "file name"))
+}
+
+/// `.include "file.asm"` parses another file as if it was written in place of the directive.
+pub struct Include(pub String);
+
+impl Parsable for Include {
+    fn parse(tokens: &mut TokenIterator) -> Result<(ContentLocation, Self), ParseError> {
+        let (location, rest) = parse_directive(tokens, "include")?;
+        Ok((location.clone(), Include(parse_path(&location, &rest)?)))
+    }
+}
+
+/// `.incbin "file.bin"[, offset[, length]]` places the raw bytes of a file.
+pub struct IncBin {
+    pub name: String,
+    location: ContentLocation,
+    offset: usize,
+    length: Option<usize>
+}
+
+impl Parsable for IncBin {
+    fn parse(tokens: &mut TokenIterator) -> Result<(ContentLocation, Self), ParseError> {
+        let (location, rest) = parse_directive(tokens, "incbin")?;
+        let (name, rest) = split_string(&location, &rest)?;
+        let mut numbers = vec![];
+        for number in rest.map(|it| it.split(',').collect()).unwrap_or(vec![]) {
+            numbers.push(parse_number(number).and_then(|it| usize::try_from(it).ok()).ok_or_else(|| invalid(&location, "offset or length"))?);
+        }
+        let (offset, length) = match numbers.as_slice() {
+            [] => (0, None),
+            [offset] => (*offset, None),
+            [offset, length] => (*offset, Some(*length)),
+            _ => return Err(invalid(&location, "end of line"))
+        };
+        Ok((location.clone(), IncBin { name: parse_path(&location, name)?, location, offset, length }))
+    }
+}
+
+impl IncBin {
+    /// The selected bytes of `path`.
+    pub fn read(&self, path: &Path) -> Result<Vec<u8>, ParseError> {
+        let error = |message: String| ParseError::Include { location: self.location.clone(), message };
+        let bytes = std::fs::read(path).map_err(|err| error(format!("failed to read {}: {}", path.display(), err)))?;
+        let end = match self.length {
+            Some(length) => self.offset.checked_add(length)
+                .ok_or_else(|| error(format!("offset {} and length {} exceed the size of any file", self.offset, length)))?,
+            None => bytes.len().max(self.offset)
+        };
+        bytes.get(self.offset..end).map(<[u8]>::to_vec).ok_or_else(|| error(format!(
+            "{} has {} bytes, cannot take {}..{}", path.display(), bytes.len(), self.offset, end)))
+    }
+}
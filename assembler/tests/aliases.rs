@@ -0,0 +1,41 @@
+mod common;
+
+use common::{bytes, error};
+
+#[test]
+fn register_aliases() {
+    assert_eq!(bytes("mov reg0 h\nmov l pc_h\nmov reg1 pc_l\nmov sp flags\n"), [0x10, 0x02, 0x13, 0x04, 0x11, 0x05, 0x16, 0x07]);
+    assert_eq!(bytes("mov h 1\nmov reg2 1\n"), bytes("mov reg2 1\nmov h 1\n"));
+}
+
+#[test]
+fn flag_aliases() {
+    let names = ["halt", "overflow", "carry", "borrow", "equal", "less", "more", "interrupt"];
+    for (index, name) in names.iter().enumerate() {
+        assert_eq!(bytes(&format!("jmp {} 0x1234\n", name)), bytes(&format!("jmp flag{} 0x1234\n", index)));
+    }
+    assert_eq!(bytes("jmp interrupt 0x1234\n"), [0x7F, 0x12, 0x34]);
+}
+
+#[test]
+fn registers_out_of_range() {
+    let report = error("mov reg8 1\n");
+    assert!(report.contains("main.asm:1:5: error: `reg8` does not exist, expected reg0 to reg7"), "{}", report);
+    let report = error("nop\nadd reg0 reg12\n");
+    assert!(report.contains("main.asm:2:10: error: `reg12` does not exist, expected reg0 to reg7"), "{}", report);
+}
+
+#[test]
+fn flags_out_of_range() {
+    let report = error("@loop:\njmp flag8 @loop\n");
+    assert_eq!(report.lines().count(), 1, "{}", report);
+    assert!(report.contains("main.asm:2:5: error: `flag8` does not exist, expected flag0 to flag7"), "{}", report);
+}
+
+#[test]
+fn every_out_of_range_operand_is_reported() {
+    let report = error("mov reg9 1\nnop\npsh reg8\n");
+    assert!(report.contains("main.asm:1:5: error: `reg9` does not exist"), "{}", report);
+    assert!(report.contains("main.asm:3:5: error: `reg8` does not exist"), "{}", report);
+    assert_eq!(report.lines().count(), 2, "{}", report);
+}
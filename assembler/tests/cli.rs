@@ -0,0 +1,55 @@
+mod common;
+
+use std::process::{Command, Output};
+
+fn run(directory: &std::path::Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_assembler")).current_dir(directory).args(args).output().unwrap()
+}
+
+#[test]
+fn writes_binary_next_to_input() {
+    let directory = common::directory("cli-binary");
+    std::fs::write(directory.join("program.asm"), "mov reg0 1\nhlt\n").unwrap();
+    let output = run(&directory, &["program.asm"]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(std::fs::read(directory.join("program.bin")).unwrap(), [0x18, 0x01, 0x01, 0x00]);
+}
+
+#[test]
+fn formats() {
+    let directory = common::directory("cli-formats");
+    std::fs::write(directory.join("program.asm"), ".org 0x100\n.byte 1, 2\n").unwrap();
+    assert!(run(&directory, &["program.asm", "-f", "text", "-o", "program.txt"]).status.success());
+    assert_eq!(std::fs::read_to_string(directory.join("program.txt")).unwrap(), "0100: 01 02\n");
+    assert!(run(&directory, &["program.asm", "-f", "ihex", "-o", "program.hex"]).status.success());
+    assert_eq!(std::fs::read_to_string(directory.join("program.hex")).unwrap(), ":020100000102FA\n:00000001FF\n");
+}
+
+#[test]
+fn defines_and_map() {
+    let directory = common::directory("cli-defines");
+    std::fs::write(directory.join("program.asm"), ".byte SIZE, FLAG\n").unwrap();
+    let output = run(&directory, &["program.asm", "-D", "SIZE=0x10", "-DFLAG", "--map", "program.map"]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(std::fs::read(directory.join("program.bin")).unwrap(), [0x10, 0x01]);
+    assert_eq!(std::fs::read_to_string(directory.join("program.map")).unwrap(), "origin 0x0000\ncode     0x0000 0x0001     2\n");
+}
+
+#[test]
+fn errors_are_located() {
+    let directory = common::directory("cli-errors");
+    std::fs::write(directory.join("program.asm"), "nop\nmov reg0 300\n").unwrap();
+    let output = run(&directory, &["program.asm"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).starts_with("program.asm:2:"));
+    assert!(!directory.join("program.bin").exists());
+}
+
+#[test]
+fn usage_errors() {
+    let directory = common::directory("cli-usage");
+    for args in [&[][..], &["missing.asm"], &["a.asm", "b.asm"], &["a.asm", "-D", "1X"], &["a.asm", "-D", "X=1+1"], &["a.asm", "-f", "elf"]] {
+        let output = run(&directory, args);
+        assert_eq!(output.status.code(), Some(2), "{:?}", args);
+    }
+}
@@ -0,0 +1,44 @@
+#![allow(dead_code)]
+
+use std::path::PathBuf;
+use assembler::{assemble, Define, Sources};
+
+/// An empty directory for the files of one test.
+pub fn directory(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("assembler-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&path);
+    std::fs::create_dir_all(&path).unwrap();
+    path
+}
+
+/// Assembles `source` as `main.asm` in `directory`, returns the loaded chunks or the reported errors.
+pub fn chunks_in(directory: &std::path::Path, source: &str, defines: &[&str]) -> Result<Vec<(u16, Vec<u8>)>, String> {
+    let defines: Vec<Define> = defines.iter().map(|it| it.parse().unwrap()).collect();
+    let mut sources = Sources::new(vec![]);
+    sources.add(directory.join("main.asm"), source.to_string());
+    assemble(&mut sources, &defines).map(|it| it.chunks()).map_err(|error| error.report(&sources))
+}
+
+pub fn chunks(source: &str) -> Result<Vec<(u16, Vec<u8>)>, String> {
+    chunks_in(&std::env::temp_dir(), source, &[])
+}
+
+/// The bytes of `source`, which has to assemble into a single chunk at 0.
+pub fn bytes(source: &str) -> Vec<u8> {
+    match chunks(source) {
+        Ok(chunks) => match chunks.as_slice() {
+            [] => vec![],
+            [(0, bytes)] => bytes.clone(),
+            _ => panic!("expected a single chunk at 0, got {:?}", chunks)
+        },
+        Err(report) => panic!("{}", report)
+    }
+}
+
+/// The reported errors of `source`, which must not assemble.
+pub fn error(source: &str) -> String {
+    match chunks(source) {
+        Ok(chunks) => panic!("assembled into {:?}", chunks),
+        Err(report) => report
+    }
+}
@@ -0,0 +1,55 @@
+mod common;
+
+use common::{bytes, chunks_in, directory, error};
+
+#[test]
+fn first_branch_that_holds() {
+    let source = |value: u8| format!("X = {}\n.if X == 1\n.byte 1\n.elif X == 2\n.byte 2\n.else\n.byte 3\n.endif\n", value);
+    assert_eq!(bytes(&source(1)), [1]);
+    assert_eq!(bytes(&source(2)), [2]);
+    assert_eq!(bytes(&source(7)), [3]);
+}
+
+#[test]
+fn nested_blocks() {
+    assert_eq!(bytes(".if 1\n.if 0\n.byte 1\n.else\n.byte 2\n.endif\n.byte 3\n.endif\n"), [2, 3]);
+}
+
+#[test]
+fn ifdef_sees_defines() {
+    let source = ".ifdef DEBUG\n.byte DEBUG\n.endif\n.ifndef DEBUG\n.byte 0\n.endif\n";
+    let directory = directory("ifdef");
+    assert_eq!(chunks_in(&directory, source, &["DEBUG=5"]).unwrap(), [(0, vec![5])]);
+    assert_eq!(chunks_in(&directory, source, &[]).unwrap(), [(0, vec![0])]);
+}
+
+#[test]
+fn user_errors_only_when_selected() {
+    assert_eq!(bytes(".if 0\n.error \"unreachable\"\n.endif\nnop\n"), [0]);
+    let report = error("SIZE = 3\n.if SIZE > 2\n.error \"SIZE is too large\"\n.endif\n");
+    assert!(report.contains("main.asm:3:1: error: SIZE is too large"), "{}", report);
+}
+
+#[test]
+fn unbalanced_blocks() {
+    assert!(error(".if 1\nnop\n").contains("main.asm:1:1: error: expected `.endif` for this `.if`"));
+    assert!(error("nop\n.endif\n").contains("main.asm:2:1: error: expected `.if` first"));
+    assert!(error(".if 1\n.else\n.elif 1\n.endif\n").contains("main.asm:3:1: error: expected `.endif` after `.else`"));
+}
+
+#[test]
+fn files_are_only_read_in_selected_branches() {
+    let directory = directory("conditional-include");
+    let source = ".ifdef LIBRARY\n.include \"missing.asm\"\n.incbin \"missing.bin\"\n.endif\nnop\n";
+    assert_eq!(chunks_in(&directory, source, &[]).unwrap(), [(0, vec![0])]);
+    let report = chunks_in(&directory, source, &["LIBRARY=1"]).unwrap_err();
+    assert!(report.contains("main.asm:2:1: error: `missing.asm` not found"), "{}", report);
+}
+
+#[test]
+fn guarded_include_cycle() {
+    let directory = directory("guarded-cycle");
+    std::fs::write(directory.join("a.asm"), ".ifndef A_INCLUDED\nA_INCLUDED = 1\n.include \"b.asm\"\n.byte 0xAA\n.endif\n").unwrap();
+    std::fs::write(directory.join("b.asm"), ".include \"a.asm\"\n.byte 0xBB\n").unwrap();
+    assert_eq!(chunks_in(&directory, ".include \"a.asm\"\n.include \"a.asm\"\nnop\n", &[]).unwrap(), [(0, vec![0xBB, 0xAA, 0])]);
+}
@@ -0,0 +1,30 @@
+mod common;
+
+use common::{bytes, error};
+
+#[test]
+fn bytes_and_words() {
+    assert_eq!(bytes(".byte 1, -1, 0xFF\n.word 0x1234, -2\n"), [1, 0xFF, 0xFF, 0x12, 0x34, 0xFF, 0xFE]);
+    assert_eq!(bytes("@start:\nnop\n.word @start, @next\n@next:\n.byte lo(@next)\n"), [0, 0, 0, 0, 5, 5]);
+}
+
+#[test]
+fn strings_are_terminated_and_escaped() {
+    assert_eq!(bytes(".string \"a\\tb\\n\\\"\\\\\\0\"\n"), [b'a', b'\t', b'b', b'\n', b'"', b'\\', 0, 0]);
+    assert_eq!(bytes(".string \"\"\n"), [0]);
+    assert!(error(".string \"\\q\"\n").contains("main.asm:1:1: error:"));
+}
+
+#[test]
+fn align_pads_to_a_multiple() {
+    assert_eq!(bytes(".byte 1\n.align 4\n.byte 2\n.align 4\n.byte 3\n"), [1, 0, 0, 0, 2, 0, 0, 0, 3]);
+    assert_eq!(bytes("SIZE = 2\n.byte 1\n.align SIZE * 4\n.byte 2\n.align 1\n.byte 3\n"), [1, 0, 0, 0, 0, 0, 0, 0, 2, 3]);
+}
+
+#[test]
+fn align_rejects_other_values() {
+    assert!(error(".align 0\n").contains("main.asm:1:8: error: alignment 0 is not a power of two from 1 to 0x8000"));
+    assert!(error(".align 3\n").contains("main.asm:1:8: error: alignment 3 is not a power of two"));
+    assert!(error(".align 0x10000\n").contains("alignment 65536 is not a power of two"));
+    assert!(error(".align MISSING\n").contains("unknown constant `MISSING`"));
+}
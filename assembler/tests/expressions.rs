@@ -0,0 +1,48 @@
+mod common;
+
+use common::{bytes, error};
+
+#[test]
+fn operators_and_precedence() {
+    assert_eq!(bytes(".byte 1 + 2 * 3, (1 + 2) * 3, 7 / 2, 7 % 2, 1 << 4 | 1, 0xF0 & 0x3C ^ 1\n"), [7, 9, 3, 1, 0x11, 0x31]);
+    assert_eq!(bytes(".byte -1, ~0 & 0xFF, 2 < 3, 2 >= 3, 1 == 1 && 0 != 0, 0 || 5\n"), [0xFF, 0xFF, 1, 0, 0, 1]);
+    assert_eq!(bytes(".byte 0b101, 0o17, 1_0, 'a', '\\n'\n"), [5, 15, 10, b'a', b'\n']);
+}
+
+#[test]
+fn constants() {
+    assert_eq!(bytes("SIZE = COUNT * 2\n.equ COUNT, 3\nmov reg0 SIZE + 1\n"), [0x18, 7]);
+    assert!(error("A = B\nB = A\n.byte A\n").contains("defined in terms of itself"));
+    assert!(error("A = 1\nA = 2\n").contains("main.asm:2:5: error: constant `A` is defined more than once"));
+    assert!(error(".byte MISSING\n").contains("main.asm:1:7: error: unknown constant `MISSING`"));
+}
+
+#[test]
+fn labels_and_hi_lo() {
+    let source = ".org 0x1234\n@start:\nmov h hi(@end)\nmov l lo(@end)\n.word @end - @start, @start\n@end:\nnop\n";
+    assert_eq!(common::chunks(source).unwrap(), [(0x1234, vec![0x1A, 0x12, 0x1B, 0x3C, 0x00, 0x08, 0x12, 0x34, 0x00])]);
+}
+
+#[test]
+fn range_and_division_errors() {
+    assert!(error(".byte 256\n").contains("main.asm:1:7: error: 256 does not fit into a byte"));
+    assert!(error(".word 0x10000\n").contains("65536 does not fit into a word"));
+    assert!(error(".byte 1 / (2 - 2)\n").contains("main.asm:1:7: error: division by zero"));
+}
+
+#[test]
+fn fill_counts_are_expressions() {
+    assert_eq!(bytes("COUNT = 2\n.fill COUNT + 1, 0xAA\n.fill 1\n"), [0xAA, 0xAA, 0xAA, 0]);
+    assert_eq!(bytes("@start:\n.byte 1, 2\n@end:\n.fill @end - @start, '-'\n"), [1, 2, b'-', b'-']);
+    assert_eq!(bytes(".fill 0\n"), []);
+}
+
+#[test]
+fn fill_counts_are_bounded() {
+    assert_eq!(bytes(".fill 0x10000, 1\n").len(), 0x10000);
+    let report = error("nop\n.fill 0xFFFFFFFF\n");
+    assert!(report.contains("main.asm:2:7: error: 4294967295 does not fit into a fill count (0 to 0x10000)"), "{}", report);
+    assert!(error(".fill -1\n").contains("main.asm:1:7: error: -1 does not fit into a fill count"));
+    assert!(error(".fill 1, 256\n").contains("256 does not fit into a byte"));
+    assert!(error(".fill @later\n@later:\nnop\n").contains("main.asm:1:7: error: unknown label `@later`"));
+}
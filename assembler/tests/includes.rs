@@ -0,0 +1,92 @@
+mod common;
+
+use std::path::Path;
+use assembler::{assemble, Sources};
+use common::{chunks_in, directory};
+
+fn write(directory: &Path, name: &str, contents: &[u8]) {
+    let path = directory.join(name);
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    std::fs::write(path, contents).unwrap();
+}
+
+#[test]
+fn includes_are_found_next_to_the_including_file() {
+    let directory = directory("include-relative");
+    write(&directory, "sub/a.asm", b".include \"b.asm\"\n.byte 1\n");
+    write(&directory, "sub/b.asm", b".byte 2\n");
+    assert_eq!(chunks_in(&directory, ".include \"sub/a.asm\"\n.byte 3\n", &[]).unwrap(), [(0, vec![2, 1, 3])]);
+}
+
+#[test]
+fn includes_are_found_in_search_paths() {
+    let directory = directory("include-search");
+    write(&directory, "lib/util.asm", b".macro twice value\n.byte \\value, \\value\n.endm\n");
+    let mut sources = Sources::new(vec![directory.join("lib")]);
+    sources.add(directory.join("src/main.asm"), ".include \"util.asm\"\ntwice 7\n".to_string());
+    let chunks = assemble(&mut sources, &[]).unwrap_or_else(|error| panic!("{}", error.report(&sources))).chunks();
+    assert_eq!(chunks, [(0, vec![7, 7])]);
+}
+
+#[test]
+fn include_errors() {
+    let directory = directory("include-errors");
+    write(&directory, "loop.asm", b".include \"loop.asm\"\n");
+    write(&directory, "broken.asm", b"nop\nmov reg9 1\n");
+    let report = chunks_in(&directory, ".include \"loop.asm\"\n", &[]).unwrap_err();
+    assert!(report.contains("loop.asm:1:1: error: recursive include:"), "{}", report);
+    let report = chunks_in(&directory, "nop\n.include \"broken.asm\"\n", &[]).unwrap_err();
+    assert!(report.contains("broken.asm:2:5: error: `reg9` does not exist"), "{}", report);
+    let report = chunks_in(&directory, "nop\n.include \"missing.asm\"\n", &[]).unwrap_err();
+    assert!(report.contains("main.asm:2:1: error: `missing.asm` not found"), "{}", report);
+}
+
+#[test]
+fn include_cycles_are_reported_right_away() {
+    let directory = directory("include-cycle");
+    write(&directory, "a.asm", b"nop\n.include \"b.asm\"\n");
+    write(&directory, "b.asm", b".include \"a.asm\"\n");
+    let mut sources = Sources::new(vec![]);
+    sources.add(directory.join("main.asm"), ".include \"a.asm\"\n".to_string());
+    let report = assemble(&mut sources, &[]).map(|_| ()).unwrap_err().report(&sources);
+    let path = |name: &str| directory.join(name).canonicalize().unwrap().display().to_string();
+    let chain = format!("recursive include: {} -> {} -> {}", path("a.asm"), path("b.asm"), path("a.asm"));
+    assert!(report.contains(&format!("b.asm:1:1: error: {}\n", chain)), "{}", report);
+    // main.asm, a.asm and b.asm, nothing was read again
+    assert_eq!(sources.paths().count(), 3);
+}
+
+#[test]
+fn guarded_include_cycles() {
+    let directory = directory("include-guarded");
+    write(&directory, "a.asm", b".ifndef A_INCLUDED\nA_INCLUDED = 1\n.include \"a.asm\"\n.byte 1\n.endif\n");
+    assert_eq!(chunks_in(&directory, ".include \"a.asm\"\n", &[]).unwrap(), [(0, vec![1])]);
+    // a guard that keeps holding only stops at the nesting limit
+    write(&directory, "b.asm", b".if 1\n.include \"b.asm\"\n.endif\n");
+    let report = chunks_in(&directory, ".include \"b.asm\"\n", &[]).unwrap_err();
+    assert!(report.contains("b.asm:2:1: error: recursive include:"), "{}", report);
+}
+
+#[test]
+fn incbin_ranges() {
+    let directory = directory("incbin");
+    write(&directory, "data.bin", &[1, 2, 3, 4, 5]);
+    let bytes = |source: &str| chunks_in(&directory, source, &[]).map(|it| it.into_iter().flat_map(|it| it.1).collect::<Vec<u8>>());
+    assert_eq!(bytes(".incbin \"data.bin\"\n").unwrap(), [1, 2, 3, 4, 5]);
+    assert_eq!(bytes(".incbin \"data.bin\", 3\n").unwrap(), [4, 5]);
+    assert_eq!(bytes(".incbin \"data.bin\", 1, 2\n").unwrap(), [2, 3]);
+    assert_eq!(bytes(".incbin \"data.bin\", 5\nnop\n").unwrap(), [0]);
+}
+
+#[test]
+fn incbin_errors() {
+    let directory = directory("incbin-errors");
+    write(&directory, "data.bin", &[1, 2, 3, 4, 5]);
+    let error = |source: &str| chunks_in(&directory, source, &[]).unwrap_err();
+    let report = error("nop\n.incbin \"data.bin\", 4, 2\n");
+    assert!(report.contains("main.asm:2:1: error:") && report.contains("has 5 bytes, cannot take 4..6"), "{}", report);
+    assert!(error(".incbin \"data.bin\", 6\n").contains("cannot take 6..6"));
+    assert!(error(".incbin \"data.bin\", 0x7FFFFFFFFFFFFFFF, 0x7FFFFFFFFFFFFFFF\n").contains("main.asm:1:1: error:"));
+    assert!(error(".incbin \"data.bin\", 1, 0xFFFFFFFFFFFFFFFF\n").contains("main.asm:1:1: error:"));
+    assert!(error(".incbin \"missing.bin\"\n").contains("main.asm:1:1: error: `missing.bin` not found"));
+}
@@ -0,0 +1,45 @@
+mod common;
+
+use common::{bytes, error};
+
+#[test]
+fn every_instruction() {
+    let source = "nop\nmov reg0 1\nmov reg1 reg0\nldw reg1 0x10\nstw reg2 hl\nlda 0x1234\nlda hl\npsh reg3\npsh 5\npop reg2\n\
+        jmp flag0 0x0005\nadd reg0 reg1\nsub reg0 2\nand reg1 1\nor reg1 1\ninv reg1\ncmp reg0 3\nshl reg1 1\nshr reg1 1\n";
+    assert_eq!(bytes(source), [
+        0x00, 0x18, 0x01, 0x11, 0x00, 0x29, 0x00, 0x10, 0x32, 0x48, 0x12, 0x34, 0x40, 0x53, 0x58, 0x05, 0x62,
+        0x78, 0x00, 0x05, 0x80, 0x01, 0x98, 0x02, 0xA9, 0x01, 0xB9, 0x01, 0xC1, 0xD8, 0x03, 0xE9, 0x01, 0xF9, 0x01
+    ]);
+}
+
+#[test]
+fn extended_instructions() {
+    assert_eq!(bytes("hlt\ncall 0x1234\ncall hl\nret\nbrk\nrti\n"), [
+        0x01, 0x00, 0x01, 0x18, 0x12, 0x34, 0x01, 0x10, 0x01, 0x20, 0x01, 0x30, 0x01, 0x40
+    ]);
+}
+
+#[test]
+fn labels_resolve_forward_and_backward() {
+    assert_eq!(bytes("@start:\njmp flag0 @end\njmp flag0 @start\n@end:\nnop\n"), [
+        0x78, 0x00, 0x06, 0x78, 0x00, 0x00, 0x00
+    ]);
+}
+
+#[test]
+fn duplicate_labels_report_the_second_definition() {
+    let report = error("@start:\nnop\n@start:\nnop\n");
+    assert!(report.contains("main.asm:3:1: error: label `@start` is defined more than once"), "{}", report);
+}
+
+#[test]
+fn literals_out_of_range() {
+    assert!(error("mov reg0 256\n").contains("main.asm:1:"));
+    assert!(error("jmp flag0 0x10000\n").contains("main.asm:1:"));
+}
+
+#[test]
+fn unknown_mnemonic_reports_its_line() {
+    let report = error("nop\nfoo reg0\n");
+    assert!(report.contains("main.asm:2:1: error:"), "{}", report);
+}
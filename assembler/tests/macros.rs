@@ -0,0 +1,64 @@
+mod common;
+
+use common::{bytes, error};
+
+#[test]
+fn parameters_are_substituted() {
+    let source = ".macro load register:reg, value:value\nmov \\register \\value\n.endm\nload reg1, 5\nload h, reg1\n";
+    assert_eq!(bytes(source), [0x19, 0x05, 0x12, 0x01]);
+}
+
+#[test]
+fn macros_expand_recursively() {
+    let source = ".macro twice instruction:instr\n\\instruction\n\\instruction\n.endm\n\
+        .macro four instruction:instr\ntwice \\instruction\ntwice \\instruction\n.endm\nfour add reg0 1\n";
+    assert_eq!(bytes(source), [0x88, 0x01].repeat(4));
+}
+
+#[test]
+fn escapes_in_literals_are_kept() {
+    let source = ".macro text value\n.string \"\\\\n\\t\"\n.byte '\\n', \\value\n.endm\ntext 1\n";
+    assert_eq!(bytes(source), [b'\\', b'n', b'\t', 0, b'\n', 1]);
+}
+
+#[test]
+fn parameters_are_not_substituted_in_literals() {
+    let source = ".macro text value\n.string \"\\\\value\"\n.endm\ntext 1\n";
+    assert_eq!(bytes(source), b"\\value\0");
+}
+
+#[test]
+fn commas_in_quotes_and_parentheses_do_not_split_arguments() {
+    let source = ".macro pair first, second\n.byte \\first\n.byte \\second\n.endm\npair ',', hi((0x1234))\npair '\\'', (3)\n";
+    assert_eq!(bytes(source), [b',', 0x12, b'\'', 3]);
+}
+
+#[test]
+fn argument_errors() {
+    let definition = ".macro load register:reg, value\nmov \\register \\value\n.endm\n";
+    assert!(error(&format!("{}load reg0\n", definition)).contains("takes 2 arguments but 1 were supplied"));
+    assert!(error(&format!("{}load 5, 5\n", definition)).contains("argument `register` expects Register"));
+    assert!(error(".macro load value\nmov reg0 \\other\n.endm\nload 5\n").contains("unknown macro argument `\\other`"));
+    assert!(error("missing 1\n").contains("main.asm:1:1: error: unknown macro `missing`"));
+}
+
+#[test]
+fn recursion_is_limited() {
+    let report = error(".macro forever\nforever\n.endm\nforever\n");
+    assert!(report.contains("exceeded the nesting limit"), "{}", report);
+}
+
+#[test]
+fn labels_are_local_to_each_expansion() {
+    let source = "@loop:\nnop\n.macro wait\n@loop:\njmp flag6 @loop\njmp flag0 @::loop\n.endm\nwait\nwait\n";
+    assert_eq!(bytes(source), [0x00, 0x7E, 0x00, 0x01, 0x78, 0x00, 0x00, 0x7E, 0x00, 0x07, 0x78, 0x00, 0x00]);
+}
+
+#[test]
+fn labels_in_literals_are_not_renamed() {
+    let source = ".macro name\n@loop:\n.string \"@loop\"\n.byte '@'\njmp flag0 @loop\n.endm\nname\nname\n";
+    assert_eq!(bytes(source), [
+        b'@', b'l', b'o', b'o', b'p', 0, b'@', 0x78, 0x00, 0x00,
+        b'@', b'l', b'o', b'o', b'p', 0, b'@', 0x78, 0x00, 0x0A
+    ]);
+}
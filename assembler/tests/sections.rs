@@ -0,0 +1,47 @@
+mod common;
+
+use assembler::{assemble, Sources};
+use common::{chunks, error};
+
+const PROGRAM: &str = ".code\nnop\n.data\n.byte 1\n.bss\n.fill 4\n.section vectors\n.org 0xFFF0\n.word 0x1234\n.code\nnop\n";
+
+#[test]
+fn sections_follow_each_other() {
+    assert_eq!(chunks(PROGRAM).unwrap(), [(0, vec![0]), (1, vec![0]), (2, vec![1]), (0xFFF0, vec![0x12, 0x34])]);
+}
+
+#[test]
+fn map_lists_every_segment() {
+    let mut sources = Sources::new(vec![]);
+    sources.add("main.asm".into(), PROGRAM.to_string());
+    let map = assemble(&mut sources, &[]).unwrap_or_else(|error| panic!("{}", error.report(&sources))).map();
+    assert_eq!(map, "origin 0x0000\n\
+        code     0x0000 0x0000     1\n\
+        code     0x0001 0x0001     1\n\
+        data     0x0002 0x0002     1\n\
+        bss      0x0003 0x0006     4 (not loaded)\n\
+        vectors  0xFFF0 0xFFF1     2\n");
+}
+
+#[test]
+fn org_moves_the_current_section() {
+    assert_eq!(chunks(".org 0x100\n@start:\n.word @start\n.org 0x200 + 2\n.word @start\n").unwrap(), [
+        (0x100, vec![0x01, 0x00]), (0x202, vec![0x01, 0x00])
+    ]);
+}
+
+#[test]
+fn labels_in_later_sections() {
+    assert_eq!(chunks("jmp flag0 @data\n.data\n@data:\n.byte 7\n").unwrap(), [(0, vec![0x78, 0x00, 0x03]), (3, vec![7])]);
+}
+
+#[test]
+fn layout_errors() {
+    assert!(error(".org 0x10\n.byte 1, 2\n.data\n.org 0x11\n.byte 3\n").contains("sections `code` and `data` overlap"));
+    assert!(error(".org 0xFFFF\n.word 1\n").contains("section `code` extends past 0xFFFF"));
+    assert!(error(".section bad-name\nnop\n").contains("main.asm:1:1: error:"));
+    let report = error(".data foo\n.byte 1\n.bss 0x100\n");
+    assert_eq!(report.lines().count(), 2, "{}", report);
+    assert!(report.contains("main.asm:1:6: error: expected `\\n`") && report.contains("main.asm:3:5: error: expected `\\n`"), "{}", report);
+    assert!(error(".org @later\n@later:\nnop\n").contains("unknown label `@later`"));
+}
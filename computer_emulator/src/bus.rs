@@ -0,0 +1,73 @@
+use std::ops::RangeInclusive;
+
+pub trait Device {
+    /// Reads the byte at `offset` from the start of the range the device is mapped to.
+    fn read(&self, offset: u16) -> u8;
+    fn write(&mut self, offset: u16, value: u8);
+    /// Called once after every executed instruction and once per step while the processor waits for an interrupt.
+    fn tick(&mut self) {}
+    /// Polled after every tick if the device is wired to an interrupt line, returning true raises it once.
+    fn interrupt(&mut self) -> bool { false }
+    /// Called once when the emulator stops running the program.
+    fn stop(&mut self) {}
+}
+
+struct Mapping {
+    range: RangeInclusive<u16>,
+    device: Box<dyn Device>,
+    line: Option<u8>
+}
+
+/// Routes accesses to the devices mapped into the 16-bit address space.
+/// Later mappings shadow earlier ones, unmapped addresses read as 0 and ignore writes.
+#[derive(Default)]
+pub struct Bus {
+    mappings: Vec<Mapping>
+}
+
+impl Bus {
+    pub fn new() -> Self {
+        Bus { mappings: vec![] }
+    }
+    pub fn map(&mut self, range: RangeInclusive<u16>, device: Box<dyn Device>) {
+        self.mappings.push(Mapping { range, device, line: None })
+    }
+    /// Like `map`, with the interrupts of `device` raised on `line`.
+    pub fn map_with_interrupt(&mut self, range: RangeInclusive<u16>, device: Box<dyn Device>, line: u8) {
+        self.mappings.push(Mapping { range, device, line: Some(line) })
+    }
+    fn mapping(&self, address: u16) -> Option<&Mapping> {
+        self.mappings.iter().rev().find(|it| it.range.contains(&address))
+    }
+    fn mapping_mut(&mut self, address: u16) -> Option<&mut Mapping> {
+        self.mappings.iter_mut().rev().find(|it| it.range.contains(&address))
+    }
+    pub fn read(&self, address: u16) -> u8 {
+        match self.mapping(address) {
+            Some(mapping) => mapping.device.read(address - mapping.range.start()),
+            None => 0
+        }
+    }
+    pub fn write(&mut self, address: u16, value: u8) {
+        if let Some(mapping) = self.mapping_mut(address) {
+            let offset = address - mapping.range.start();
+            mapping.device.write(offset, value)
+        }
+    }
+    /// Ticks every device, returns the interrupt lines they raised as a bit mask.
+    pub fn tick(&mut self) -> u8 {
+        let mut lines = 0;
+        for mapping in &mut self.mappings {
+            mapping.device.tick();
+            if let Some(line) = mapping.line {
+                if mapping.device.interrupt() { lines |= 1 << line }
+            }
+        }
+        lines
+    }
+    pub fn stop(&mut self) {
+        for mapping in &mut self.mappings {
+            mapping.device.stop()
+        }
+    }
+}
@@ -0,0 +1,97 @@
+use std::collections::BTreeMap;
+
+/// A source line an instruction was assembled from.
+#[derive(Clone)]
+pub struct SourceLine {
+    /// Index into the files of the map.
+    pub file: usize,
+    pub line: usize,
+    pub text: String
+}
+
+/// Labels and source lines written by `assembler --debug-map`.
+#[derive(Clone, Default)]
+pub struct DebugMap {
+    /// The main file first, then every included file.
+    files: Vec<String>,
+    /// The first label at every address.
+    labels: BTreeMap<u16, String>,
+    addresses: BTreeMap<String, u16>,
+    lines: BTreeMap<u16, SourceLine>
+}
+
+fn parse_address(string: &str) -> Option<u16> {
+    u16::from_str_radix(string.strip_prefix("0x")?, 16).ok()
+}
+
+impl DebugMap {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut map = DebugMap::default();
+        for (index, line) in text.lines().enumerate() {
+            let invalid = || format!("line {}: invalid debug map entry: {}", index + 1, line);
+            let (kind, rest) = line.split_once(' ').unwrap_or((line, ""));
+            match kind {
+                "" => {},
+                "file" => {
+                    let (index, path) = rest.split_once(' ').ok_or_else(invalid)?;
+                    if index.parse() != Ok(map.files.len()) {
+                        return Err(invalid())
+                    }
+                    map.files.push(path.to_string());
+                },
+                "label" => {
+                    let (address, name) = rest.split_once(' ').ok_or_else(invalid)?;
+                    let address = parse_address(address).ok_or_else(invalid)?;
+                    map.labels.entry(address).or_insert_with(|| name.to_string());
+                    map.addresses.insert(name.to_string(), address);
+                },
+                "line" => {
+                    let mut parts = rest.splitn(4, ' ');
+                    let address = parts.next().and_then(parse_address).ok_or_else(invalid)?;
+                    let file = parts.next().and_then(|it| it.parse().ok()).filter(|it| *it < map.files.len()).ok_or_else(invalid)?;
+                    let line = parts.next().and_then(|it| it.parse().ok()).ok_or_else(invalid)?;
+                    let text = parts.next().unwrap_or("").to_string();
+                    map.lines.insert(address, SourceLine { file, line, text });
+                },
+                _ => return Err(invalid())
+            }
+        }
+        Ok(map)
+    }
+
+    pub fn load(path: &str) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|err| format!("failed to read {}: {}", path, err))?;
+        Self::parse(&text)
+    }
+
+    pub fn file(&self, index: usize) -> &str { &self.files[index] }
+
+    pub fn address(&self, label: &str) -> Option<u16> {
+        self.addresses.get(label).copied()
+    }
+
+    /// `label+offset` relative to the closest label at or before `address`.
+    pub fn symbol(&self, address: u16) -> Option<String> {
+        let (start, name) = self.labels.range(..=address).next_back()?;
+        Some(match address - start {
+            0 => name.clone(),
+            offset => format!("{}+{}", name, offset)
+        })
+    }
+
+    /// The line the instruction starting at `address` was assembled from.
+    pub fn source(&self, address: u16) -> Option<&SourceLine> {
+        self.lines.get(&address)
+    }
+
+    /// `label+offset file:line text`, leaving out whatever is unknown.
+    pub fn describe(&self, address: u16) -> String {
+        let mut parts = vec![];
+        parts.extend(self.symbol(address));
+        if let Some(source) = self.source(address) {
+            parts.push(format!("{}:{}", self.file(source.file), source.line));
+            parts.push(source.text.clone());
+        }
+        parts.join(" ")
+    }
+}
@@ -0,0 +1,240 @@
+use std::collections::BTreeSet;
+use std::io::{BufRead, Write};
+use isa::{opcode, Instruction, Operands, Value};
+use crate::debug_map::DebugMap;
+use crate::{Computer, flag, register};
+
+const HELP: &str = "\
+b <address|label>        set breakpoint
+d <address|label>        delete breakpoint
+l                        list breakpoints
+s [count]                single-step
+n                        step over a CALL or a PSH/JMP call sequence
+c                        continue until halt, breakpoint, BRK or the step budget runs out
+r [<register> <value>]   print or modify registers
+f [<flag> <0|1>]         print or modify flags
+m <address> [length]     print memory
+w <address> <byte>...    modify memory
+q                        quit";
+
+pub enum Control {
+    Continue,
+    Quit
+}
+
+pub struct Debugger {
+    pub computer: Computer,
+    breakpoints: BTreeSet<u16>,
+    debug_map: Option<DebugMap>,
+    max_steps: u64
+}
+
+fn parse_number(string: &str) -> Result<u64, String> {
+    let result = if let Some(hex) = string.strip_prefix("0x") {
+        u64::from_str_radix(hex, 16)
+    } else {
+        string.parse()
+    };
+    result.map_err(|_| format!("invalid number: {}", string))
+}
+
+fn parse_u16(string: &str) -> Result<u16, String> {
+    u16::try_from(parse_number(string)?).map_err(|_| format!("out of range: {}", string))
+}
+
+fn parse_u8(string: &str) -> Result<u8, String> {
+    u8::try_from(parse_number(string)?).map_err(|_| format!("out of range: {}", string))
+}
+
+/// Index of `string` in `names` or, failing that, in `aliases`.
+fn parse_name(names: &[&str], aliases: &[Option<&str>], string: &str) -> Result<u8, String> {
+    names.iter().position(|it| it.eq_ignore_ascii_case(string))
+        .or_else(|| aliases.iter().position(|it| it.is_some_and(|it| it.eq_ignore_ascii_case(string))))
+        .map(|it| it as u8)
+        .ok_or(format!("unknown name: {}", string))
+}
+
+impl Debugger {
+    pub fn new(computer: Computer) -> Self {
+        Debugger { computer, breakpoints: BTreeSet::new(), debug_map: None, max_steps: 1_000_000 }
+    }
+
+    /// Steps `c` and `n` may take before giving up and returning to the prompt.
+    pub fn with_max_steps(mut self, max_steps: u64) -> Self {
+        self.max_steps = max_steps;
+        self
+    }
+
+    /// Show labels and source lines next to addresses and accept labels as breakpoints.
+    pub fn with_debug_map(mut self, debug_map: DebugMap) -> Self {
+        self.debug_map = Some(debug_map);
+        self
+    }
+
+    /// A number or, with a debug map, a label name.
+    fn parse_address(&self, string: &str) -> Result<u16, String> {
+        match self.debug_map.as_ref().and_then(|it| it.address(string)) {
+            Some(address) => Ok(address),
+            None => parse_u16(string)
+        }
+    }
+
+    fn describe(&self, address: u16) -> String {
+        match self.debug_map.as_ref().map(|it| it.describe(address)) {
+            Some(description) if !description.is_empty() => format!(" ; {}", description),
+            _ => String::new()
+        }
+    }
+
+    pub fn add_breakpoint(&mut self, address: u16) { self.breakpoints.insert(address); }
+    pub fn remove_breakpoint(&mut self, address: u16) { self.breakpoints.remove(&address); }
+
+    /// Steps until `until` returns true, the cpu stops, a breakpoint is reached or BRK is executed, but always at least once.
+    /// Returns false if the step budget ran out first.
+    fn run_until(&mut self, until: impl Fn(&Computer) -> bool) -> bool {
+        for _ in 0..self.max_steps {
+            self.computer.step();
+            if self.computer.stopped() || self.computer.hit_break() || until(&self.computer) || self.breakpoints.contains(&self.computer.pc()) {
+                return true
+            }
+        }
+        false
+    }
+
+    fn print_budget_exceeded(&self, out: &mut impl Write) -> std::io::Result<()> {
+        writeln!(out, "step budget of {} exceeded", self.max_steps)
+    }
+
+    /// Value pushed by a `psh` of a literal at `address`.
+    fn pushed_literal(&self, address: u16) -> Option<u8> {
+        match self.computer.instruction(address) {
+            Instruction { code: opcode::PSH, operands: Operands::Value(Value::Literal(value)) } => Some(value),
+            _ => None
+        }
+    }
+
+    /// Address after a CALL, or after a `psh <high>`, `psh <low>`, `jmp` sequence that pushes exactly that address,
+    /// starting at the current instruction. Any other instruction, like a plain JMP, is not a call.
+    fn return_address(&self) -> Option<u16> {
+        let next = |address: u16| address.wrapping_add(self.computer.instruction_size(address));
+        let pc = self.computer.pc();
+        if self.computer.instruction(pc).code == opcode::CALL {
+            return Some(next(pc))
+        }
+        let high = self.pushed_literal(pc)?;
+        let low = self.pushed_literal(next(pc))?;
+        let jump = next(next(pc));
+        let address = next(jump);
+        (self.computer.instruction(jump).code == opcode::JMP && address == u16::from_be_bytes([high, low])).then_some(address)
+    }
+
+    fn print_position(&self, out: &mut impl Write) -> std::io::Result<()> {
+        let pc = self.computer.pc();
+        let size = self.computer.instruction_size(pc);
+        write!(out, "{:04X}:", pc)?;
+        for offset in 0..size {
+            write!(out, " {:02X}", self.computer.ram8(pc.wrapping_add(offset)))?;
+        }
+        if self.computer.stopped() {
+            write!(out, " (halted)")?;
+        } else if self.computer.flag(flag::HALT) {
+            write!(out, " (waiting for an interrupt)")?;
+        }
+        if self.computer.hit_break() { write!(out, " (brk)")?; }
+        writeln!(out, "{}", self.describe(pc))
+    }
+
+    fn print_registers(&self, out: &mut impl Write) -> std::io::Result<()> {
+        for (index, name) in register::NAMES.iter().enumerate() {
+            writeln!(out, "{:<9} 0x{:02X}", name, self.computer.reg8(index as u8))?;
+        }
+        Ok(())
+    }
+
+    fn print_flags(&self, out: &mut impl Write) -> std::io::Result<()> {
+        for (index, name) in flag::NAMES.iter().enumerate() {
+            writeln!(out, "{:<9} {}", name, self.computer.flag(index as u8) as u8)?;
+        }
+        Ok(())
+    }
+
+    fn print_memory(&self, out: &mut impl Write, address: u16, length: u16) -> std::io::Result<()> {
+        for line in (0..length).step_by(16) {
+            write!(out, "{:04X}:", address.wrapping_add(line))?;
+            for offset in line..length.min(line.saturating_add(16)) {
+                write!(out, " {:02X}", self.computer.ram8(address.wrapping_add(offset)))?;
+            }
+            writeln!(out)?;
+        }
+        Ok(())
+    }
+
+    /// Executes a single command line, writing its output to `out`.
+    pub fn execute(&mut self, line: &str, out: &mut impl Write) -> Result<Control, String> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let io_error = |err: std::io::Error| err.to_string();
+        match words.as_slice() {
+            [] => {},
+            ["b", address] => self.add_breakpoint(self.parse_address(address)?),
+            ["d", address] => self.remove_breakpoint(self.parse_address(address)?),
+            ["l"] => for address in &self.breakpoints {
+                writeln!(out, "{:04X}{}", address, self.describe(*address)).map_err(io_error)?;
+            },
+            ["s"] | ["s", _] => {
+                let count = words.get(1).map(|it| parse_number(it)).transpose()?.unwrap_or(1);
+                for _ in 0..count {
+                    if self.computer.stopped() { break }
+                    self.computer.step();
+                }
+                self.print_position(out).map_err(io_error)?;
+            },
+            ["n"] => {
+                let finished = match self.return_address() {
+                    Some(address) => self.run_until(|it| it.pc() == address),
+                    None => {
+                        self.computer.step();
+                        true
+                    }
+                };
+                if !finished {
+                    self.print_budget_exceeded(out).map_err(io_error)?;
+                }
+                self.print_position(out).map_err(io_error)?;
+            },
+            ["c"] => {
+                if !self.computer.stopped() && !self.run_until(|_| false) {
+                    self.print_budget_exceeded(out).map_err(io_error)?;
+                }
+                self.print_position(out).map_err(io_error)?;
+            },
+            ["r"] => self.print_registers(out).map_err(io_error)?,
+            ["r", name, value] => self.computer.set_reg8(parse_name(&register::NAMES, &register::ALIASES, name)?, parse_u8(value)?),
+            ["f"] => self.print_flags(out).map_err(io_error)?,
+            ["f", name, value] => self.computer.set_flag(parse_name(&flag::NAMES, &flag::ALIASES.map(Some), name)?, parse_u8(value)? != 0),
+            ["m", address] => self.print_memory(out, self.parse_address(address)?, 16).map_err(io_error)?,
+            ["m", address, length] => self.print_memory(out, self.parse_address(address)?, parse_u16(length)?).map_err(io_error)?,
+            ["w", address, bytes @ ..] if !bytes.is_empty() => {
+                let address = self.parse_address(address)?;
+                for (offset, byte) in bytes.iter().enumerate() {
+                    self.computer.set_ram8(address.wrapping_add(offset as u16), parse_u8(byte)?);
+                }
+            },
+            ["h"] => writeln!(out, "{}", HELP).map_err(io_error)?,
+            ["q"] => return Ok(Control::Quit),
+            _ => return Err(format!("unknown command: {} (h for help)", line.trim()))
+        }
+        Ok(Control::Continue)
+    }
+
+    /// Reads commands line by line until `q` or the end of `input`.
+    pub fn repl(&mut self, input: impl BufRead, out: &mut impl Write) -> std::io::Result<()> {
+        for line in input.lines() {
+            match self.execute(&line?, out) {
+                Ok(Control::Quit) => break,
+                Ok(Control::Continue) => {},
+                Err(message) => writeln!(out, "error: {}", message)?
+            }
+        }
+        Ok(())
+    }
+}
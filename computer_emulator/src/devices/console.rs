@@ -0,0 +1,36 @@
+use std::cell::RefCell;
+use std::io::{Read, Stdin, Stdout, Write};
+use crate::bus::Device;
+
+/// Character device occupying a single address.
+/// Writing outputs the byte, reading blocks for the next input byte and yields 0 at the end of input.
+pub struct Console<R: Read, W: Write> {
+    input: RefCell<R>,
+    output: W
+}
+
+impl Console<Stdin, Stdout> {
+    pub fn stdio() -> Self {
+        Console::new(std::io::stdin(), std::io::stdout())
+    }
+}
+
+impl<R: Read, W: Write> Console<R, W> {
+    pub fn new(input: R, output: W) -> Self {
+        Console { input: RefCell::new(input), output }
+    }
+    pub fn output(&self) -> &W { &self.output }
+}
+
+impl<R: Read, W: Write> Device for Console<R, W> {
+    fn read(&self, _offset: u16) -> u8 {
+        let mut byte = [0];
+        match self.input.borrow_mut().read(&mut byte) {
+            Ok(1) => byte[0],
+            _ => 0
+        }
+    }
+    fn write(&mut self, _offset: u16, value: u8) {
+        let _ = self.output.write_all(&[value]).and_then(|_| self.output.flush());
+    }
+}
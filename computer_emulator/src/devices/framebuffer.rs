@@ -0,0 +1,145 @@
+use std::io::Write;
+use std::path::PathBuf;
+use std::str::FromStr;
+use crate::bus::Device;
+
+/// The 16 colors of palette mode, indexed by the 4-bit value of a pixel.
+pub const PALETTE: [[u8; 3]; 16] = [
+    [0x00, 0x00, 0x00], [0x00, 0x00, 0xAA], [0x00, 0xAA, 0x00], [0x00, 0xAA, 0xAA],
+    [0xAA, 0x00, 0x00], [0xAA, 0x00, 0xAA], [0xAA, 0x55, 0x00], [0xAA, 0xAA, 0xAA],
+    [0x55, 0x55, 0x55], [0x55, 0x55, 0xFF], [0x55, 0xFF, 0x55], [0x55, 0xFF, 0xFF],
+    [0xFF, 0x55, 0x55], [0xFF, 0x55, 0xFF], [0xFF, 0xFF, 0x55], [0xFF, 0xFF, 0xFF]
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    /// 128×64 pixels, 8 per byte with the leftmost one in the highest bit.
+    Mono,
+    /// 64×64 pixels, 2 per byte with the left one in the high nibble, indexing `PALETTE`.
+    Palette
+}
+
+impl Mode {
+    pub fn width(&self) -> usize {
+        match self {
+            Mode::Mono => 128,
+            Mode::Palette => 64
+        }
+    }
+    pub fn height(&self) -> usize { 64 }
+    fn bits(&self) -> usize {
+        match self {
+            Mode::Mono => 1,
+            Mode::Palette => 4
+        }
+    }
+    /// Bytes of pixel memory, the control register follows them.
+    pub fn size(&self) -> u16 {
+        (self.width() * self.height() * self.bits() / 8) as u16
+    }
+}
+
+impl FromStr for Mode {
+    type Err = String;
+    fn from_str(string: &str) -> Result<Self, String> {
+        match string {
+            "mono" => Ok(Mode::Mono),
+            "palette" => Ok(Mode::Palette),
+            _ => Err(format!("unknown framebuffer mode: {} (mono or palette)", string))
+        }
+    }
+}
+
+/// Pixel memory followed by a control register, writing any value to it renders the frame to every output.
+/// The last frame is also rendered when the emulator stops.
+pub struct Framebuffer {
+    mode: Mode,
+    pixels: Vec<u8>,
+    /// Rewritten with every frame.
+    ppm: Option<PathBuf>,
+    terminal: Option<Box<dyn Write>>
+}
+
+impl Framebuffer {
+    pub fn new(mode: Mode) -> Self {
+        Framebuffer { mode, pixels: vec![0; mode.size() as usize], ppm: None, terminal: None }
+    }
+    pub fn with_ppm(mut self, path: PathBuf) -> Self {
+        self.ppm = Some(path);
+        self
+    }
+    /// Draws frames with ANSI block characters, two pixel rows per line.
+    pub fn with_terminal(mut self, output: Box<dyn Write>) -> Self {
+        self.terminal = Some(output);
+        self
+    }
+    pub fn mode(&self) -> Mode { self.mode }
+    /// Color of the pixel at `x`, `y`.
+    pub fn pixel(&self, x: usize, y: usize) -> [u8; 3] {
+        let bits = self.mode.bits();
+        let index = (y * self.mode.width() + x) * bits;
+        let value = self.pixels[index / 8] >> (8 - bits - index % 8) & ((1 << bits) - 1) as u8;
+        match self.mode {
+            Mode::Mono => if value != 0 { PALETTE[15] } else { PALETTE[0] },
+            Mode::Palette => PALETTE[value as usize]
+        }
+    }
+    pub fn write_ppm(&self, out: &mut impl Write) -> std::io::Result<()> {
+        write!(out, "P6\n{} {}\n255\n", self.mode.width(), self.mode.height())?;
+        for y in 0..self.mode.height() {
+            for x in 0..self.mode.width() {
+                out.write_all(&self.pixel(x, y))?;
+            }
+        }
+        Ok(())
+    }
+    /// Mono frames only use block characters, palette frames set the colors of both halves of every block.
+    pub fn write_ansi(&self, out: &mut (impl Write + ?Sized)) -> std::io::Result<()> {
+        for y in (0..self.mode.height()).step_by(2) {
+            for x in 0..self.mode.width() {
+                let (top, bottom) = (self.pixel(x, y), self.pixel(x, y + 1));
+                match self.mode {
+                    Mode::Mono => write!(out, "{}", match (top == PALETTE[15], bottom == PALETTE[15]) {
+                        (false, false) => ' ',
+                        (true, false) => '▀',
+                        (false, true) => '▄',
+                        (true, true) => '█'
+                    })?,
+                    Mode::Palette => write!(out, "\x1b[38;2;{};{};{}m\x1b[48;2;{};{};{}m▀",
+                        top[0], top[1], top[2], bottom[0], bottom[1], bottom[2])?
+                }
+            }
+            if self.mode == Mode::Palette { write!(out, "\x1b[0m")?; }
+            writeln!(out)?;
+        }
+        out.flush()
+    }
+    fn render(&mut self) {
+        if let Some(path) = &self.ppm {
+            let mut bytes = vec![];
+            self.write_ppm(&mut bytes).expect("writing to a vec does not fail");
+            if let Err(err) = std::fs::write(path, bytes) {
+                eprintln!("failed to write {}: {}", path.display(), err);
+            }
+        }
+        if let Some(mut terminal) = self.terminal.take() {
+            let _ = self.write_ansi(terminal.as_mut());
+            self.terminal = Some(terminal);
+        }
+    }
+}
+
+impl Device for Framebuffer {
+    fn read(&self, offset: u16) -> u8 {
+        self.pixels.get(offset as usize).copied().unwrap_or(0)
+    }
+    fn write(&mut self, offset: u16, value: u8) {
+        match self.pixels.get_mut(offset as usize) {
+            Some(byte) => *byte = value,
+            None => self.render()
+        }
+    }
+    fn stop(&mut self) {
+        self.render()
+    }
+}
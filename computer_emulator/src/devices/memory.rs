@@ -0,0 +1,28 @@
+use crate::bus::Device;
+
+/// Plain RAM, or ROM when `read_only` is set.
+pub struct Memory {
+    bytes: Vec<u8>,
+    read_only: bool
+}
+
+impl Memory {
+    pub fn ram(size: usize) -> Self {
+        Memory { bytes: vec![0; size], read_only: false }
+    }
+    pub fn rom(bytes: Vec<u8>) -> Self {
+        Memory { bytes, read_only: true }
+    }
+}
+
+impl Device for Memory {
+    fn read(&self, offset: u16) -> u8 {
+        self.bytes.get(offset as usize).copied().unwrap_or(0)
+    }
+    fn write(&mut self, offset: u16, value: u8) {
+        if self.read_only { return }
+        if let Some(byte) = self.bytes.get_mut(offset as usize) {
+            *byte = value
+        }
+    }
+}
@@ -0,0 +1,11 @@
+mod memory;
+mod console;
+mod ticks;
+pub mod timer;
+pub mod framebuffer;
+
+pub use memory::Memory;
+pub use console::Console;
+pub use ticks::TickCounter;
+pub use timer::Timer;
+pub use framebuffer::Framebuffer;
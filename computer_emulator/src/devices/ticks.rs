@@ -0,0 +1,26 @@
+use crate::bus::Device;
+
+/// Counts steps, including the ones spent halted waiting for an interrupt, as a 32-bit big-endian value over four addresses, any write resets it.
+#[derive(Default)]
+pub struct TickCounter {
+    ticks: u32
+}
+
+impl TickCounter {
+    /// Bytes of address space the counter takes.
+    pub const SIZE: u16 = 4;
+
+    pub fn new() -> Self { TickCounter { ticks: 0 } }
+}
+
+impl Device for TickCounter {
+    fn read(&self, offset: u16) -> u8 {
+        self.ticks.to_be_bytes().get(offset as usize).copied().unwrap_or(0)
+    }
+    fn write(&mut self, _offset: u16, _value: u8) {
+        self.ticks = 0
+    }
+    fn tick(&mut self) {
+        self.ticks = self.ticks.wrapping_add(1)
+    }
+}
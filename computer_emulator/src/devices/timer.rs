@@ -0,0 +1,90 @@
+use crate::bus::Device;
+
+pub const CONTROL: u16 = 0;
+pub const PRESCALER: u16 = 1;
+pub const RELOAD_H: u16 = 2;
+pub const RELOAD_L: u16 = 3;
+pub const COUNT_H: u16 = 4;
+pub const COUNT_L: u16 = 5;
+pub const STATUS: u16 = 6;
+/// Number of addresses the timer occupies.
+pub const SIZE: u16 = 7;
+
+/// Bits of the CONTROL register.
+pub const ENABLE: u8 = 1 << 0;
+/// Reloads and keeps counting when the count runs out, otherwise ENABLE is cleared.
+pub const PERIODIC: u8 = 1 << 1;
+/// Raises an interrupt whenever the count runs out.
+pub const INTERRUPT: u8 = 1 << 2;
+
+/// Bit of the STATUS register, set when the count runs out and cleared by writing it back.
+pub const EXPIRED: u8 = 1 << 0;
+
+/// Counts down from the reload value once every `prescaler + 1` ticks, so it runs in emulated cycles.
+/// Writing CONTROL with ENABLE set restarts the count, a reload value of 0 counts 0x10000 steps.
+#[derive(Default)]
+pub struct Timer {
+    control: u8,
+    prescaler: u8,
+    reload: u16,
+    count: u16,
+    /// Ticks since the count was last decremented.
+    divider: u8,
+    status: u8,
+    interrupt: bool
+}
+
+impl Timer {
+    pub fn new() -> Self { Timer::default() }
+}
+
+impl Device for Timer {
+    fn read(&self, offset: u16) -> u8 {
+        match offset {
+            CONTROL => self.control,
+            PRESCALER => self.prescaler,
+            RELOAD_H => (self.reload >> 8) as u8,
+            RELOAD_L => self.reload as u8,
+            COUNT_H => (self.count >> 8) as u8,
+            COUNT_L => self.count as u8,
+            STATUS => self.status,
+            _ => 0
+        }
+    }
+    fn write(&mut self, offset: u16, value: u8) {
+        match offset {
+            CONTROL => {
+                self.control = value;
+                if value & ENABLE != 0 {
+                    self.count = self.reload;
+                    self.divider = 0;
+                }
+            },
+            PRESCALER => self.prescaler = value,
+            RELOAD_H => self.reload = (value as u16) << 8 | self.reload & 0xFF,
+            RELOAD_L => self.reload = self.reload & 0xFF00 | value as u16,
+            STATUS => self.status &= !value,
+            _ => {}
+        }
+    }
+    fn tick(&mut self) {
+        if self.control & ENABLE == 0 { return }
+        if self.divider < self.prescaler {
+            self.divider += 1;
+            return
+        }
+        self.divider = 0;
+        self.count = self.count.wrapping_sub(1);
+        if self.count != 0 { return }
+        self.status |= EXPIRED;
+        self.interrupt |= self.control & INTERRUPT != 0;
+        if self.control & PERIODIC != 0 {
+            self.count = self.reload;
+        } else {
+            self.control &= !ENABLE;
+        }
+    }
+    fn interrupt(&mut self) -> bool {
+        std::mem::take(&mut self.interrupt)
+    }
+}
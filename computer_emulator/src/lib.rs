@@ -0,0 +1,315 @@
+pub mod debugger;
+pub mod debug_map;
+pub mod bus;
+pub mod devices;
+pub mod loader;
+pub mod trace;
+
+use std::ops::RangeInclusive;
+use crate::bus::{Bus, Device};
+use crate::devices::Memory;
+use crate::trace::{TraceRecord, Tracer};
+use isa::{interrupt, opcode, Address, Instruction, Operands, Value};
+
+pub use isa::{flag, register};
+
+pub struct Computer {
+    registers: [u8; 1 << 3],
+    bus: Bus,
+    stack: [u8; 1 << 8],
+    tracer: Option<Tracer>,
+    /// Set by BRK until the next instruction is executed.
+    hit_break: bool,
+    /// Bit mask of the interrupt lines that were raised but not yet taken.
+    pending: u8
+}
+
+impl Computer {
+    /// A computer with ram over the whole address space.
+    pub fn new() -> Self {
+        let mut bus = Bus::new();
+        bus.map(0x0000..=0xFFFF, Box::new(Memory::ram(1 << 16)));
+        Computer::with_bus(bus)
+    }
+    pub fn with_bus(bus: Bus) -> Self {
+        Computer {
+            registers: [0; 1 << 3],
+            bus,
+            stack: [0; 1 << 8],
+            tracer: None,
+            hit_break: false,
+            pending: 0
+        }
+    }
+    /// Maps `device` over `range`, shadowing whatever was mapped there before.
+    pub fn map(&mut self, range: RangeInclusive<u16>, device: Box<dyn Device>) {
+        self.bus.map(range, device)
+    }
+    /// Like `map`, with the interrupts of `device` raised on `line`.
+    pub fn map_with_interrupt(&mut self, range: RangeInclusive<u16>, device: Box<dyn Device>, line: u8) {
+        assert!(line < interrupt::LINES, "there are only {} interrupt lines", interrupt::LINES);
+        self.bus.map_with_interrupt(range, device, line)
+    }
+    pub fn set_tracer(&mut self, tracer: Option<Tracer>) { self.tracer = tracer }
+    pub fn bus(&self) -> &Bus { &self.bus }
+    pub fn bus_mut(&mut self) -> &mut Bus { &mut self.bus }
+    /// Writes `image` to the bus starting at `origin`, wrapping around at the end of the address space.
+    pub fn load(&mut self, image: &[u8], origin: u16) {
+        for (offset, byte) in image.iter().enumerate() {
+            self.set_ram8(origin.wrapping_add(offset as u16), *byte);
+        }
+    }
+}
+
+impl Default for Computer {
+    fn default() -> Self { Self::new() }
+}
+
+// static utils
+impl Computer {
+    pub fn set_reg8(&mut self, register: u8, value: u8) {
+        self.registers[register as usize] = value
+    }
+    pub fn reg8(&self, register: u8) -> u8 {
+        self.registers[register as usize]
+    }
+    pub fn set_reg16(&mut self, register: u8, value: u16) {
+        self.registers[register as usize] = (value >> 8) as u8;
+        self.registers[register as usize + 1] = value as u8;
+    }
+    pub fn reg16(&self, register: u8) -> u16 {
+        ((self.registers[register as usize] as u16) << 8) | (self.registers[register as usize + 1] as u16)
+    }
+    pub fn address(&self) -> u16 {
+        self.reg16(register::HIGH)
+    }
+    pub fn set_ram8(&mut self, address: u16, value: u8) {
+        self.bus.write(address, value)
+    }
+    pub fn ram8(&self, address: u16) -> u8 {
+        self.bus.read(address)
+    }
+    pub fn ram16(&self, address: u16) -> u16 {
+        (self.ram8(address) as u16) << 8 | (self.ram8(address.wrapping_add(1)) as u16)
+    }
+    pub fn set_flag(&mut self, index: u8, value: bool) {
+        self.set_reg8(register::FLAG, if value {
+            self.reg8(register::FLAG) | (1 << index)
+        } else {
+            self.reg8(register::FLAG) & !(1 << index)
+        })
+    }
+    pub fn flag(&self, index: u8) -> bool {
+        self.reg8(register::FLAG) >> index & 1 != 0
+    }
+    pub fn stack(&self) -> &[u8; 1 << 8] { &self.stack }
+    pub fn stack_mut(&mut self) -> &mut [u8; 1 << 8] { &mut self.stack }
+    pub fn stack_ptr(&self) -> u8 { self.reg8(register::SCTR) }
+    pub fn set_stack_ptr(&mut self, value: u8) { self.set_reg8(register::SCTR, value) }
+    fn inc_stack_ptr(&mut self) { self.set_stack_ptr(self.stack_ptr().wrapping_add(1)) }
+    fn dec_stack_ptr(&mut self) { self.set_stack_ptr(self.stack_ptr().wrapping_sub(1)) }
+}
+
+// current operation related utils
+impl Computer {
+    pub fn pc(&self) -> u16 {
+        self.reg16(register::PC_H)
+    }
+    pub fn set_pc(&mut self, value: u16) { self.set_reg16(register::PC_H, value) }
+    fn value8(&self, value: Value) -> u8 {
+        match value {
+            Value::Register(register) => self.reg8(register),
+            Value::Literal(literal) => literal
+        }
+    }
+    fn value16(&self, address: Address) -> u16 {
+        match address {
+            Address::HL => self.reg16(register::HIGH),
+            Address::Literal(address) => address
+        }
+    }
+}
+
+// decoding
+impl Computer {
+    /// Size in bytes of the instruction stored at `address`.
+    pub fn instruction_size(&self, address: u16) -> u16 {
+        isa::size(self.ram8(address), self.ram8(address.wrapping_add(1))) as u16
+    }
+    /// Decodes the instruction stored at `address`.
+    pub fn instruction(&self, address: u16) -> Instruction {
+        let bytes: Vec<u8> = (0..self.instruction_size(address)).map(|it| self.ram8(address.wrapping_add(it))).collect();
+        isa::decode(&bytes).expect("every opcode decodes").0
+    }
+}
+
+// Execution Manager
+impl Computer {
+    /// Whether the last executed instruction was BRK.
+    pub fn hit_break(&self) -> bool { self.hit_break }
+    /// Raises interrupt `line`, it is taken before the next instruction once INTERRUPT is set.
+    pub fn request_interrupt(&mut self, line: u8) {
+        assert!(line < interrupt::LINES, "there are only {} interrupt lines", interrupt::LINES);
+        self.pending |= 1 << line
+    }
+    pub fn pending_interrupts(&self) -> u8 { self.pending }
+    /// Whether the processor halted for good, a halted processor with INTERRUPT set waits for an interrupt instead.
+    pub fn stopped(&self) -> bool {
+        self.flag(flag::HALT) && !self.flag(flag::INTERRUPT)
+    }
+    pub fn run(&mut self) {
+        while !self.stopped() {
+            self.step()
+        }
+    }
+    /// Takes a pending interrupt, executes an instruction or, while halted, only ticks the devices.
+    pub fn step(&mut self) {
+        if self.flag(flag::INTERRUPT) && self.pending != 0 {
+            return self.enter_interrupt()
+        }
+        if self.flag(flag::HALT) {
+            return self.tick()
+        }
+        let pc = self.pc();
+        if !self.tracer.as_ref().is_some_and(|it| it.traces(pc)) {
+            return self.execute()
+        }
+        let bytes = (0..self.instruction_size(pc)).map(|it| self.ram8(pc.wrapping_add(it))).collect();
+        let registers_before = self.registers;
+        self.execute();
+        let record = TraceRecord { pc, bytes, registers_before, registers_after: self.registers };
+        if let Some(tracer) = &mut self.tracer {
+            tracer.record(&record)
+        }
+    }
+    fn execute(&mut self) {
+        let pc = self.pc();
+        let instruction = self.instruction(pc);
+        self.set_pc(pc.wrapping_add(self.instruction_size(pc)));
+        self.hit_break = false;
+        match (instruction.code, instruction.operands) {
+            (opcode::NOP, _) => {},
+            (opcode::MOV, Operands::RegisterValue(register, value)) => self.run_mov(register, value),
+            (opcode::LDW, Operands::RegisterAddress(register, address)) => self.run_ldw(register, address),
+            (opcode::STW, Operands::RegisterAddress(register, address)) => self.run_stw(register, address),
+            (opcode::LDA, Operands::Address(address)) => self.run_lda(address),
+            (opcode::PSH, Operands::Value(value)) => self.run_psh(value),
+            (opcode::POP, Operands::Register(register)) => self.run_pop(register),
+            (opcode::JMP, Operands::FlagAddress(flag, address)) => self.run_jmp(flag, address),
+            (opcode::ADD, Operands::RegisterValue(register, value)) => self.run_add(register, value),
+            (opcode::SUB, Operands::RegisterValue(register, value)) => self.run_sub(register, value),
+            (opcode::AND, Operands::RegisterValue(register, value)) => self.run_and(register, value),
+            (opcode::OR , Operands::RegisterValue(register, value)) => self.run_or(register, value),
+            (opcode::INV, Operands::Register(register)) => self.run_inv(register),
+            (opcode::CMP, Operands::RegisterValue(register, value)) => self.run_cmp(register, value),
+            (opcode::SHL, Operands::RegisterValue(register, value)) => self.run_shl(register, value),
+            (opcode::SHR, Operands::RegisterValue(register, value)) => self.run_shr(register, value),
+            (opcode::HLT, Operands::None) => self.set_flag(flag::HALT, true),
+            (opcode::CALL, Operands::Address(address)) => self.run_call(address),
+            (opcode::RET, Operands::None) => self.run_ret(),
+            (opcode::BRK, Operands::None) => self.hit_break = true,
+            (opcode::RTI, Operands::None) => self.run_rti(),
+            // reserved extended opcodes stop images that were written for a newer processor
+            _ if instruction.opcode().is_none() => self.set_flag(flag::HALT, true),
+            _ => unreachable!()
+        }
+        self.tick();
+    }
+    fn tick(&mut self) {
+        self.pending |= self.bus.tick();
+    }
+    fn enter_interrupt(&mut self) {
+        let line = self.pending.trailing_zeros() as u8;
+        self.pending &= !(1 << line);
+        self.run_psh(Value::Register(register::PC_H));
+        self.run_psh(Value::Register(register::PC_L));
+        self.set_flag(flag::HALT, false);
+        self.run_psh(Value::Register(register::FLAG));
+        self.set_flag(flag::INTERRUPT, false);
+        self.set_pc(self.ram16(interrupt::VECTORS + 2 * line as u16));
+    }
+}
+
+// OP Implementations
+impl Computer {
+    fn run_mov(&mut self, register: u8, value: Value) {
+        self.set_reg8(register, self.value8(value));
+    }
+    fn run_ldw(&mut self, register: u8, address: Address) {
+        self.set_reg8(register, self.ram8(self.value16(address)));
+    }
+    fn run_stw(&mut self, register: u8, address: Address) {
+        self.set_ram8(self.value16(address), self.reg8(register));
+    }
+    fn run_lda(&mut self, address: Address) {
+        self.set_reg16(register::HIGH, self.ram16(self.value16(address)));
+    }
+    fn run_psh(&mut self, value: Value) {
+        self.stack[self.stack_ptr() as usize] = self.value8(value);
+        self.inc_stack_ptr();
+    }
+    fn run_pop(&mut self, register: u8) {
+        self.dec_stack_ptr();
+        self.set_reg8(register, self.stack[self.stack_ptr() as usize]);
+    }
+    fn run_jmp(&mut self, condition: u8, address: Address) {
+        // flag0 is HALT which is never set while running, so it is used for unconditional jumps
+        if condition == flag::HALT || self.flag(condition) {
+            self.set_pc(self.value16(address))
+        }
+    }
+    fn run_call(&mut self, address: Address) {
+        let target = self.value16(address);
+        self.run_psh(Value::Register(register::PC_H));
+        self.run_psh(Value::Register(register::PC_L));
+        self.set_pc(target);
+    }
+    fn run_ret(&mut self) {
+        self.run_pop(register::PC_L);
+        self.run_pop(register::PC_H);
+    }
+    fn run_rti(&mut self) {
+        self.run_pop(register::FLAG);
+        self.run_ret();
+    }
+    fn run_add(&mut self, register: u8, value: Value) {
+        let a = self.reg8(register);
+        let value = self.value8(value);
+        let result = a.overflowing_add(value);
+        self.set_reg8(register, result.0);
+        self.set_flag(flag::CARRY, result.1);
+        self.set_flag(flag::OVERFLOW, (a as i8).overflowing_add(value as i8).1);
+    }
+    fn run_sub(&mut self, register: u8, value: Value) {
+        let a = self.reg8(register);
+        let value = self.value8(value);
+        let result = a.overflowing_sub(value);
+        self.set_reg8(register, result.0);
+        self.set_flag(flag::BORROW, result.1);
+        self.set_flag(flag::OVERFLOW, (a as i8).overflowing_sub(value as i8).1);
+    }
+    fn run_and(&mut self, register: u8, value: Value) {
+        self.set_reg8(register, self.reg8(register) & self.value8(value));
+    }
+    fn run_or(&mut self, register: u8, value: Value) {
+        self.set_reg8(register, self.reg8(register) | self.value8(value));
+    }
+    fn run_inv(&mut self, register: u8) {
+        self.set_reg8(register, !self.reg8(register));
+    }
+    fn run_cmp(&mut self, register: u8, value: Value) {
+        let a = self.reg8(register);
+        let b = self.value8(value);
+        self.set_flag(flag::LESS, a < b);
+        self.set_flag(flag::EQUAL, a == b);
+        self.set_flag(flag::MORE, a > b);
+    }
+    fn run_shl(&mut self, register: u8, value: Value) {
+        let value = self.value8(value);
+        self.set_reg8(register, self.reg8(register).checked_shl(value as u32).unwrap_or(0));
+    }
+    fn run_shr(&mut self, register: u8, value: Value) {
+        let value = self.value8(value);
+        self.set_reg8(register, self.reg8(register).checked_shr(value as u32).unwrap_or(0));
+    }
+}
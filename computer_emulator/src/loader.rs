@@ -0,0 +1,46 @@
+use std::str::FromStr;
+
+pub use records::Chunk;
+
+pub enum ImageFormat {
+    Binary,
+    IntelHex,
+    SRecord
+}
+
+impl FromStr for ImageFormat {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "bin" => Ok(ImageFormat::Binary),
+            "ihex" => Ok(ImageFormat::IntelHex),
+            "srec" => Ok(ImageFormat::SRecord),
+            _ => Err(format!("unknown image format: {}", s))
+        }
+    }
+}
+
+impl ImageFormat {
+    /// Guesses the format from the file extension, anything unknown is a raw binary.
+    pub fn from_path(path: &str) -> Self {
+        let extension = std::path::Path::new(path).extension().and_then(|it| it.to_str()).unwrap_or("");
+        match extension.to_ascii_lowercase().as_str() {
+            "hex" | "ihex" | "ihx" => ImageFormat::IntelHex,
+            "srec" | "s19" | "s28" | "s37" | "mot" => ImageFormat::SRecord,
+            _ => ImageFormat::Binary
+        }
+    }
+
+    /// The chunks of `image`, a raw binary is a single chunk at `origin`.
+    pub fn parse(&self, image: &[u8], origin: u16) -> Result<Vec<Chunk>, String> {
+        match self {
+            ImageFormat::Binary => Ok(vec![(origin, image.to_vec())]),
+            ImageFormat::IntelHex => records::parse_intel_hex(text(image)?),
+            ImageFormat::SRecord => records::parse_s_record(text(image)?)
+        }
+    }
+}
+
+fn text(image: &[u8]) -> Result<&str, String> {
+    std::str::from_utf8(image).map_err(|_| "image is not text".to_string())
+}
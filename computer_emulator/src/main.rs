@@ -1,246 +1,225 @@
-use std::intrinsics::unreachable;
-use crate::register::{HIGH, PC_H};
+use std::process::exit;
+use computer_emulator::{Computer, flag, register};
+use computer_emulator::debug_map::DebugMap;
+use computer_emulator::debugger::Debugger;
+use computer_emulator::devices::framebuffer::Mode;
+use computer_emulator::devices::{timer, Console, Framebuffer, TickCounter, Timer};
+use computer_emulator::loader::ImageFormat;
+use computer_emulator::trace::{TraceFormat, Tracer};
 
-fn main() {
-    println!("Hello, world!");
-}
+const USAGE: &str = "usage: computer_emulator <image> [--format bin|ihex|srec] [--origin <address>] [--entry <address>] [--max-steps <count>] [--console <address>] [--ticks <address>]\n       [--timer <address>] [--timer-line <line>] [--trace <file|->] [--trace-format text|json] [--trace-range <start>:<end>] [--debug]
+       [--debug-map <file>] [--framebuffer <address>] [--framebuffer-mode mono|palette] [--framebuffer-ppm <file>] [--framebuffer-ansi]";
 
-mod register {
-    pub const REG0: u8 = 0;
-    pub const REG1: u8 = 1;
-    pub const HIGH: u8 = 2;
-    pub const LOW : u8 = 3;
-    pub const PC_H: u8 = 4;
-    pub const PC_L: u8 = 5;
-    pub const SCTR: u8 = 6;
-    pub const FLAG: u8 = 7;
+struct Options {
+    image: String,
+    /// Guessed from the extension of `image` if not given.
+    format: Option<ImageFormat>,
+    origin: u16,
+    entry: Option<u16>,
+    max_steps: u64,
+    console: Option<u16>,
+    ticks: Option<u16>,
+    timer: Option<u16>,
+    /// Interrupt line the timer raises.
+    timer_line: u8,
+    framebuffer: Option<u16>,
+    framebuffer_mode: Mode,
+    framebuffer_ppm: Option<String>,
+    framebuffer_ansi: bool,
+    trace: Option<String>,
+    trace_format: TraceFormat,
+    trace_range: (u16, u16),
+    debug: bool,
+    debug_map: Option<String>
 }
 
-mod flag {
-    pub const HALT: u8 = 0;
-    pub const OVERFLOW: u8 = 1;
-    pub const CARRY: u8 = 2;
-    pub const BORROW: u8 = 3;
-    pub const EQUAL: u8 = 4;
-    pub const LESS: u8 = 5;
-    pub const MORE: u8 = 6;
+fn parse_number(string: &str) -> Option<u64> {
+    if let Some(hex) = string.strip_prefix("0x") {
+        u64::from_str_radix(hex, 16).ok()
+    } else {
+        string.parse().ok()
+    }
 }
 
-struct Computer {
-    registers: [u8; 2**3],
-    ram: [u8; 2**16],
-    stack: [u8; 2**8]
+fn parse_address(string: &str) -> Result<u16, String> {
+    let number = parse_number(string).ok_or(format!("invalid address: {}", string))?;
+    u16::try_from(number).map_err(|_| format!("address out of range: {}", string))
 }
 
-// static utils
-impl Computer {
-    fn set_reg8(&self, register: u8, value: u8) {
-        self.registers[register] = value
-    }
-    fn reg8(&self, register: u8) -> u8 {
-        self.registers[register]
-    }
-    fn set_reg16(&self, register: u8, value: u16) {
-        self.registers[register] = value >> 8;
-        self.registers[register+1] = value;
-    }
-    fn reg16(&self, register: u8) -> u16 {
-        ((self.registers[register] as u16) << 8) | (self.registers[register+1] as u16)
-    }
-    fn opc(&self) -> u8 { self.op_lit8() >> 4 }
-    fn address(&self) -> u16 {
-        self.reg16(register::H)
-    }
-    fn set_ram8(&self, address: u16, value: u8) {
-        self.ram[address] = value
-    }
-    fn ram8(&self, address: u16) -> u8 {
-        self.ram[address]
-    }
-    fn ram16(&self, address: u16) -> u16 {
-        (self.ram8(address) as u16) << 8 | (self.ram8(address+1) as u16)
-    }
-    fn set_flag(&self, index: u8, value: bool) {
-        self.set_reg8(register::FLAG, if value {
-            self.reg8(register::FLAG) | (1 << index)
-        } else {
-            self.reg8(register::FLAG) & !(1 << index)
-        })
-    }
-    fn flag(&self, index: u8) -> bool {
-        self.reg8(register::FLAG) >> index & 1 != 0
+/// Fails if the `size` bytes of a device mapped at `address` by `option` would run past 0xFFFF.
+fn check_fits(option: &str, address: Option<u16>, size: u16) -> Result<(), String> {
+    match address {
+        Some(address) if address.checked_add(size - 1).is_none() =>
+            Err(format!("{} 0x{:04X} does not fit: the device needs {} bytes below 0x10000", option, address, size)),
+        _ => Ok(())
     }
-    fn stack_ptr(&mut self) -> u8 { self.reg8(register::SCTR) }
-    fn set_stack_ptr(&mut self, value: u8) { self.set_reg8(register::SCTR, value) }
-    fn inc_stack_ptr(&mut self) { self.set_stack_ptr(self.stack_ptr()+1) }
-    fn dec_stack_ptr(&mut self) { self.set_stack_ptr(self.stack_ptr()-1) }
 }
 
-// current operation related utils
-impl Computer {
-    fn pc(&self) -> u16 {
-        self.reg16(register::PC_H)
-    }
-    fn pc_inc(&self) { self.set_reg16(register::PC_H, self.pc()+1)}
-    fn op_lit8(&self) -> u8 {
-        self.ram8(self.pc())
-    }
-    fn op_lit16(&self) -> u16 {
-        self.ram16(self.pc())
-    }
-    fn op_reg(&self) -> u8 {
-        self.op_lit8() & 0b111
-    }
-    fn op_flag(&self) -> bool {
-        self.ram8(self.pc()) >> 3 & 1 != 0
-    }
-    fn op_value8(&mut self) -> u8 {
-        if self.op_flag() {
-            self.pc_inc();
-            self.op_lit8()
-        } else {
-            self.pc_inc();
-            self.reg8(self.op_reg())
-        }
-    }
-    fn op_value16(&mut self) -> u16 {
-        if self.op_flag() {
-            self.reg16(register::HIGH)
-        } else {
-            self.pc_inc();
-            let ret = self.op_lit16();
-            self.pc_inc();
-            ret
+fn parse_options() -> Result<Options, String> {
+    let mut args = std::env::args().skip(1);
+    let mut image = None;
+    let mut format = None;
+    let mut origin = 0;
+    let mut entry = None;
+    let mut max_steps = 1_000_000;
+    let mut console = None;
+    let mut ticks = None;
+    let mut timer = None;
+    let mut timer_line = 0;
+    let mut framebuffer = None;
+    let mut framebuffer_mode = Mode::Mono;
+    let mut framebuffer_ppm = None;
+    let mut framebuffer_ansi = false;
+    let mut trace = None;
+    let mut trace_format = TraceFormat::Text;
+    let mut trace_range = (0x0000, 0xFFFF);
+    let mut debug = false;
+    let mut debug_map = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--origin" | "--entry" | "--max-steps" | "--console" | "--ticks" | "--timer" | "--framebuffer" => {
+                let value = args.next().ok_or(format!("missing value for {}", arg))?;
+                let number = parse_number(&value).ok_or(format!("invalid number for {}: {}", arg, value))?;
+                if arg == "--max-steps" {
+                    max_steps = number;
+                    continue
+                }
+                let address = u16::try_from(number).map_err(|_| format!("address out of range: {}", value))?;
+                match arg.as_str() {
+                    "--origin" => origin = address,
+                    "--entry" => entry = Some(address),
+                    "--console" => console = Some(address),
+                    "--timer" => timer = Some(address),
+                    "--framebuffer" => framebuffer = Some(address),
+                    _ => ticks = Some(address)
+                }
+            }
+            "--timer-line" => {
+                let value = args.next().ok_or("missing value for --timer-line")?;
+                timer_line = parse_number(&value).and_then(|it| u8::try_from(it).ok()).filter(|it| *it < isa::interrupt::LINES)
+                    .ok_or(format!("invalid interrupt line: {}", value))?;
+            }
+            "--framebuffer-mode" => framebuffer_mode = args.next().ok_or("missing value for --framebuffer-mode")?.parse()?,
+            "--framebuffer-ppm" => framebuffer_ppm = Some(args.next().ok_or("missing value for --framebuffer-ppm")?),
+            "--framebuffer-ansi" => framebuffer_ansi = true,
+            "--format" => format = Some(args.next().ok_or("missing value for --format")?.parse()?),
+            "--trace" => trace = Some(args.next().ok_or("missing value for --trace")?),
+            "--trace-format" => trace_format = match args.next().as_deref() {
+                Some("text") => TraceFormat::Text,
+                Some("json") => TraceFormat::Json,
+                _ => return Err("--trace-format expects text or json".to_string())
+            },
+            "--trace-range" => {
+                let value = args.next().ok_or("missing value for --trace-range")?;
+                let (start, end) = value.split_once(':').ok_or(format!("invalid range: {}", value))?;
+                trace_range = (parse_address(start)?, parse_address(end)?);
+            }
+            "--debug" => debug = true,
+            "--debug-map" => debug_map = Some(args.next().ok_or("missing value for --debug-map")?),
+            _ if image.is_none() => image = Some(arg),
+            _ => return Err(format!("unexpected argument: {}", arg))
         }
     }
+    check_fits("--ticks", ticks, TickCounter::SIZE)?;
+    check_fits("--timer", timer, timer::SIZE)?;
+    check_fits("--framebuffer", framebuffer, framebuffer_mode.size() + 1)?;
+    Ok(Options { image: image.ok_or("missing image")?, format, origin, entry, max_steps, console, ticks, timer, timer_line,
+        framebuffer, framebuffer_mode, framebuffer_ppm, framebuffer_ansi, trace, trace_format, trace_range, debug, debug_map })
 }
 
-// Execution Manager
-impl Computer {
-    fn run(&mut self) {
-        while !self.flag(flag::HALT) {
-            self.step()
-        }
+fn print_state(computer: &Computer) {
+    for (index, name) in register::NAMES.iter().enumerate() {
+        println!("{:<9} 0x{:02X}", name, computer.reg8(index as u8));
     }
-    fn step(&mut self) {
-        match self.opc() {
-            0x0 => self.run_nop(),
-            0x1 => self.run_mov(),
-            0x2 => self.run_ldw(),
-            0x3 => self.run_stw(),
-            0x4 => self.run_lda(),
-            0x5 => self.run_psh(),
-            0x6 => self.run_pop(),
-            0x7 => self.run_jmp(),
-            0x8 => self.run_add(),
-            0x9 => self.run_sub(),
-            0xA => self.run_and(),
-            0xB => self.run_or(),
-            0xC => self.run_inv(),
-            0xD => self.run_cmp(),
-            0xE => self.run_shl(),
-            0xF => self.run_shr(),
-            _ => unreachable!()
-        }
+    for (index, name) in flag::NAMES.iter().enumerate() {
+        println!("{:<9} {}", name, computer.flag(index as u8) as u8);
     }
 }
 
-// OP Implementations
-impl Computer {
-    fn run_nop(self) { }
-    fn run_mov(&mut self) {
-        self.set_reg8(
-            self.op_reg(),
-            self.op_value8()
-        );
-        self.pc_inc()
-    }
-    fn run_ldw(&mut self) {
-        self.set_reg8(
-            self.op_reg(),
-            self.ram8(self.op_value16())
-        );
-        self.pc_inc();
-    }
-    fn run_stw(&mut self) {
-        let value = self.op_reg();
-        let dest = self.op_value16();
-        self.set_ram8(
-            dest,
-            value
-        );
-        self.pc_inc();
-    }
-    fn run_lda(&mut self) {
-        self.set_reg16(register::HIGH, self.ram16(self.op_value16()));
-        self.pc_inc();
-    }
-    fn run_psh(&mut self) {
-        self.stack[self.stack_ptr()] = self.reg8(self.op_reg());
-        self.inc_stack_ptr();
-        self.pc_inc();
-    }
-    fn run_pop(&mut self) {
-        self.set_reg8(self.op_reg(), self.stack[self.stack_ptr()]);
-        self.dec_stack_ptr();
-        self.pc_inc();
-    }
-    fn run_jmp(&mut self) {
-        if self.flag(self.op_reg()) {
-            self.set_reg16(register::PC_H, self.op_value16())
+fn main() {
+    let options = parse_options().unwrap_or_else(|message| {
+        eprintln!("{}\n{}", message, USAGE);
+        exit(2)
+    });
+    let image = std::fs::read(&options.image).unwrap_or_else(|err| {
+        eprintln!("failed to read {}: {}", options.image, err);
+        exit(2)
+    });
+    let debug_map = options.debug_map.map(|path| DebugMap::load(&path).unwrap_or_else(|message| {
+        eprintln!("{}", message);
+        exit(2)
+    }));
+    let mut computer = Computer::new();
+    if let Some(address) = options.console {
+        computer.map(address..=address, Box::new(Console::stdio()));
+    }
+    if let Some(address) = options.ticks {
+        computer.map(address..=address + (TickCounter::SIZE - 1), Box::new(TickCounter::new()));
+    }
+    if let Some(address) = options.timer {
+        computer.map_with_interrupt(address..=address + (timer::SIZE - 1), Box::new(Timer::new()), options.timer_line);
+    }
+    if let Some(address) = options.framebuffer {
+        let mut framebuffer = Framebuffer::new(options.framebuffer_mode);
+        if let Some(path) = options.framebuffer_ppm {
+            framebuffer = framebuffer.with_ppm(path.into());
         }
-        self.pc_inc();
-    }
-    fn run_add(&mut self) {
-        let result_reg = self.op_reg();
-        let value = self.op_value8();
-        let result = (self.reg8(result_reg) as i8).overflowing_add(value as i8) as (u8, bool);
-        self.set_reg8(result_reg, result.0);
-        self.set_flag(flag::CARRY, result.1);
-        self.pc_inc();
-    }
-    fn run_sub(&mut self) {
-        let result_reg = self.op_reg();
-        let value = self.op_value8();
-        let result = (self.reg8(result_reg) as i8).overflowing_sub(value as i8) as (u8, bool);
-        self.set_reg8(result_reg, result.0);
-        self.set_flag(flag::BORROW, result.1);
-        self.pc_inc();
-    }
-    fn run_and(&mut self) {
-        let result_reg = self.op_reg();
-        let value = self.op_value8();
-        self.set_reg8(result_reg, self.reg8(result_reg)&value);
-        self.pc_inc();
-    }
-    fn run_or(&mut self) {
-        let result_reg = self.op_reg();
-        let value = self.op_value8();
-        self.set_reg8(result_reg, self.reg8(result_reg)|value);
-        self.pc_inc();
-    }
-    fn run_inv(&mut self) {
-        let result_reg = self.op_reg();
-        self.set_reg8(result_reg, !self.reg8(result_reg));
-        self.pc_inc();
-    }
-    fn run_cmp(&mut self) {
-        let a = self.op_reg();
-        let b = self.op_value8();
-        self.set_flag(flag::LESS, a < b);
-        self.set_flag(flag::EQUAL, a == b);
-        self.pc_inc();
-    }
-    fn run_shl(&mut self) {
-        let result_reg = self.op_reg();
-        let value = self.op_value8();
-        self.set_reg8(result_reg, self.reg8(result_reg) << value);
-        self.pc_inc();
+        if options.framebuffer_ansi {
+            framebuffer = framebuffer.with_terminal(Box::new(std::io::stdout()));
+        }
+        // pixels and the control register after them
+        computer.map(address..=address + options.framebuffer_mode.size(), Box::new(framebuffer));
     }
-    fn run_shr(&mut self) {
-        let result_reg = self.op_reg();
-        let value = self.op_value8();
-        self.set_reg8(result_reg, self.reg8(result_reg) << value);
-        self.pc_inc();
+    if let Some(path) = options.trace {
+        let output: Box<dyn std::io::Write> = if path == "-" {
+            Box::new(std::io::stderr())
+        } else {
+            Box::new(std::io::BufWriter::new(std::fs::File::create(&path).unwrap_or_else(|err| {
+                eprintln!("failed to create {}: {}", path, err);
+                exit(2)
+            })))
+        };
+        let (start, end) = options.trace_range;
+        let mut tracer = Tracer::new(options.trace_format, output).with_range(start..=end);
+        if let Some(debug_map) = &debug_map {
+            tracer = tracer.with_debug_map(debug_map.clone());
+        }
+        computer.set_tracer(Some(tracer));
+    }
+    let format = options.format.unwrap_or_else(|| ImageFormat::from_path(&options.image));
+    let chunks = format.parse(&image, options.origin).unwrap_or_else(|message| {
+        eprintln!("failed to load {}: {}", options.image, message);
+        exit(2)
+    });
+    for (address, bytes) in &chunks {
+        computer.load(bytes, *address);
+    }
+    // hex images carry their own addresses, execution starts at the lowest one
+    let start = chunks.iter().map(|it| it.0).min().unwrap_or(options.origin);
+    computer.set_pc(options.entry.unwrap_or(start));
+    if options.debug {
+        let mut debugger = Debugger::new(computer).with_max_steps(options.max_steps);
+        if let Some(debug_map) = debug_map {
+            debugger = debugger.with_debug_map(debug_map);
+        }
+        debugger.repl(std::io::stdin().lock(), &mut std::io::stdout()).unwrap_or_else(|err| {
+            eprintln!("{}", err);
+            exit(2)
+        });
+        debugger.computer.bus_mut().stop();
+        return
+    }
+    let mut steps = 0;
+    while !computer.stopped() {
+        if steps == options.max_steps {
+            computer.bus_mut().stop();
+            print_state(&computer);
+            eprintln!("instruction budget of {} exceeded", options.max_steps);
+            exit(1)
+        }
+        computer.step();
+        steps += 1;
     }
+    computer.bus_mut().stop();
+    print_state(&computer);
 }
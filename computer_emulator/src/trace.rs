@@ -0,0 +1,134 @@
+use std::io::Write;
+use std::ops::RangeInclusive;
+use disassembler::decode_lenient;
+use crate::debug_map::DebugMap;
+use crate::{flag, register};
+
+pub enum TraceFormat {
+    Text,
+    /// One JSON object per line.
+    Json
+}
+
+/// Everything about a single executed instruction.
+pub struct TraceRecord {
+    pub pc: u16,
+    pub bytes: Vec<u8>,
+    pub registers_before: [u8; 1 << 3],
+    pub registers_after: [u8; 1 << 3]
+}
+
+impl TraceRecord {
+    /// Registers other than PC_H, PC_L and FLAG that changed, as (name, before, after).
+    pub fn register_deltas(&self) -> Vec<(&'static str, u8, u8)> {
+        (0..register::NAMES.len())
+            .filter(|it| ![register::PC_H, register::PC_L, register::FLAG].contains(&(*it as u8)))
+            .filter(|it| self.registers_before[*it] != self.registers_after[*it])
+            .map(|it| (register::NAMES[it], self.registers_before[it], self.registers_after[it]))
+            .collect()
+    }
+    pub fn flag_deltas(&self) -> Vec<(&'static str, bool, bool)> {
+        let before = self.registers_before[register::FLAG as usize];
+        let after = self.registers_after[register::FLAG as usize];
+        (0..flag::NAMES.len())
+            .filter(|it| (before ^ after) >> it & 1 != 0)
+            .map(|it| (flag::NAMES[it], before >> it & 1 != 0, after >> it & 1 != 0))
+            .collect()
+    }
+    /// Mnemonic and operands, `None` if the bytes do not decode.
+    pub fn decoded(&self) -> Option<(&'static str, Vec<String>)> {
+        decode_lenient(&self.bytes).map(|(mnemonic, operands, _)| (mnemonic, operands.iter().map(|it| it.to_string()).collect()))
+    }
+}
+
+pub struct Tracer {
+    format: TraceFormat,
+    range: RangeInclusive<u16>,
+    output: Box<dyn Write>,
+    debug_map: Option<DebugMap>
+}
+
+impl Tracer {
+    pub fn new(format: TraceFormat, output: Box<dyn Write>) -> Self {
+        Tracer { format, range: 0x0000..=0xFFFF, output, debug_map: None }
+    }
+    /// Only trace instructions whose address lies in `range`.
+    pub fn with_range(mut self, range: RangeInclusive<u16>) -> Self {
+        self.range = range;
+        self
+    }
+    /// Show the label and source line of every traced instruction.
+    pub fn with_debug_map(mut self, debug_map: DebugMap) -> Self {
+        self.debug_map = Some(debug_map);
+        self
+    }
+    pub fn traces(&self, pc: u16) -> bool {
+        self.range.contains(&pc)
+    }
+    pub fn record(&mut self, record: &TraceRecord) {
+        let line = match self.format {
+            TraceFormat::Text => text(record, self.debug_map.as_ref()),
+            TraceFormat::Json => json(record, self.debug_map.as_ref())
+        };
+        let _ = writeln!(self.output, "{}", line);
+    }
+}
+
+fn text(record: &TraceRecord, debug_map: Option<&DebugMap>) -> String {
+    let bytes: Vec<String> = record.bytes.iter().map(|it| format!("{:02X}", it)).collect();
+    let instruction = match record.decoded() {
+        Some((mnemonic, operands)) => std::iter::once(mnemonic.to_string()).chain(operands).collect::<Vec<_>>().join(" "),
+        None => "???".to_string()
+    };
+    let mut result = format!("{:04X}  {:<9} {:<20}", record.pc, bytes.join(" "), instruction);
+    for (name, before, after) in record.register_deltas() {
+        result += &format!(" {} {:02X}->{:02X}", name, before, after);
+    }
+    for (name, before, after) in record.flag_deltas() {
+        result += &format!(" {} {}->{}", name, before as u8, after as u8);
+    }
+    if let Some(description) = debug_map.map(|it| it.describe(record.pc)).filter(|it| !it.is_empty()) {
+        result += &format!(" ; {}", description);
+    }
+    result.trim_end().to_string()
+}
+
+/// `string` as a JSON string literal.
+fn json_string(string: &str) -> String {
+    let mut result = String::from("\"");
+    for char in string.chars() {
+        match char {
+            '"' => result += "\\\"",
+            '\\' => result += "\\\\",
+            char if char.is_control() => result += &format!("\\u{:04x}", char as u32),
+            char => result.push(char)
+        }
+    }
+    result + "\""
+}
+
+fn json(record: &TraceRecord, debug_map: Option<&DebugMap>) -> String {
+    let bytes: Vec<String> = record.bytes.iter().map(|it| it.to_string()).collect();
+    let (mnemonic, operands) = match record.decoded() {
+        Some((mnemonic, operands)) => (
+            format!("\"{}\"", mnemonic),
+            operands.iter().map(|it| format!("\"{}\"", it)).collect::<Vec<_>>()
+        ),
+        None => ("null".to_string(), vec![])
+    };
+    let registers: Vec<String> = record.register_deltas().iter()
+        .map(|(name, before, after)| format!("\"{}\":[{},{}]", name, before, after))
+        .collect();
+    let flags: Vec<String> = record.flag_deltas().iter()
+        .map(|(name, before, after)| format!("\"{}\":[{},{}]", name, before, after))
+        .collect();
+    let symbol = debug_map.and_then(|it| it.symbol(record.pc)).map(|it| json_string(&it)).unwrap_or("null".to_string());
+    let source = match debug_map.and_then(|map| map.source(record.pc).map(|source| (map, source))) {
+        Some((map, source)) => format!("{{\"file\":{},\"line\":{},\"text\":{}}}", json_string(map.file(source.file)), source.line, json_string(&source.text)),
+        None => "null".to_string()
+    };
+    format!(
+        "{{\"pc\":{},\"bytes\":[{}],\"mnemonic\":{},\"operands\":[{}],\"registers\":{{{}}},\"flags\":{{{}}},\"symbol\":{},\"source\":{}}}",
+        record.pc, bytes.join(","), mnemonic, operands.join(","), registers.join(","), flags.join(","), symbol, source
+    )
+}
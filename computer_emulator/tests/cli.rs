@@ -0,0 +1,39 @@
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+/// A directory holding `program.bin`, which halts right away.
+fn program(name: &str) -> PathBuf {
+    let directory = std::env::temp_dir().join(format!("computer-emulator-{}-{}", name, std::process::id()));
+    std::fs::create_dir_all(&directory).unwrap();
+    std::fs::write(directory.join("program.bin"), [0x01, 0x00]).unwrap();
+    directory
+}
+
+fn run(name: &str, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_computer_emulator")).current_dir(program(name)).arg("program.bin").args(args).output().unwrap()
+}
+
+#[test]
+fn devices_at_the_end_of_memory() {
+    for args in [["--ticks", "0xFFFC"], ["--timer", "0xFFF9"], ["--framebuffer", "0xFBFF"]] {
+        let output = run("devices-fit", &args);
+        assert!(output.status.success(), "{:?}: {}", args, String::from_utf8_lossy(&output.stderr));
+    }
+    let output = run("devices-fit-palette", &["--framebuffer-mode", "palette", "--framebuffer", "0xF7FF"]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+}
+
+#[test]
+fn devices_past_the_end_of_memory() {
+    let cases = [
+        (vec!["--ticks", "0xFFFD"], "--ticks 0xFFFD does not fit: the device needs 4 bytes"),
+        (vec!["--timer", "0xFFFA"], "--timer 0xFFFA does not fit: the device needs 7 bytes"),
+        (vec!["--framebuffer", "0xFC00"], "--framebuffer 0xFC00 does not fit: the device needs 1025 bytes"),
+        (vec!["--framebuffer", "0xF800", "--framebuffer-mode", "palette"], "--framebuffer 0xF800 does not fit: the device needs 2049 bytes")
+    ];
+    for (args, message) in cases {
+        let output = run("devices-overflow", &args);
+        assert_eq!(output.status.code(), Some(2), "{:?}", args);
+        assert!(String::from_utf8_lossy(&output.stderr).contains(message), "{}", String::from_utf8_lossy(&output.stderr));
+    }
+}
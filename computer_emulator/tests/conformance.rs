@@ -0,0 +1,90 @@
+use std::fs;
+use std::path::Path;
+use computer_emulator::{Computer, flag, register};
+use assembler::{assemble, Sources};
+
+const MAX_STEPS: usize = 1000;
+
+fn parse_number(string: &str) -> i64 {
+    let (negative, string) = match string.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, string)
+    };
+    let value = if let Some(hex) = string.strip_prefix("0x") {
+        i64::from_str_radix(hex, 16)
+    } else {
+        string.parse()
+    }.unwrap_or_else(|_| panic!("invalid number: {}", string));
+    if negative { -value } else { value }
+}
+
+fn name_index(names: &[&str], name: &str) -> Option<u8> {
+    names.iter().position(|it| it.eq_ignore_ascii_case(name)).map(|it| it as u8)
+}
+
+fn index(string: &str, prefix: &str) -> Option<i64> {
+    string.strip_prefix(prefix)?.strip_suffix(']').map(parse_number)
+}
+
+/// Checks every `name = value` line of the expectation block, returning the mismatches.
+fn check(computer: &Computer, expectations: &str) -> Vec<String> {
+    let mut failures = vec![];
+    for line in expectations.lines().map(str::trim).filter(|it| !it.is_empty() && !it.starts_with(';')) {
+        let (name, expected) = line.split_once('=').unwrap_or_else(|| panic!("invalid expectation: {}", line));
+        let (name, expected) = (name.trim(), parse_number(expected.trim()));
+        let found = if let Some(register) = name_index(&register::NAMES, name) {
+            computer.reg8(register) as i64
+        } else if let Some(flag) = name_index(&flag::NAMES, name) {
+            computer.flag(flag) as i64
+        } else if let Some(address) = index(name, "ram[") {
+            computer.ram8(address as u16) as i64
+        } else if let Some(address) = index(name, "stack[") {
+            computer.stack()[address as usize] as i64
+        } else {
+            panic!("unknown name in expectation: {}", name)
+        };
+        if found != expected {
+            failures.push(format!("{}: expected 0x{:02X}, found 0x{:02X}", name, expected, found));
+        }
+    }
+    failures
+}
+
+fn run_golden(path: &Path) -> Vec<String> {
+    let content = fs::read_to_string(path).unwrap();
+    let (program, expectations) = content.split_once("\n---\n").unwrap_or_else(|| panic!("{} has no --- separator", path.display()));
+    let mut sources = Sources::new(vec![]);
+    sources.add(path.to_path_buf(), program.to_string());
+    let assembly = match assemble(&mut sources, &[]) {
+        Ok(assembly) => assembly,
+        Err(error) => return vec![error.report(&sources)]
+    };
+    let mut computer = Computer::new();
+    for (address, bytes) in assembly.chunks() {
+        computer.load(&bytes, address);
+    }
+    let mut steps = 0;
+    while !computer.flag(flag::HALT) {
+        if steps == MAX_STEPS {
+            return vec![format!("did not halt within {} steps", MAX_STEPS)]
+        }
+        computer.step();
+        steps += 1;
+    }
+    check(&computer, expectations)
+}
+
+#[test]
+fn golden_files() {
+    let directory = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden");
+    let mut paths: Vec<_> = fs::read_dir(directory).unwrap().map(|it| it.unwrap().path()).collect();
+    paths.sort();
+    assert!(!paths.is_empty());
+    let mut failures = vec![];
+    for path in paths {
+        for failure in run_golden(&path) {
+            failures.push(format!("{}: {}", path.file_name().unwrap().to_string_lossy(), failure));
+        }
+    }
+    assert!(failures.is_empty(), "\n{}", failures.join("\n"));
+}
@@ -0,0 +1,95 @@
+use std::io::Cursor;
+use assembler::{assemble, Sources};
+use computer_emulator::debugger::Debugger;
+use computer_emulator::{Computer, flag, register};
+
+fn debugger(source: &str) -> Debugger {
+    let mut sources = Sources::new(vec![]);
+    sources.add("debugger.asm".into(), source.to_string());
+    let assembly = assemble(&mut sources, &[]).unwrap_or_else(|error| panic!("{}", error.report(&sources)));
+    let mut computer = Computer::new();
+    for (address, bytes) in assembly.chunks() {
+        computer.load(&bytes, address);
+    }
+    Debugger::new(computer)
+}
+
+/// Runs `commands` and returns every line of output.
+fn run(debugger: &mut Debugger, commands: &str) -> Vec<String> {
+    let mut out = vec![];
+    debugger.repl(Cursor::new(commands), &mut out).unwrap();
+    String::from_utf8(out).unwrap().lines().map(str::to_string).collect()
+}
+
+const CALL: &str = "mov reg0 3\ncall @double\nmov reg1 1\nhlt\n@double:\nadd reg0 reg0\nret\n";
+
+#[test]
+fn step() {
+    let mut debugger = debugger(CALL);
+    assert_eq!(run(&mut debugger, "s\ns 2\n"), ["0002: 01 18 00 0A", "000C: 01 20"]);
+    assert_eq!(debugger.computer.reg8(register::REG0), 6);
+}
+
+#[test]
+fn break_and_continue() {
+    let mut debugger = debugger(CALL);
+    assert_eq!(run(&mut debugger, "b 8\nb 0x0C\nl\nc\nd 0x0C\nc\nc\n"), [
+        "0008", "000C", "000C: 01 20", "0008: 01 00", "000A: 80 00 (halted)"
+    ]);
+    assert_eq!(debugger.computer.reg8(register::REG1), 1);
+    assert_eq!(run(&mut debugger, "c\n"), ["000A: 80 00 (halted)"]);
+}
+
+#[test]
+fn next_steps_over_call() {
+    let mut debugger = debugger(CALL);
+    assert_eq!(run(&mut debugger, "s\nn\n"), ["0002: 01 18 00 0A", "0006: 19 01"]);
+    assert_eq!(debugger.computer.reg8(register::REG0), 6);
+}
+
+#[test]
+fn next_steps_over_push_jump_sequence() {
+    let mut debugger = debugger("psh 0\npsh 7\njmp flag0 @double\nhlt\n@double:\nadd reg0 1\nret\n");
+    assert_eq!(run(&mut debugger, "n\n"), ["0007: 01 00"]);
+    assert_eq!(debugger.computer.reg8(register::REG0), 1);
+}
+
+#[test]
+fn next_single_steps_plain_jumps() {
+    let mut debugger = debugger("@loop:\nadd reg0 1\njmp flag0 @loop\n");
+    assert_eq!(run(&mut debugger, "s\nn\nn\n"), ["0002: 78 00 00", "0000: 88 01", "0002: 78 00 00"]);
+    // a push that does not push the address after the jump is not a call either
+    let mut debugger = self::debugger("@loop:\npsh 0\npsh 0\njmp flag0 @loop\n");
+    assert_eq!(run(&mut debugger, "n\n"), ["0002: 58 00"]);
+}
+
+#[test]
+fn registers_and_memory() {
+    let mut debugger = debugger(CALL);
+    let output = run(&mut debugger, "m 0 4\nw 0x100 1 2 3\nm 0x100 3\nr reg1 0x42\nr\nx\n");
+    assert_eq!(output[..2], ["0000: 18 03 01 18", "0100: 01 02 03"]);
+    assert_eq!(output[3], "REG1      0x42");
+    assert_eq!(output.last().unwrap(), "error: unknown command: x (h for help)");
+}
+
+#[test]
+fn register_and_flag_aliases() {
+    let mut debugger = debugger(CALL);
+    run(&mut debugger, "r sp 0x80\nr H 1\nf carry 1\n");
+    assert_eq!(debugger.computer.reg8(register::SCTR), 0x80);
+    assert_eq!(debugger.computer.reg8(register::HIGH), 1);
+    assert!(debugger.computer.flag(flag::CARRY));
+}
+
+#[test]
+fn step_budget() {
+    let mut debugger = debugger("@loop:\njmp flag0 @loop\n").with_max_steps(10);
+    assert_eq!(run(&mut debugger, "c\nc\n"), ["step budget of 10 exceeded", "0000: 78 00 00", "step budget of 10 exceeded", "0000: 78 00 00"]);
+}
+
+#[test]
+fn quit_stops_reading() {
+    let mut debugger = debugger(CALL);
+    assert_eq!(run(&mut debugger, "q\ns\n"), Vec::<String>::new());
+    assert_eq!(debugger.computer.pc(), 0);
+}
@@ -0,0 +1,67 @@
+use computer_emulator::bus::Device;
+use computer_emulator::devices::framebuffer::{Framebuffer, Mode, PALETTE};
+use computer_emulator::Computer;
+
+const WHITE: [u8; 3] = PALETTE[15];
+const BLACK: [u8; 3] = PALETTE[0];
+
+#[test]
+fn mono_pixels_start_at_the_high_bit() {
+    let mut framebuffer = Framebuffer::new(Mode::Mono);
+    framebuffer.write(0, 0b1000_0001);
+    framebuffer.write(16, 0b0100_0000);
+    assert_eq!(framebuffer.pixel(0, 0), WHITE);
+    assert_eq!(framebuffer.pixel(1, 0), BLACK);
+    assert_eq!(framebuffer.pixel(7, 0), WHITE);
+    assert_eq!(framebuffer.pixel(1, 1), WHITE);
+    assert_eq!(framebuffer.read(16), 0b0100_0000);
+}
+
+#[test]
+fn palette_pixels_use_nibbles() {
+    let mut framebuffer = Framebuffer::new(Mode::Palette);
+    framebuffer.write(33, 0x4A);
+    assert_eq!(framebuffer.pixel(2, 1), PALETTE[4]);
+    assert_eq!(framebuffer.pixel(3, 1), PALETTE[10]);
+    assert_eq!(Mode::Palette.size(), 2048);
+}
+
+#[test]
+fn ppm_has_every_pixel() {
+    let mut framebuffer = Framebuffer::new(Mode::Mono);
+    framebuffer.write(Mode::Mono.size() - 1, 1);
+    let mut ppm = vec![];
+    framebuffer.write_ppm(&mut ppm).unwrap();
+    let header = b"P6\n128 64\n255\n";
+    assert_eq!(&ppm[..header.len()], header);
+    assert_eq!(ppm.len(), header.len() + 128 * 64 * 3);
+    assert_eq!(ppm[ppm.len() - 6..], [BLACK, WHITE].concat());
+}
+
+#[test]
+fn ansi_mono_uses_half_blocks() {
+    let mut framebuffer = Framebuffer::new(Mode::Mono);
+    framebuffer.write(0, 0b1010_0000);
+    framebuffer.write(16, 0b1100_0000);
+    let mut ansi = vec![];
+    framebuffer.write_ansi(&mut ansi).unwrap();
+    let ansi = String::from_utf8(ansi).unwrap();
+    let lines: Vec<&str> = ansi.lines().collect();
+    assert_eq!(lines.len(), 32);
+    assert!(lines[0].starts_with("█▄▀ "));
+    assert_eq!(lines[0].chars().count(), 128);
+}
+
+#[test]
+fn control_register_renders_a_frame() {
+    let path = std::env::temp_dir().join(format!("framebuffer-{}.ppm", std::process::id()));
+    let mut computer = Computer::new();
+    let framebuffer = Framebuffer::new(Mode::Mono).with_ppm(path.clone());
+    computer.map(0x8000..=0x8000 + Mode::Mono.size(), Box::new(framebuffer));
+    computer.set_ram8(0x8000, 0x80);
+    assert!(!path.exists());
+    computer.set_ram8(0x8000 + Mode::Mono.size(), 1);
+    let ppm = std::fs::read(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(ppm[14..17], WHITE);
+}
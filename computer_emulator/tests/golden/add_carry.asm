@@ -0,0 +1,7 @@
+mov reg0 -1
+add reg0 1
+or reg7 1
+---
+REG0 = 0
+CARRY = 1
+OVERFLOW = 0
@@ -0,0 +1,8 @@
+mov reg0 5
+mov reg1 7
+add reg0 reg1
+or reg7 1
+---
+REG0 = 12
+CARRY = 0
+OVERFLOW = 0
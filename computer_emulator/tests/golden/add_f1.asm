@@ -0,0 +1,7 @@
+mov reg0 5
+add reg0 -2
+or reg7 1
+---
+REG0 = 3
+CARRY = 1
+OVERFLOW = 0
@@ -0,0 +1,7 @@
+mov reg0 0x7F
+add reg0 1
+or reg7 1
+---
+REG0 = 0x80
+OVERFLOW = 1
+CARRY = 0
@@ -0,0 +1,6 @@
+mov reg0 0x0C
+mov reg1 0x0A
+and reg0 reg1
+or reg7 1
+---
+REG0 = 0x08
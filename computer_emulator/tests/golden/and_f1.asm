@@ -0,0 +1,5 @@
+mov reg0 -1
+and reg0 0x5A
+or reg7 1
+---
+REG0 = 0x5A
@@ -0,0 +1,6 @@
+brk
+mov reg0 1
+hlt
+---
+REG0 = 1
+PC_L = 6
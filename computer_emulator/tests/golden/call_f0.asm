@@ -0,0 +1,11 @@
+mov reg2 0
+mov reg3 8
+call hl
+hlt
+mov reg1 2
+ret
+---
+REG1 = 2
+SCTR = 0
+stack[1] = 0x06
+PC_L = 8
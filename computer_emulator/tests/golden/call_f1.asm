@@ -0,0 +1,11 @@
+mov reg6 0x10
+call 8
+hlt
+mov reg0 1
+ret
+---
+REG0 = 1
+SCTR = 0x10
+stack[0x10] = 0x00
+stack[0x11] = 0x06
+PC_L = 8
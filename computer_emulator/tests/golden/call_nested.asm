@@ -0,0 +1,17 @@
+mov reg0 3
+mov reg1 7
+psh reg1
+call 12
+pop reg1
+hlt
+add reg0 reg0
+call 20
+ret
+add reg0 reg0
+ret
+---
+; reg1 is caller saved, the subroutine at 12 calls the one at 20 to double reg0 again
+REG0 = 12
+REG1 = 7
+SCTR = 0
+PC_L = 12
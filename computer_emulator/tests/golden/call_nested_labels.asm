@@ -0,0 +1,19 @@
+mov reg0 3
+mov reg1 7
+psh reg1
+call @quadruple
+pop reg1
+hlt
+@quadruple:
+add reg0 reg0
+call @double
+ret
+@double:
+add reg0 reg0
+ret
+---
+; call_nested.asm with labels, @quadruple is at 12 and calls @double at 20
+REG0 = 12
+REG1 = 7
+SCTR = 0
+PC_L = 12
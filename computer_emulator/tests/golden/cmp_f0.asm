@@ -0,0 +1,9 @@
+mov reg0 3
+mov reg1 5
+cmp reg0 reg1
+or reg7 1
+---
+LESS = 1
+EQUAL = 0
+MORE = 0
+REG0 = 3
@@ -0,0 +1,7 @@
+mov reg0 5
+cmp reg0 5
+or reg7 1
+---
+LESS = 0
+EQUAL = 1
+MORE = 0
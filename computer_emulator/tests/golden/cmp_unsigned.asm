@@ -0,0 +1,7 @@
+mov reg0 -1
+cmp reg0 1
+or reg7 1
+---
+LESS = 0
+EQUAL = 0
+MORE = 1
@@ -0,0 +1,8 @@
+mov reg0 1
+hlt
+mov reg0 2
+---
+; the instruction after hlt is not executed
+HALT = 1
+REG0 = 1
+PC_L = 4
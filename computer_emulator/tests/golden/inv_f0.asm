@@ -0,0 +1,6 @@
+mov reg0 0x0F
+inv reg0
+or reg7 1
+---
+REG0 = 0xF0
+PC_L = 5
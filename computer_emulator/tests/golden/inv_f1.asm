@@ -0,0 +1,6 @@
+mov reg0 0x0F
+.byte 0xC8
+or reg7 1
+---
+; 0xC8 is inv reg0 with F set
+REG0 = 0xF0
@@ -0,0 +1,10 @@
+mov reg2 0
+mov reg3 7
+jmp flag0 hl
+mov reg0 1
+mov reg1 2
+or reg7 1
+---
+; the first mov after the jump is skipped
+REG0 = 0
+REG1 = 2
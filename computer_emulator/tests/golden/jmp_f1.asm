@@ -0,0 +1,8 @@
+jmp flag0 5
+mov reg0 1
+mov reg1 2
+or reg7 1
+---
+; the first mov after the jump is skipped
+REG0 = 0
+REG1 = 2
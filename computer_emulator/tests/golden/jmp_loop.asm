@@ -0,0 +1,12 @@
+mov reg0 3
+sub reg0 1
+add reg1 1
+cmp reg0 0
+jmp flag6 2
+or reg7 1
+---
+; jumps back to the sub at 2 while reg0 is more than 0
+REG0 = 0
+REG1 = 3
+EQUAL = 1
+MORE = 0
@@ -0,0 +1,8 @@
+cmp reg0 1
+jmp flag4 9
+mov reg0 1
+or reg7 1
+---
+REG0 = 1
+LESS = 1
+EQUAL = 0
@@ -0,0 +1,11 @@
+mov reg0 0x12
+stw reg0 0x0300
+mov reg0 0x34
+stw reg0 0x0301
+mov reg2 0x03
+mov reg3 0x00
+lda hl
+or reg7 1
+---
+HIGH = 0x12
+LOW = 0x34
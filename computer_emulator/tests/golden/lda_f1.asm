@@ -0,0 +1,10 @@
+mov reg0 0x12
+stw reg0 0x0300
+mov reg0 0x34
+stw reg0 0x0301
+lda 0x0300
+or reg7 1
+---
+HIGH = 0x12
+LOW = 0x34
+PC_L = 15
@@ -0,0 +1,9 @@
+mov reg0 99
+stw reg0 0x0100
+mov reg2 1
+mov reg3 0
+ldw reg1 hl
+or reg7 1
+---
+REG1 = 99
+ram[0x0100] = 99
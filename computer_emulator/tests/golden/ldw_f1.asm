@@ -0,0 +1,6 @@
+mov reg0 -5
+stw reg0 0x0200
+ldw reg1 0x0200
+or reg7 1
+---
+REG1 = 0xFB
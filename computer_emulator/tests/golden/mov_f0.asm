@@ -0,0 +1,6 @@
+mov reg1 42
+mov reg0 reg1
+or reg7 1
+---
+REG0 = 42
+REG1 = 42
@@ -0,0 +1,6 @@
+mov reg0 -1
+mov reg1 0x7F
+or reg7 1
+---
+REG0 = 0xFF
+REG1 = 0x7F
@@ -0,0 +1,5 @@
+nop
+nop
+or reg7 1
+---
+PC_L = 4
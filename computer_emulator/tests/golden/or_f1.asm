@@ -0,0 +1,5 @@
+mov reg0 0x50
+or reg0 0x0A
+or reg7 1
+---
+REG0 = 0x5A
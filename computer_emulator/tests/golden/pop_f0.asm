@@ -0,0 +1,9 @@
+psh 5
+psh 6
+pop reg0
+pop reg1
+or reg7 1
+---
+REG0 = 6
+REG1 = 5
+SCTR = 0
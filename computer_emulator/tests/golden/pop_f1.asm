@@ -0,0 +1,7 @@
+psh 9
+.byte 0x68
+or reg7 1
+---
+; 0x68 is pop reg0 with F set
+REG0 = 9
+SCTR = 0
@@ -0,0 +1,6 @@
+mov reg0 11
+psh reg0
+or reg7 1
+---
+stack[0] = 11
+SCTR = 1
@@ -0,0 +1,8 @@
+psh 22
+psh -1
+or reg7 1
+---
+stack[0] = 22
+stack[1] = 0xFF
+SCTR = 2
+PC_L = 6
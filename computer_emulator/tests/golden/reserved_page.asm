@@ -0,0 +1,7 @@
+.byte 0x08, 0x00
+mov reg0 1
+---
+; page 8 is reserved and halts
+HALT = 1
+REG0 = 0
+PC_L = 2
@@ -0,0 +1,7 @@
+.byte 0x01, 0xF0
+mov reg0 1
+---
+; sub-opcode 0xF of page 1 is reserved and halts
+HALT = 1
+REG0 = 0
+PC_L = 2
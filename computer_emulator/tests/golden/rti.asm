@@ -0,0 +1,15 @@
+psh 0
+psh 10
+psh 0x86
+rti
+mov reg0 1
+mov reg1 2
+or reg7 1
+---
+; pushes PC_H, PC_L of the second mov at 10 and FLAG with INTERRUPT, CARRY and OVERFLOW, the first mov is skipped
+REG0 = 0
+REG1 = 2
+INTERRUPT = 1
+CARRY = 1
+OVERFLOW = 1
+SCTR = 0
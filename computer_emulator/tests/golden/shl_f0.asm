@@ -0,0 +1,6 @@
+mov reg0 1
+mov reg1 3
+shl reg0 reg1
+or reg7 1
+---
+REG0 = 8
@@ -0,0 +1,8 @@
+mov reg0 -127
+shl reg0 1
+mov reg1 1
+shl reg1 8
+or reg7 1
+---
+REG0 = 0x02
+REG1 = 0
@@ -0,0 +1,6 @@
+mov reg0 -128
+mov reg1 7
+shr reg0 reg1
+or reg7 1
+---
+REG0 = 1
@@ -0,0 +1,8 @@
+mov reg0 0x70
+shr reg0 4
+mov reg1 -1
+shr reg1 8
+or reg7 1
+---
+REG0 = 0x07
+REG1 = 0
@@ -0,0 +1,11 @@
+mov reg6 -1
+psh 1
+psh 2
+pop reg0
+pop reg1
+pop reg0
+or reg7 1
+---
+REG0 = 0
+REG1 = 1
+SCTR = 0xFE
@@ -0,0 +1,8 @@
+mov reg6 -1
+psh 1
+psh 2
+or reg7 1
+---
+stack[0xFF] = 1
+stack[0] = 2
+SCTR = 1
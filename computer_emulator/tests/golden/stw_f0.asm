@@ -0,0 +1,8 @@
+mov reg2 0x01
+mov reg3 0x23
+mov reg0 7
+stw reg0 hl
+or reg7 1
+---
+ram[0x0123] = 7
+PC_L = 9
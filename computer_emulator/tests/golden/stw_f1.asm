@@ -0,0 +1,6 @@
+mov reg1 0x55
+stw reg1 0xABCD
+or reg7 1
+---
+ram[0xABCD] = 0x55
+PC_L = 7
@@ -0,0 +1,8 @@
+mov reg0 9
+mov reg1 4
+sub reg0 reg1
+or reg7 1
+---
+REG0 = 5
+BORROW = 0
+OVERFLOW = 0
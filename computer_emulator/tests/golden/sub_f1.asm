@@ -0,0 +1,7 @@
+mov reg0 1
+sub reg0 2
+or reg7 1
+---
+REG0 = 0xFF
+BORROW = 1
+OVERFLOW = 0
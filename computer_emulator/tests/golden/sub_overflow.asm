@@ -0,0 +1,7 @@
+mov reg0 -128
+sub reg0 1
+or reg7 1
+---
+REG0 = 0x7F
+OVERFLOW = 1
+BORROW = 0
@@ -0,0 +1,92 @@
+use computer_emulator::bus::Device;
+use computer_emulator::{Computer, flag, register};
+use isa::interrupt;
+
+/// Raises its interrupt once, after `ticks` ticks.
+struct Alarm {
+    ticks: Option<u8>
+}
+
+impl Device for Alarm {
+    fn read(&self, _offset: u16) -> u8 { self.ticks.unwrap_or(0) }
+    fn write(&mut self, _offset: u16, value: u8) { self.ticks = Some(value) }
+    fn tick(&mut self) { self.ticks = self.ticks.map(|it| it.saturating_sub(1)) }
+    fn interrupt(&mut self) -> bool { self.ticks.take_if(|it| *it == 0).is_some() }
+}
+
+/// `program` at 0 and a handler for every line at 0x40 + 8 * line, it stores the line number at 0x80 + line.
+fn computer(program: &[u8]) -> Computer {
+    let mut computer = Computer::new();
+    computer.load(program, 0);
+    for line in 0..interrupt::LINES {
+        let handler = 0x40 + 8 * line as u16;
+        computer.load(&handler.to_be_bytes(), interrupt::VECTORS + 2 * line as u16);
+        // mov reg1 <line>, stw reg1 <0x80 + line>, rti
+        computer.load(&[0x19, line, 0x39, 0x00, 0x80 + line, 0x01, 0x40], handler);
+    }
+    computer
+}
+
+fn run(computer: &mut Computer, max_steps: usize) -> usize {
+    let mut steps = 0;
+    while !computer.stopped() {
+        assert!(steps < max_steps, "did not stop within {} steps", max_steps);
+        computer.step();
+        steps += 1;
+    }
+    steps
+}
+
+#[test]
+fn interrupts_wait_until_enabled() {
+    let mut computer = computer(&[0x00, 0x00, 0x00, 0x00]);
+    computer.request_interrupt(3);
+    computer.step();
+    assert_eq!(computer.pc(), 1);
+    computer.set_flag(flag::INTERRUPT, true);
+    computer.set_flag(flag::CARRY, true);
+    computer.step();
+    assert_eq!(computer.pc(), 0x58);
+    assert!(!computer.flag(flag::INTERRUPT));
+    assert_eq!(computer.pending_interrupts(), 0);
+    assert_eq!(computer.stack()[..3], [0x00, 0x01, 1 << flag::INTERRUPT | 1 << flag::CARRY]);
+    while computer.pc() >= 0x40 {
+        computer.step();
+    }
+    assert_eq!(computer.ram8(0x83), 3);
+    assert_eq!(computer.pc(), 1);
+    assert_eq!(computer.stack_ptr(), 0);
+    assert!(computer.flag(flag::INTERRUPT) && computer.flag(flag::CARRY));
+}
+
+#[test]
+fn lowest_line_is_taken_first() {
+    let mut computer = computer(&[0x00]);
+    computer.set_flag(flag::INTERRUPT, true);
+    computer.request_interrupt(5);
+    computer.request_interrupt(2);
+    computer.step();
+    assert_eq!(computer.pc(), 0x50);
+    assert_eq!(computer.pending_interrupts(), 1 << 5);
+}
+
+#[test]
+fn device_wakes_halted_processor() {
+    // or reg7 0x81, mov reg0 1, and reg7 0x7F, hlt
+    let mut computer = computer(&[0xBF, 0x81, 0x18, 0x01, 0xAF, 0x7F, 0x01, 0x00]);
+    computer.map_with_interrupt(0x100..=0x100, Box::new(Alarm { ticks: Some(20) }), 1);
+    let steps = run(&mut computer, 100);
+    assert!(steps > 20);
+    assert_eq!(computer.ram8(0x81), 1);
+    assert_eq!(computer.reg8(register::REG0), 1);
+    assert_eq!(computer.pc(), 8);
+}
+
+#[test]
+fn halt_without_interrupts_stops() {
+    // or reg7 1
+    let mut computer = computer(&[0xB8 | register::FLAG, 0x01]);
+    computer.request_interrupt(0);
+    assert_eq!(run(&mut computer, 10), 1);
+    assert_eq!(computer.ram8(0x80), 0);
+}
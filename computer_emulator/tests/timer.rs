@@ -0,0 +1,93 @@
+use computer_emulator::bus::Device;
+use computer_emulator::devices::timer::{self, Timer};
+use computer_emulator::{Computer, flag, register};
+use isa::interrupt;
+
+fn timer(control: u8, prescaler: u8, reload: u16) -> Timer {
+    let mut timer = Timer::new();
+    timer.write(timer::PRESCALER, prescaler);
+    timer.write(timer::RELOAD_H, (reload >> 8) as u8);
+    timer.write(timer::RELOAD_L, reload as u8);
+    timer.write(timer::CONTROL, control);
+    timer
+}
+
+/// Ticks until the count runs out, returns the number of ticks.
+fn ticks_until_expired(timer: &mut Timer) -> usize {
+    for ticks in 1..=0x20000 {
+        timer.tick();
+        if timer.read(timer::STATUS) & timer::EXPIRED != 0 {
+            return ticks
+        }
+    }
+    panic!("timer did not expire")
+}
+
+#[test]
+fn one_shot_stops() {
+    let mut timer = timer(timer::ENABLE, 0, 5);
+    assert_eq!(ticks_until_expired(&mut timer), 5);
+    assert_eq!(timer.read(timer::CONTROL) & timer::ENABLE, 0);
+    assert!(!timer.interrupt());
+    timer.write(timer::STATUS, timer::EXPIRED);
+    assert_eq!(timer.read(timer::STATUS), 0);
+    for _ in 0..10 { timer.tick() }
+    assert_eq!(timer.read(timer::STATUS), 0);
+}
+
+#[test]
+fn prescaler_divides_ticks() {
+    let mut timer = timer(timer::ENABLE, 3, 5);
+    timer.tick();
+    assert_eq!(timer.read(timer::COUNT_L), 5);
+    assert_eq!(ticks_until_expired(&mut timer), 19);
+}
+
+#[test]
+fn periodic_reloads_and_interrupts() {
+    let mut timer = timer(timer::ENABLE | timer::PERIODIC | timer::INTERRUPT, 1, 3);
+    assert_eq!(ticks_until_expired(&mut timer), 6);
+    assert!(timer.interrupt());
+    assert!(!timer.interrupt());
+    assert_eq!(timer.read(timer::COUNT_L), 3);
+    timer.write(timer::STATUS, timer::EXPIRED);
+    assert_eq!(ticks_until_expired(&mut timer), 6);
+    assert!(timer.interrupt());
+}
+
+#[test]
+fn zero_reload_counts_full_range() {
+    let mut timer = timer(timer::ENABLE, 0, 0);
+    assert_eq!(ticks_until_expired(&mut timer), 0x10000);
+}
+
+#[test]
+fn interrupt_counts_in_handler() {
+    const TIMER: u16 = 0x100;
+    let mut computer = Computer::new();
+    computer.map_with_interrupt(TIMER..=TIMER + timer::SIZE - 1, Box::new(Timer::new()), 2);
+    let program = [
+        0x19, 20,                             // mov reg1 20, reload
+        0x39, 0x01, 0x03,                     // stw reg1 0x103
+        0x19, timer::ENABLE | timer::PERIODIC | timer::INTERRUPT,
+        0x39, 0x01, 0x00,                     // stw reg1 0x100
+        0xBF, 0x81,                           // 10: or reg7 0x81, waits for the next interrupt
+        0xD8, 0x03,                           // cmp reg0 3
+        0x7D, 0x00, 0x0A,                     // jmp less 10
+        0xAF, 0x7F,                           // and reg7 0x7F
+        0x01, 0x00                            // hlt
+    ];
+    computer.load(&program, 0);
+    // handler: add reg0 1, rti
+    computer.load(&[0x88, 0x01, 0x01, 0x40], 0x40);
+    computer.load(&[0x00, 0x40], interrupt::VECTORS + 2 * 2);
+    let mut steps = 0;
+    while !computer.stopped() {
+        assert!(steps < 200, "did not stop");
+        computer.step();
+        steps += 1;
+    }
+    assert_eq!(computer.reg8(register::REG0), 3);
+    assert!(!computer.flag(flag::INTERRUPT));
+    assert_eq!(computer.ram8(TIMER + timer::STATUS), timer::EXPIRED);
+}
@@ -0,0 +1,55 @@
+use std::cell::RefCell;
+use std::io::Write;
+use std::rc::Rc;
+use computer_emulator::register;
+use computer_emulator::trace::{TraceFormat, TraceRecord, Tracer};
+
+/// Output shared with the test after the tracer took it.
+#[derive(Clone, Default)]
+struct Output(Rc<RefCell<Vec<u8>>>);
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.borrow_mut().write(buf)
+    }
+    fn flush(&mut self) -> std::io::Result<()> { Ok(()) }
+}
+
+/// `bytes` executed at 0x0010, moving 5 from REG1 into REG0.
+fn record(bytes: &[u8]) -> TraceRecord {
+    let mut registers_before = [0; 8];
+    registers_before[register::REG1 as usize] = 5;
+    let mut registers_after = registers_before;
+    registers_after[register::REG0 as usize] = 5;
+    TraceRecord { pc: 0x0010, bytes: bytes.to_vec(), registers_before, registers_after }
+}
+
+fn trace(format: TraceFormat, record: &TraceRecord) -> String {
+    let output = Output::default();
+    let mut tracer = Tracer::new(format, Box::new(output.clone()));
+    tracer.record(record);
+    let bytes = output.0.borrow().clone();
+    String::from_utf8(bytes).unwrap()
+}
+
+#[test]
+fn canonical_instructions() {
+    assert_eq!(trace(TraceFormat::Text, &record(&[0x11, 0x00])), "0010  11 00     mov reg1 reg0        REG0 00->05\n");
+}
+
+#[test]
+fn padding_bits_decode_like_the_processor() {
+    // mov reg0 reg1 with a padding bit set, the processor ignores it
+    let record = record(&[0x10, 0x09]);
+    assert_eq!(record.decoded(), Some(("mov", vec!["reg0".to_string(), "reg1".to_string()])));
+    assert_eq!(trace(TraceFormat::Text, &record), "0010  10 09     mov reg0 reg1        REG0 00->05\n");
+    assert!(trace(TraceFormat::Json, &record).starts_with("{\"pc\":16,\"bytes\":[16,9],\"mnemonic\":\"mov\",\"operands\":[\"reg0\",\"reg1\"],"));
+}
+
+#[test]
+fn reserved_opcodes_do_not_decode() {
+    let record = record(&[0x08, 0x00]);
+    assert_eq!(record.decoded(), None);
+    assert_eq!(trace(TraceFormat::Text, &record), "0010  08 00     ???                  REG0 00->05\n");
+    assert!(trace(TraceFormat::Json, &record).contains("\"mnemonic\":null,\"operands\":[],"));
+}
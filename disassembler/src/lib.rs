@@ -0,0 +1,163 @@
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+
+pub enum Operand {
+    Register(u8),
+    Literal(i8),
+    Flag(u8),
+    HL,
+    Address(u16)
+}
+
+impl Display for Operand {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Operand::Register(register) => write!(f, "reg{}", register),
+            Operand::Literal(literal) => write!(f, "{}", literal),
+            Operand::Flag(flag) => write!(f, "flag{}", flag),
+            Operand::HL => write!(f, "hl"),
+            Operand::Address(address) => write!(f, "{}", address),
+        }
+    }
+}
+
+pub struct Instruction {
+    pub address: u16,
+    pub bytes: Vec<u8>,
+    /// `None` for bytes that do not form a valid instruction, they are emitted as a single `.byte`.
+    pub mnemonic: Option<&'static str>,
+    pub operands: Vec<Operand>
+}
+
+impl Instruction {
+    /// Literal address the instruction jumps to or accesses, if any.
+    pub fn target(&self) -> Option<u16> {
+        self.operands.iter().find_map(|it| match it {
+            Operand::Address(address) => Some(*address),
+            _ => None
+        })
+    }
+}
+
+fn value(value: isa::Value) -> Operand {
+    match value {
+        isa::Value::Register(register) => Operand::Register(register),
+        isa::Value::Literal(literal) => Operand::Literal(literal as i8)
+    }
+}
+
+fn address(address: isa::Address) -> Operand {
+    match address {
+        isa::Address::HL => Operand::HL,
+        isa::Address::Literal(address) => Operand::Address(address)
+    }
+}
+
+/// Decodes the instruction at the start of `bytes` into its mnemonic, operands and size.
+/// Encodings with padding bits set are rejected, so decoded instructions re-assemble to the same bytes.
+pub fn decode(bytes: &[u8]) -> Option<(&'static str, Vec<Operand>, usize)> {
+    let (instruction, size) = isa::decode(bytes)?;
+    if isa::encode(&instruction).ok()? != bytes[..size] {
+        return None
+    }
+    decode_lenient(bytes)
+}
+
+/// Like `decode`, but accepts padding bits the way the processor does, only reserved opcodes are rejected.
+pub fn decode_lenient(bytes: &[u8]) -> Option<(&'static str, Vec<Operand>, usize)> {
+    let (instruction, size) = isa::decode(bytes)?;
+    let operands = match instruction.operands {
+        isa::Operands::None => vec![],
+        isa::Operands::Register(register) => vec![Operand::Register(register)],
+        isa::Operands::Value(operand) => vec![value(operand)],
+        isa::Operands::Address(operand) => vec![address(operand)],
+        isa::Operands::RegisterValue(register, operand) => vec![Operand::Register(register), value(operand)],
+        isa::Operands::RegisterAddress(register, operand) => vec![Operand::Register(register), address(operand)],
+        isa::Operands::FlagAddress(flag, operand) => vec![Operand::Flag(flag), address(operand)]
+    };
+    Some((instruction.opcode()?.mnemonic, operands, size))
+}
+
+/// Decodes a whole image that is loaded at `origin`.
+/// Bytes the processor reads as one instruction stay together even if they do not decode, like a reserved
+/// extended opcode with its page byte, only a truncated instruction at the end is split into single bytes.
+pub fn disassemble(image: &[u8], origin: u16) -> Vec<Instruction> {
+    let mut result = vec![];
+    let mut offset = 0;
+    while offset < image.len() {
+        let address = origin.wrapping_add(offset as u16);
+        let instruction = match decode(&image[offset..]) {
+            Some((mnemonic, operands, size)) => Instruction {
+                address,
+                bytes: image[offset..offset + size].to_vec(),
+                mnemonic: Some(mnemonic),
+                operands
+            },
+            None => {
+                let size = isa::decode(&image[offset..]).map_or(1, |it| it.1);
+                Instruction { address, bytes: image[offset..offset + size].to_vec(), mnemonic: None, operands: vec![] }
+            }
+        };
+        offset += instruction.bytes.len();
+        result.push(instruction);
+    }
+    result
+}
+
+/// Names for every address targeted by an instruction that is also the start of an instruction.
+pub fn labels(instructions: &[Instruction]) -> BTreeMap<u16, String> {
+    instructions.iter()
+        .filter_map(|it| it.target())
+        .filter(|target| instructions.iter().any(|it| it.address == *target))
+        .map(|target| (target, format!("L{:04X}", target)))
+        .collect()
+}
+
+fn write_instruction(result: &mut String, instruction: &Instruction, labels: &BTreeMap<u16, String>) {
+    if let Some(label) = labels.get(&instruction.address) {
+        *result += &format!("@{}: ", label);
+    }
+    match instruction.mnemonic {
+        Some(mnemonic) => {
+            *result += mnemonic;
+            for operand in &instruction.operands {
+                *result += " ";
+                match operand {
+                    Operand::Address(address) if labels.contains_key(address) => *result += &format!("@{}", labels[address]),
+                    _ => *result += &operand.to_string()
+                }
+            }
+        },
+        None => {
+            let bytes: Vec<String> = instruction.bytes.iter().map(|it| it.to_string()).collect();
+            *result += &format!(".byte {}", bytes.join(", "))
+        }
+    }
+}
+
+/// Assembler source that re-assembles to the original image.
+pub fn source(instructions: &[Instruction]) -> String {
+    let labels = labels(instructions);
+    let mut result = match instructions.first() {
+        Some(first) if first.address != 0 => format!(".org {}\n", first.address),
+        _ => String::new()
+    };
+    for instruction in instructions {
+        write_instruction(&mut result, instruction, &labels);
+        result += "\n";
+    }
+    result
+}
+
+/// Human readable listing with the address and raw bytes in front of every instruction.
+pub fn listing(instructions: &[Instruction]) -> String {
+    let labels = labels(instructions);
+    let mut result = String::new();
+    for instruction in instructions {
+        let bytes: Vec<String> = instruction.bytes.iter().map(|it| format!("{:02X}", it)).collect();
+        result += &format!("{:04X}: {:<9} ", instruction.address, bytes.join(" "));
+        write_instruction(&mut result, instruction, &labels);
+        result += "\n";
+    }
+    result
+}
@@ -0,0 +1,54 @@
+use std::process::exit;
+use disassembler::{disassemble, listing, source};
+
+const USAGE: &str = "usage: disassembler <image> [--origin <address>] [--source]";
+
+struct Options {
+    image: String,
+    origin: u16,
+    source: bool
+}
+
+fn parse_address(string: &str) -> Option<u16> {
+    if let Some(hex) = string.strip_prefix("0x") {
+        u16::from_str_radix(hex, 16).ok()
+    } else {
+        string.parse().ok()
+    }
+}
+
+fn parse_options() -> Result<Options, String> {
+    let mut args = std::env::args().skip(1);
+    let mut image = None;
+    let mut origin = 0;
+    let mut source = false;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--origin" => {
+                let value = args.next().ok_or("missing value for --origin")?;
+                origin = parse_address(&value).ok_or(format!("invalid address: {}", value))?;
+            }
+            "--source" => source = true,
+            _ if image.is_none() => image = Some(arg),
+            _ => return Err(format!("unexpected argument: {}", arg))
+        }
+    }
+    Ok(Options { image: image.ok_or("missing image")?, origin, source })
+}
+
+fn main() {
+    let options = parse_options().unwrap_or_else(|message| {
+        eprintln!("{}\n{}", message, USAGE);
+        exit(2)
+    });
+    let image = std::fs::read(&options.image).unwrap_or_else(|err| {
+        eprintln!("failed to read {}: {}", options.image, err);
+        exit(2)
+    });
+    let instructions = disassemble(&image, options.origin);
+    if options.source {
+        print!("{}", source(&instructions));
+    } else {
+        print!("{}", listing(&instructions));
+    }
+}
@@ -0,0 +1,50 @@
+use assembler::{assemble, Sources};
+use disassembler::{decode, decode_lenient, disassemble, listing, source};
+
+/// The bytes of `source` assembled at its `.org`.
+fn assemble_source(source: &str) -> Vec<u8> {
+    let mut sources = Sources::new(vec![]);
+    sources.add("disassembled.asm".into(), source.to_string());
+    let chunks = assemble(&mut sources, &[]).unwrap_or_else(|error| panic!("{}{}", error.report(&sources), source)).chunks();
+    chunks.into_iter().flat_map(|it| it.1).collect()
+}
+
+#[test]
+fn source_reassembles_to_the_image() {
+    let image = [0x18, 0xFF, 0x11, 0x00, 0x78, 0x01, 0x0B, 0x01, 0x18, 0x12, 0x34, 0x01, 0x00];
+    let instructions = disassemble(&image, 0x0100);
+    assert_eq!(source(&instructions), ".org 256\nmov reg0 -1\nmov reg1 reg0\njmp flag0 @L010B\ncall 4660\n@L010B: hlt\n");
+    assert_eq!(assemble_source(&source(&instructions)), image);
+}
+
+#[test]
+fn listing_shows_addresses_and_bytes() {
+    let instructions = disassemble(&[0x78, 0x00, 0x03, 0x00], 0);
+    assert_eq!(listing(&instructions), "0000: 78 00 03  jmp flag0 @L0003\n0003: 00        @L0003: nop\n");
+}
+
+#[test]
+fn padding_bits_are_only_accepted_leniently() {
+    // mov reg0 reg1 with a padding bit set in the second byte
+    assert!(decode(&[0x10, 0x09]).is_none());
+    let (mnemonic, operands, size) = decode_lenient(&[0x10, 0x09]).unwrap();
+    assert_eq!((mnemonic, operands.iter().map(ToString::to_string).collect::<Vec<_>>(), size), ("mov", vec!["reg0".to_string(), "reg1".to_string()], 2));
+    let instructions = disassemble(&[0x10, 0x09, 0x00], 0);
+    assert_eq!(source(&instructions), ".byte 16, 9\nnop\n");
+    assert_eq!(assemble_source(&source(&instructions)), [0x10, 0x09, 0x00]);
+}
+
+#[test]
+fn reserved_extended_opcodes_stay_together() {
+    assert!(decode_lenient(&[0x08, 0x00]).is_none());
+    // a reserved page and a reserved sub-opcode of page 1
+    let image = [0x08, 0x00, 0x18, 0x01, 0x01, 0xF0, 0x18, 0x02];
+    let instructions = disassemble(&image, 0);
+    assert_eq!(source(&instructions), ".byte 8, 0\nmov reg0 1\n.byte 1, 240\nmov reg0 2\n");
+    assert_eq!(assemble_source(&source(&instructions)), image);
+}
+
+#[test]
+fn truncated_instructions_are_single_bytes() {
+    assert_eq!(source(&disassemble(&[0x00, 0x78, 0x01], 0)), "nop\n.byte 120\n.byte 1\n");
+}
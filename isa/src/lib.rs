@@ -0,0 +1,325 @@
+use std::fmt::{Display, Formatter};
+
+pub mod register {
+    pub const REG0: u8 = 0;
+    pub const REG1: u8 = 1;
+    pub const HIGH: u8 = 2;
+    pub const LOW : u8 = 3;
+    pub const PC_H: u8 = 4;
+    pub const PC_L: u8 = 5;
+    pub const SCTR: u8 = 6;
+    pub const FLAG: u8 = 7;
+
+    pub const NAMES: [&str; 8] = ["REG0", "REG1", "HIGH", "LOW", "PC_H", "PC_L", "SCTR", "FLAG"];
+    /// Names accepted by the assembler in addition to `reg<N>`.
+    pub const ALIASES: [Option<&str>; 8] = [None, None, Some("h"), Some("l"), Some("pc_h"), Some("pc_l"), Some("sp"), Some("flags")];
+}
+
+pub mod flag {
+    pub const HALT: u8 = 0;
+    pub const OVERFLOW: u8 = 1;
+    pub const CARRY: u8 = 2;
+    pub const BORROW: u8 = 3;
+    pub const EQUAL: u8 = 4;
+    pub const LESS: u8 = 5;
+    pub const MORE: u8 = 6;
+    /// Interrupts are only taken while it is set, a halted processor with it set waits for one.
+    pub const INTERRUPT: u8 = 7;
+
+    pub const NAMES: [&str; 8] = ["HALT", "OVERFLOW", "CARRY", "BORROW", "EQUAL", "LESS", "MORE", "INTERRUPT"];
+    /// Names accepted by the assembler in addition to `flag<N>`.
+    pub const ALIASES: [&str; 8] = ["halt", "overflow", "carry", "borrow", "equal", "less", "more", "interrupt"];
+    /// Every bit of the FLAG register can be addressed as `flag<N>`, even the ones without a name.
+    pub const COUNT: u8 = 8;
+}
+
+pub mod opcode {
+    pub const NOP: u8 = 0x0;
+    pub const MOV: u8 = 0x1;
+    pub const LDW: u8 = 0x2;
+    pub const STW: u8 = 0x3;
+    pub const LDA: u8 = 0x4;
+    pub const PSH: u8 = 0x5;
+    pub const POP: u8 = 0x6;
+    pub const JMP: u8 = 0x7;
+    pub const ADD: u8 = 0x8;
+    pub const SUB: u8 = 0x9;
+    pub const AND: u8 = 0xA;
+    pub const OR : u8 = 0xB;
+    pub const INV: u8 = 0xC;
+    pub const CMP: u8 = 0xD;
+    pub const SHL: u8 = 0xE;
+    pub const SHR: u8 = 0xF;
+
+    // extended opcodes, the high nibble is the page selected by the first byte and the low nibble the sub-opcode
+    pub const HLT : u8 = 0x10;
+    pub const CALL: u8 = 0x11;
+    pub const RET : u8 = 0x12;
+    pub const BRK : u8 = 0x13;
+    pub const RTI : u8 = 0x14;
+}
+
+pub mod interrupt {
+    /// Start of the table with the big-endian handler address of every line, line 0 first.
+    pub const VECTORS: u16 = 0xFFF0;
+    pub const LINES: u8 = 8;
+}
+
+/// Which operands an opcode takes and where they are encoded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Form {
+    None,
+    /// Register in the low three bits of the first byte.
+    Register,
+    /// Register in the first byte when F = 0, lit8 in a second byte when F = 1.
+    Value,
+    /// HL when F = 0, lit16 in two more bytes when F = 1.
+    Address,
+    /// Register in the first byte, then a register or lit8 in the second byte.
+    RegisterValue,
+    /// Register in the first byte, then HL or lit16.
+    RegisterAddress,
+    /// Flag index in the first byte, then HL or lit16.
+    FlagAddress
+}
+
+impl Form {
+    /// Operand notation used in spec.md.
+    pub fn syntax(&self) -> &'static str {
+        match self {
+            Form::None => "",
+            Form::Register => "reg",
+            Form::Value => "reg/lit8",
+            Form::Address => "[HL/lit16]",
+            Form::RegisterValue => "reg, reg/lit8",
+            Form::RegisterAddress => "reg, [HL/lit16]",
+            Form::FlagAddress => "lit3, [HL/lit16]"
+        }
+    }
+    /// Size of an instruction of this form, `f` is the F bit of the first byte.
+    pub fn size(&self, f: bool) -> usize {
+        match self {
+            Form::None | Form::Register => 1,
+            Form::Value => if f { 2 } else { 1 },
+            Form::Address | Form::RegisterAddress | Form::FlagAddress => if f { 3 } else { 1 },
+            Form::RegisterValue => 2
+        }
+    }
+}
+
+pub struct Opcode {
+    pub code: u8,
+    pub mnemonic: &'static str,
+    pub form: Form
+}
+
+pub const OPCODES: [Opcode; 16] = [
+    Opcode { code: opcode::NOP, mnemonic: "nop", form: Form::None },
+    Opcode { code: opcode::MOV, mnemonic: "mov", form: Form::RegisterValue },
+    Opcode { code: opcode::LDW, mnemonic: "ldw", form: Form::RegisterAddress },
+    Opcode { code: opcode::STW, mnemonic: "stw", form: Form::RegisterAddress },
+    Opcode { code: opcode::LDA, mnemonic: "lda", form: Form::Address },
+    Opcode { code: opcode::PSH, mnemonic: "psh", form: Form::Value },
+    Opcode { code: opcode::POP, mnemonic: "pop", form: Form::Register },
+    Opcode { code: opcode::JMP, mnemonic: "jmp", form: Form::FlagAddress },
+    Opcode { code: opcode::ADD, mnemonic: "add", form: Form::RegisterValue },
+    Opcode { code: opcode::SUB, mnemonic: "sub", form: Form::RegisterValue },
+    Opcode { code: opcode::AND, mnemonic: "and", form: Form::RegisterValue },
+    Opcode { code: opcode::OR , mnemonic: "or" , form: Form::RegisterValue },
+    Opcode { code: opcode::INV, mnemonic: "inv", form: Form::Register },
+    Opcode { code: opcode::CMP, mnemonic: "cmp", form: Form::RegisterValue },
+    Opcode { code: opcode::SHL, mnemonic: "shl", form: Form::RegisterValue },
+    Opcode { code: opcode::SHR, mnemonic: "shr", form: Form::RegisterValue }
+];
+
+/// Opcodes of the extended pages, they are encoded as the page byte `0x01..=0x0F` followed by the sub-opcode byte.
+pub const EXTENDED_OPCODES: [Opcode; 5] = [
+    Opcode { code: opcode::HLT , mnemonic: "hlt" , form: Form::None },
+    Opcode { code: opcode::CALL, mnemonic: "call", form: Form::Address },
+    Opcode { code: opcode::RET , mnemonic: "ret" , form: Form::None },
+    Opcode { code: opcode::BRK , mnemonic: "brk" , form: Form::None },
+    Opcode { code: opcode::RTI , mnemonic: "rti" , form: Form::None }
+];
+
+impl Opcode {
+    pub fn from_mnemonic(mnemonic: &str) -> Option<&'static Opcode> {
+        OPCODES.iter().chain(&EXTENDED_OPCODES).find(|it| it.mnemonic == mnemonic)
+    }
+    pub fn from_code(code: u8) -> Option<&'static Opcode> {
+        OPCODES.iter().chain(&EXTENDED_OPCODES).find(|it| it.code == code)
+    }
+    /// 0 for the main opcodes, otherwise the first byte of the encoding.
+    pub fn page(&self) -> u8 { self.code >> 4 }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Value {
+    Register(u8),
+    Literal(u8)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Address {
+    HL,
+    Literal(u16)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operands {
+    None,
+    Register(u8),
+    Value(Value),
+    Address(Address),
+    RegisterValue(u8, Value),
+    RegisterAddress(u8, Address),
+    FlagAddress(u8, Address)
+}
+
+impl Operands {
+    pub fn form(&self) -> Form {
+        match self {
+            Operands::None => Form::None,
+            Operands::Register(_) => Form::Register,
+            Operands::Value(_) => Form::Value,
+            Operands::Address(_) => Form::Address,
+            Operands::RegisterValue(_, _) => Form::RegisterValue,
+            Operands::RegisterAddress(_, _) => Form::RegisterAddress,
+            Operands::FlagAddress(_, _) => Form::FlagAddress
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Instruction {
+    pub code: u8,
+    pub operands: Operands
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum EncodeError {
+    UnknownOpcode(u8),
+    WrongForm { mnemonic: &'static str, expected: Form, found: Form },
+    RegisterOutOfRange(u8),
+    FlagOutOfRange(u8)
+}
+
+impl Display for EncodeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EncodeError::UnknownOpcode(code) => write!(f, "unknown opcode 0x{:X}", code),
+            EncodeError::WrongForm { mnemonic, expected, .. } => write!(f, "{} expects operands `{}`", mnemonic, expected.syntax()),
+            EncodeError::RegisterOutOfRange(register) => write!(f, "reg{} does not exist", register),
+            EncodeError::FlagOutOfRange(flag) => write!(f, "flag{} does not exist", flag)
+        }
+    }
+}
+
+impl Instruction {
+    pub fn opcode(&self) -> Option<&'static Opcode> {
+        Opcode::from_code(self.code)
+    }
+    pub fn size(&self) -> usize {
+        let f = matches!(self.operands,
+            Operands::Value(Value::Literal(_)) |
+            Operands::Address(Address::Literal(_)) |
+            Operands::RegisterAddress(_, Address::Literal(_)) |
+            Operands::FlagAddress(_, Address::Literal(_))
+        );
+        let page_byte = if self.code >> 4 == 0 { 0 } else { 1 };
+        page_byte + self.operands.form().size(f)
+    }
+}
+
+fn check_register(register: u8) -> Result<u8, EncodeError> {
+    if (register as usize) < register::NAMES.len() { Ok(register) } else { Err(EncodeError::RegisterOutOfRange(register)) }
+}
+
+/// First byte plus the lit16 for `address`.
+fn encode_address(first: u8, address: Address) -> Vec<u8> {
+    match address {
+        Address::HL => vec![first],
+        Address::Literal(address) => vec![first | 0b1000, (address >> 8) as u8, address as u8]
+    }
+}
+
+/// Encodes `instruction` in its canonical form, with every padding bit cleared.
+pub fn encode(instruction: &Instruction) -> Result<Vec<u8>, EncodeError> {
+    let opcode = instruction.opcode().ok_or(EncodeError::UnknownOpcode(instruction.code))?;
+    let form = instruction.operands.form();
+    if form != opcode.form {
+        return Err(EncodeError::WrongForm { mnemonic: opcode.mnemonic, expected: opcode.form, found: form })
+    }
+    let code = (opcode.code & 0xF) << 4;
+    let mut bytes = if opcode.page() == 0 { vec![] } else { vec![opcode.page()] };
+    bytes.extend(match instruction.operands {
+        Operands::None => vec![code],
+        Operands::Register(register) => vec![code | check_register(register)?],
+        Operands::Value(Value::Register(register)) => vec![code | check_register(register)?],
+        Operands::Value(Value::Literal(literal)) => vec![code | 0b1000, literal],
+        Operands::Address(address) => encode_address(code, address),
+        Operands::RegisterValue(register, Value::Register(value)) => vec![code | check_register(register)?, check_register(value)?],
+        Operands::RegisterValue(register, Value::Literal(value)) => vec![code | 0b1000 | check_register(register)?, value],
+        Operands::RegisterAddress(register, address) => encode_address(code | check_register(register)?, address),
+        Operands::FlagAddress(flag, address) => {
+            if flag >= flag::COUNT { return Err(EncodeError::FlagOutOfRange(flag)) }
+            encode_address(code | flag, address)
+        }
+    });
+    Ok(bytes)
+}
+
+/// Whether `op` is the page byte of an extended opcode rather than the first byte of a main one.
+fn is_page(op: u8) -> bool {
+    op != 0 && op >> 4 == 0
+}
+
+/// Size of the instruction starting with the byte `op`, `next` is the byte after it.
+/// Reserved extended opcodes are two bytes long.
+pub fn size(op: u8, next: u8) -> usize {
+    if !is_page(op) {
+        return OPCODES[(op >> 4) as usize].form.size(op >> 3 & 1 != 0)
+    }
+    match Opcode::from_code(op << 4 | next >> 4) {
+        Some(opcode) => 1 + opcode.form.size(next >> 3 & 1 != 0),
+        None => 2
+    }
+}
+
+/// Decodes the instruction at the start of `bytes` the way the processor does, ignoring padding bits.
+/// Reserved extended opcodes decode to their code with no operands, `Instruction::opcode` is `None` for them.
+/// Returns `None` if `bytes` ends before the instruction does.
+pub fn decode(bytes: &[u8]) -> Option<(Instruction, usize)> {
+    let op = *bytes.first()?;
+    if is_page(op) {
+        let next = *bytes.get(1)?;
+        let code = op << 4 | next >> 4;
+        let Some(opcode) = Opcode::from_code(code) else {
+            return Some((Instruction { code, operands: Operands::None }, 2))
+        };
+        let (instruction, size) = decode_operands(opcode, &bytes[1..])?;
+        return Some((instruction, size + 1))
+    }
+    decode_operands(&OPCODES[(op >> 4) as usize], bytes)
+}
+
+/// Decodes the operands of `opcode`, `bytes` starts at the byte holding the F and reg bits.
+fn decode_operands(opcode: &Opcode, bytes: &[u8]) -> Option<(Instruction, usize)> {
+    let op = bytes[0];
+    let f = op >> 3 & 1 != 0;
+    let reg = op & 0b111;
+    let size = opcode.form.size(f);
+    if bytes.len() < size {
+        return None
+    }
+    let address = || if f { Address::Literal((bytes[1] as u16) << 8 | bytes[2] as u16) } else { Address::HL };
+    let operands = match opcode.form {
+        Form::None => Operands::None,
+        Form::Register => Operands::Register(reg),
+        Form::Value => Operands::Value(if f { Value::Literal(bytes[1]) } else { Value::Register(reg) }),
+        Form::Address => Operands::Address(address()),
+        Form::RegisterValue => Operands::RegisterValue(reg, if f { Value::Literal(bytes[1]) } else { Value::Register(bytes[1] & 0b111) }),
+        Form::RegisterAddress => Operands::RegisterAddress(reg, address()),
+        Form::FlagAddress => Operands::FlagAddress(reg, address())
+    };
+    Some((Instruction { code: opcode.code, operands }, size))
+}
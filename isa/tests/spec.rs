@@ -0,0 +1,55 @@
+use isa::{flag, register, Opcode, EXTENDED_OPCODES, OPCODES};
+
+const SPEC: &str = include_str!("../../spec.md");
+
+/// Lines of the section starting with `heading`, up to the next heading of the same level.
+fn section(heading: &str) -> Vec<&'static str> {
+    let level = heading.split(' ').next().unwrap();
+    SPEC.lines()
+        .skip_while(|it| *it != heading)
+        .skip(1)
+        .take_while(|it| !(it.starts_with(level) && it[level.len()..].starts_with(' ')))
+        .map(str::trim)
+        .filter(|it| !it.is_empty())
+        .collect()
+}
+
+fn check_opcodes(heading: &str, opcodes: &[Opcode], code: impl Fn(&Opcode) -> String) {
+    let lines: Vec<_> = section(heading).into_iter().filter(|it| it.starts_with('(')).collect();
+    assert_eq!(lines.len(), opcodes.len());
+    for (line, opcode) in lines.iter().zip(opcodes) {
+        let expected = format!("({}): {} {}", code(opcode), opcode.mnemonic.to_uppercase(), opcode.form.syntax());
+        assert_eq!(line.split_whitespace().collect::<Vec<_>>(), expected.split_whitespace().collect::<Vec<_>>());
+    }
+}
+
+#[test]
+fn opcodes_match_spec() {
+    check_opcodes("## OP Codes", &OPCODES, |it| format!("{:X}", it.code));
+}
+
+#[test]
+fn extended_opcodes_match_spec() {
+    check_opcodes("## Extended OP Codes", &EXTENDED_OPCODES, |it| format!("{:X}:{:X}", it.page(), it.code & 0xF));
+}
+
+#[test]
+fn registers_match_spec() {
+    let lines: Vec<_> = section("## Register").into_iter().filter(|it| it.starts_with("reg")).collect();
+    assert_eq!(lines.len(), register::NAMES.len());
+    for (index, line) in lines.iter().enumerate() {
+        let expected = [Some(format!("reg{}", index)), Some(register::NAMES[index].to_string()), register::ALIASES[index].map(str::to_string)];
+        let expected: Vec<_> = expected.into_iter().flatten().collect();
+        assert_eq!(line.split_whitespace().collect::<Vec<_>>(), expected);
+    }
+}
+
+#[test]
+fn flags_match_spec() {
+    let lines: Vec<_> = section("## Flag").into_iter().filter(|it| it.starts_with("flag")).collect();
+    assert_eq!(lines.len(), flag::NAMES.len());
+    for (index, line) in lines.iter().enumerate() {
+        let expected = [format!("flag{}", index), flag::NAMES[index].to_string(), flag::ALIASES[index].to_string()];
+        assert_eq!(line.split_whitespace().collect::<Vec<_>>(), expected);
+    }
+}
@@ -0,0 +1,3 @@
+pub mod object;
+pub mod link;
+pub mod output;
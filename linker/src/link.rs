@@ -0,0 +1,188 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt::{Display, Formatter};
+use crate::object::{Archive, ObjectFile, RelocationKind, Target};
+
+/// A section of the linked program with the contributions of every object.
+pub struct LinkedSection {
+    pub name: String,
+    pub origin: u16,
+    pub size: usize,
+    /// Zero filled between contributions, empty if no object has data for the section.
+    pub data: Vec<u8>
+}
+
+impl LinkedSection {
+    /// First address after the section, may be 0x10000.
+    pub fn end(&self) -> usize { self.origin as usize + self.size }
+    /// `bss` only reserves memory, it is not part of the image.
+    pub fn is_loaded(&self) -> bool { self.name != "bss" }
+}
+
+pub struct Linked {
+    pub sections: Vec<LinkedSection>,
+    /// The address of every global symbol.
+    pub symbols: BTreeMap<String, u16>,
+    /// Names of the linked objects, including the archive members that were needed.
+    pub objects: Vec<String>
+}
+
+#[derive(Debug)]
+pub enum LinkError {
+    DuplicateSymbol { name: String, first: String, second: String },
+    UndefinedSymbol { name: String, object: String },
+    UnknownSection { section: String, object: String },
+    AddressSpaceExceeded(String),
+    OverlappingSections(String, String),
+    OutOfRange { value: i64, section: String, offset: u16, object: String },
+    /// A relocation patches a section like `bss` that only reserves memory.
+    RelocationWithoutData { section: String, offset: u16, object: String }
+}
+
+impl Display for LinkError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LinkError::DuplicateSymbol { name, first, second } => write!(f, "symbol `{}` is defined by both {} and {}", name, first, second),
+            LinkError::UndefinedSymbol { name, object } => write!(f, "undefined symbol `{}` referenced by {}", name, object),
+            LinkError::UnknownSection { section, object } => write!(f, "{} refers to the unknown section `{}`", object, section),
+            LinkError::AddressSpaceExceeded(section) => write!(f, "section `{}` extends past 0xFFFF", section),
+            LinkError::OverlappingSections(first, second) => write!(f, "sections `{}` and `{}` overlap", first, second),
+            LinkError::OutOfRange { value, section, offset, object } =>
+                write!(f, "relocated value {} does not fit at {}+0x{:04X} of {}", value, section, offset, object),
+            LinkError::RelocationWithoutData { section, offset, object } =>
+                write!(f, "{} relocates {}+0x{:04X}, but section `{}` has no data to patch", object, section, offset, section)
+        }
+    }
+}
+
+/// Adds the objects of `archives` that define symbols still undefined, until nothing more is needed.
+fn select(mut objects: Vec<(String, ObjectFile)>, archives: Vec<(String, Archive)>) -> Vec<(String, ObjectFile)> {
+    let mut members: Vec<Option<(String, ObjectFile)>> = archives.into_iter()
+        .flat_map(|(archive, it)| it.members.into_iter().map(move |(name, object)| Some((format!("{}({})", archive, name), object))))
+        .collect();
+    loop {
+        let defined: HashSet<&str> = objects.iter()
+            .flat_map(|it| it.1.symbols.iter().filter(|it| it.global).map(|it| it.name.as_str()))
+            .collect();
+        let undefined: HashSet<&str> = objects.iter()
+            .flat_map(|it| it.1.externs.iter().map(|it| it.as_str()))
+            .filter(|it| !defined.contains(it))
+            .collect();
+        let needed = members.iter().position(|member| member.as_ref()
+            .is_some_and(|it| it.1.symbols.iter().any(|it| it.global && undefined.contains(it.name.as_str()))));
+        match needed {
+            Some(index) => objects.push(members[index].take().unwrap()),
+            None => return objects
+        }
+    }
+}
+
+/// Every global symbol with the index of the object defining it, or every duplicate and undefined symbol.
+fn resolve(objects: &[(String, ObjectFile)]) -> Result<HashMap<&str, usize>, Vec<LinkError>> {
+    let mut errors = vec![];
+    let mut defined: HashMap<&str, usize> = HashMap::new();
+    for (index, (name, object)) in objects.iter().enumerate() {
+        for symbol in object.symbols.iter().filter(|it| it.global) {
+            if let Some(first) = defined.insert(&symbol.name, index) {
+                errors.push(LinkError::DuplicateSymbol { name: symbol.name.clone(), first: objects[first].0.clone(), second: name.clone() });
+            }
+        }
+    }
+    for (name, object) in objects {
+        for symbol in object.externs.iter().filter(|it| !defined.contains_key(it.as_str())) {
+            errors.push(LinkError::UndefinedSymbol { name: symbol.clone(), object: name.clone() });
+        }
+    }
+    if errors.is_empty() { Ok(defined) } else { Err(errors) }
+}
+
+/// Links `objects` and whatever members of `archives` they need.
+/// Sections are placed at `bases`, the others follow the section first used before them.
+/// Within a section the objects follow each other in command line order.
+pub fn link(objects: Vec<(String, ObjectFile)>, archives: Vec<(String, Archive)>, bases: &HashMap<String, u16>) -> Result<Linked, Vec<LinkError>> {
+    let objects = select(objects, archives);
+    let defined = resolve(&objects)?;
+    let mut names: Vec<&str> = vec![];
+    for section in objects.iter().flat_map(|it| &it.1.sections) {
+        if !names.contains(&section.name.as_str()) {
+            names.push(&section.name);
+        }
+    }
+    // where each section of each object starts
+    let mut starts: Vec<HashMap<&str, usize>> = vec![HashMap::new(); objects.len()];
+    let mut sections = vec![];
+    let mut address = 0;
+    for name in names {
+        if let Some(base) = bases.get(name) {
+            address = *base as usize;
+        }
+        let origin = address;
+        for (index, (_, object)) in objects.iter().enumerate() {
+            if let Some(section) = object.section(name) {
+                let align = section.align.max(1) as usize;
+                address = address.div_ceil(align) * align;
+                starts[index].insert(name, address);
+                address += section.size;
+            }
+        }
+        if address > 1 << 16 {
+            return Err(vec![LinkError::AddressSpaceExceeded(name.to_string())])
+        }
+        sections.push(LinkedSection { name: name.to_string(), origin: origin as u16, size: address - origin, data: vec![] });
+    }
+    for (index, section) in sections.iter().enumerate() {
+        if let Some(other) = sections[index + 1..].iter().find(|it| (it.origin as usize) < section.end() && (section.origin as usize) < it.end()) {
+            return Err(vec![LinkError::OverlappingSections(section.name.clone(), other.name.clone())])
+        }
+    }
+    let symbol_address = |index: usize, name: &str| -> Option<usize> {
+        let symbol = objects[index].1.symbols.iter().find(|it| it.name == name)?;
+        Some(starts[index].get(symbol.section.as_str())? + symbol.offset as usize)
+    };
+    let mut symbols = BTreeMap::new();
+    for (name, index) in &defined {
+        let address = symbol_address(*index, name).ok_or_else(|| vec![LinkError::UnknownSection {
+            section: objects[*index].1.symbols.iter().find(|it| it.name == *name).unwrap().section.clone(),
+            object: objects[*index].0.clone()
+        }])?;
+        symbols.insert(name.to_string(), address as u16);
+    }
+    for section in &mut sections {
+        let contributions = || objects.iter().enumerate().filter_map(|(index, it)| Some((starts[index].get(section.name.as_str())?, it.1.section(&section.name)?)));
+        if contributions().all(|it| it.1.data.is_empty()) { continue }
+        section.data = vec![0; section.size];
+        for (start, contribution) in contributions() {
+            let start = start - section.origin as usize;
+            section.data[start..start + contribution.data.len()].copy_from_slice(&contribution.data);
+        }
+    }
+    for (index, (name, object)) in objects.iter().enumerate() {
+        for relocation in &object.relocations {
+            let unknown_section = |section: &str| vec![LinkError::UnknownSection { section: section.to_string(), object: name.clone() }];
+            let target = match &relocation.target {
+                Target::Section(section) => *starts[index].get(section.as_str()).ok_or_else(|| unknown_section(section))?,
+                Target::Symbol(symbol) => match symbol_address(index, symbol) {
+                    Some(address) => address,
+                    None => *symbols.get(symbol).ok_or_else(|| vec![LinkError::UndefinedSymbol { name: symbol.clone(), object: name.clone() }])? as usize
+                }
+            };
+            let value = target as i64 + relocation.addend;
+            let out_of_range = || vec![LinkError::OutOfRange { value, section: relocation.section.clone(), offset: relocation.offset, object: name.clone() }];
+            if !(0..=0xFFFF).contains(&value) {
+                return Err(out_of_range())
+            }
+            let start = *starts[index].get(relocation.section.as_str()).ok_or_else(|| unknown_section(&relocation.section))?;
+            let section = sections.iter_mut().find(|it| it.name == relocation.section).unwrap();
+            if section.data.is_empty() {
+                return Err(vec![LinkError::RelocationWithoutData { section: section.name.clone(), offset: relocation.offset, object: name.clone() }])
+            }
+            let field = start - section.origin as usize + relocation.offset as usize;
+            let bytes = match relocation.kind {
+                RelocationKind::Word => vec![(value >> 8) as u8, value as u8],
+                RelocationKind::High => vec![(value >> 8) as u8],
+                RelocationKind::Low => vec![value as u8]
+            };
+            section.data.get_mut(field..field + relocation.kind.size()).ok_or_else(out_of_range)?.copy_from_slice(&bytes);
+        }
+    }
+    Ok(Linked { sections, symbols, objects: objects.into_iter().map(|it| it.0).collect() })
+}
@@ -0,0 +1,130 @@
+use std::collections::HashMap;
+use std::process::exit;
+use linker::link::{link, Linked};
+use linker::object::{Archive, ObjectFile};
+use linker::output::{image, Chunk, OutputFormat};
+
+const USAGE: &str = "usage: linker <object|archive>... [-o <output>] [-f bin|text|ihex|srec] [--base <section>=<address>] [--map <file>]\n       linker --archive <output> <object>...";
+
+struct Options {
+    inputs: Vec<String>,
+    output: Option<String>,
+    format: OutputFormat,
+    bases: HashMap<String, u16>,
+    map: Option<String>,
+    archive: Option<String>
+}
+
+fn parse_address(string: &str) -> Option<u16> {
+    match string.strip_prefix("0x") {
+        Some(hex) => u16::from_str_radix(hex, 16).ok(),
+        None => string.parse().ok()
+    }
+}
+
+fn parse_options() -> Result<Options, String> {
+    let mut args = std::env::args().skip(1);
+    let mut inputs = vec![];
+    let mut output = None;
+    let mut format = OutputFormat::Binary;
+    let mut bases = HashMap::new();
+    let mut map = None;
+    let mut archive = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-o" => output = Some(args.next().ok_or("missing value for -o")?),
+            "-f" => format = args.next().ok_or("missing value for -f")?.parse()?,
+            "--base" => {
+                let value = args.next().ok_or("missing value for --base")?;
+                let (section, address) = value.split_once('=').ok_or(format!("invalid base: {}", value))?;
+                bases.insert(section.to_string(), parse_address(address).ok_or(format!("invalid address: {}", address))?);
+            },
+            "--map" => map = Some(args.next().ok_or("missing value for --map")?),
+            "--archive" => archive = Some(args.next().ok_or("missing value for --archive")?),
+            _ if arg.starts_with('-') => return Err(format!("unexpected argument: {}", arg)),
+            _ => inputs.push(arg)
+        }
+    }
+    if inputs.is_empty() {
+        return Err("missing input".to_string())
+    }
+    Ok(Options { inputs, output, format, bases, map, archive })
+}
+
+fn read(path: &str) -> String {
+    std::fs::read_to_string(path).unwrap_or_else(|err| {
+        eprintln!("failed to read {}: {}", path, err);
+        exit(2)
+    })
+}
+
+fn write(path: &str, contents: impl AsRef<[u8]>) {
+    std::fs::write(path, contents).unwrap_or_else(|err| {
+        eprintln!("failed to write {}: {}", path, err);
+        exit(2)
+    })
+}
+
+/// One line per section with its first and last address and size, then every global symbol.
+fn map(linked: &Linked) -> String {
+    let mut sections: Vec<_> = linked.sections.iter().filter(|it| it.size > 0).collect();
+    sections.sort_by_key(|it| it.origin);
+    let mut result = String::new();
+    for section in sections {
+        result += &format!("{:<8} 0x{:04X} 0x{:04X} {:>5}{}\n",
+            section.name, section.origin, section.end() - 1, section.size,
+            if section.is_loaded() { "" } else { " (not loaded)" });
+    }
+    for (name, address) in &linked.symbols {
+        result += &format!("0x{:04X} {}\n", address, name);
+    }
+    result
+}
+
+fn main() {
+    let options = parse_options().unwrap_or_else(|message| {
+        eprintln!("{}\n{}", message, USAGE);
+        exit(2)
+    });
+    let mut objects = vec![];
+    let mut archives = vec![];
+    for path in &options.inputs {
+        let text = read(path);
+        let result = if text.starts_with("archive") {
+            Archive::parse(&text).map(|it| archives.push((path.clone(), it)))
+        } else {
+            ObjectFile::parse(&text).map(|it| objects.push((path.clone(), it)))
+        };
+        result.unwrap_or_else(|err| {
+            eprintln!("{}: error: {}", path, err);
+            exit(1)
+        });
+    }
+    if let Some(path) = &options.archive {
+        if !archives.is_empty() {
+            eprintln!("archives cannot contain archives");
+            exit(2)
+        }
+        let members = objects.into_iter().map(|(path, object)| {
+            let name = std::path::Path::new(&path).file_name().map(|it| it.to_string_lossy().into_owned()).unwrap_or(path);
+            (name, object)
+        });
+        write(path, Archive { members: members.collect() }.write());
+        return
+    }
+    let linked = link(objects, archives, &options.bases).unwrap_or_else(|errors| {
+        for error in errors {
+            eprintln!("error: {}", error);
+        }
+        exit(1)
+    });
+    let chunks: Vec<Chunk> = linked.sections.iter()
+        .filter(|it| it.is_loaded() && !it.data.is_empty())
+        .map(|it| (it.origin, it.data.clone()))
+        .collect();
+    if let Some(path) = &options.map {
+        write(path, format!("origin 0x{:04X}\n{}", image(&chunks).0, map(&linked)));
+    }
+    let output = options.output.unwrap_or_else(|| format!("a.{}", options.format.extension()));
+    write(&output, options.format.write(&chunks));
+}
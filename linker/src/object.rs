@@ -0,0 +1,242 @@
+use std::fmt::{Display, Formatter};
+
+/// Bytes per `data` line when writing objects.
+const DATA_LINE_SIZE: usize = 16;
+
+/// The part of a section contributed by one object, its addresses start at 0.
+pub struct Section {
+    pub name: String,
+    pub size: usize,
+    /// The linker places the section at a multiple of this.
+    pub align: u16,
+    /// Empty for sections like `bss` that only reserve memory.
+    pub data: Vec<u8>
+}
+
+pub struct Symbol {
+    pub name: String,
+    pub section: String,
+    pub offset: u16,
+    /// Only global symbols are visible to other objects.
+    pub global: bool
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum RelocationKind {
+    /// Big-endian 16-bit address.
+    Word,
+    /// Bits 8 to 15 of the address, `hi(x)`.
+    High,
+    /// Bits 0 to 7 of the address, `lo(x)`.
+    Low
+}
+
+impl RelocationKind {
+    pub fn size(&self) -> usize {
+        match self {
+            RelocationKind::Word => 2,
+            RelocationKind::High | RelocationKind::Low => 1
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Target {
+    /// The start of a section of the same object.
+    Section(String),
+    /// A global symbol, possibly of another object.
+    Symbol(String)
+}
+
+/// The field at `offset` of `section` is overwritten with the address of `target` plus `addend`.
+pub struct Relocation {
+    pub section: String,
+    pub offset: u16,
+    pub kind: RelocationKind,
+    pub target: Target,
+    pub addend: i64
+}
+
+/// A separately assembled file whose sections can still be moved.
+#[derive(Default)]
+pub struct ObjectFile {
+    pub sections: Vec<Section>,
+    pub symbols: Vec<Symbol>,
+    /// Symbols referenced but not defined by this object.
+    pub externs: Vec<String>,
+    pub relocations: Vec<Relocation>
+}
+
+/// A collection of objects, only the members that define a needed symbol are linked.
+#[derive(Default)]
+pub struct Archive {
+    pub members: Vec<(String, ObjectFile)>
+}
+
+#[derive(Debug)]
+pub struct FormatError {
+    pub line: usize,
+    pub message: String
+}
+
+impl Display for FormatError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl Display for RelocationKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RelocationKind::Word => write!(f, "word"),
+            RelocationKind::High => write!(f, "hi"),
+            RelocationKind::Low => write!(f, "lo")
+        }
+    }
+}
+
+impl Display for Target {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Target::Section(name) => write!(f, "section {}", name),
+            Target::Symbol(name) => write!(f, "symbol {}", name)
+        }
+    }
+}
+
+fn parse_address(string: &str) -> Option<u16> {
+    u16::from_str_radix(string.strip_prefix("0x")?, 16).ok()
+}
+
+impl ObjectFile {
+    pub fn section(&self, name: &str) -> Option<&Section> {
+        self.sections.iter().find(|it| it.name == name)
+    }
+
+    /// One entry per line, starting with `object`:
+    /// `section <name> <size> <align>`, `data <section> <offset> <hex>`, `symbol local|global <name> <section> <offset>`,
+    /// `extern <name>` and `reloc <section> <offset> word|hi|lo section|symbol <name> <addend>`.
+    pub fn write(&self) -> String {
+        let mut result = "object\n".to_string();
+        for section in &self.sections {
+            result += &format!("section {} {} {}\n", section.name, section.size, section.align);
+        }
+        for section in &self.sections {
+            for (index, line) in section.data.chunks(DATA_LINE_SIZE).enumerate() {
+                result += &format!("data {} 0x{:04X} {}\n", section.name, index * DATA_LINE_SIZE, records::hex(line));
+            }
+        }
+        for symbol in &self.symbols {
+            let visibility = if symbol.global { "global" } else { "local" };
+            result += &format!("symbol {} {} {} 0x{:04X}\n", visibility, symbol.name, symbol.section, symbol.offset);
+        }
+        for name in &self.externs {
+            result += &format!("extern {}\n", name);
+        }
+        for relocation in &self.relocations {
+            result += &format!("reloc {} 0x{:04X} {} {} {}\n",
+                relocation.section, relocation.offset, relocation.kind, relocation.target, relocation.addend);
+        }
+        result
+    }
+
+    /// Parses the numbered lines of an object that follow its header.
+    fn parse_lines<'a>(lines: impl Iterator<Item = (usize, &'a str)>) -> Result<Self, FormatError> {
+        let mut object = ObjectFile::default();
+        for (line, text) in lines {
+            let error = |message: &str| FormatError { line, message: format!("{}: {}", message, text) };
+            let words: Vec<&str> = text.split_whitespace().collect();
+            match words.as_slice() {
+                [] => {},
+                ["section", name, size, align] => object.sections.push(Section {
+                    name: name.to_string(),
+                    size: size.parse().map_err(|_| error("invalid size"))?,
+                    align: align.parse().map_err(|_| error("invalid alignment"))?,
+                    data: vec![]
+                }),
+                ["data", name, offset, hex] => {
+                    let offset = parse_address(offset).ok_or_else(|| error("invalid offset"))? as usize;
+                    let bytes = records::parse_hex(hex).map_err(|_| error("invalid data"))?;
+                    let section = object.sections.iter_mut().find(|it| &it.name == name).ok_or_else(|| error("unknown section"))?;
+                    if offset != section.data.len() || offset + bytes.len() > section.size {
+                        return Err(error("data out of order"))
+                    }
+                    section.data.extend(bytes);
+                },
+                ["symbol", visibility @ ("local" | "global"), name, section, offset] => object.symbols.push(Symbol {
+                    name: name.to_string(),
+                    section: section.to_string(),
+                    offset: parse_address(offset).ok_or_else(|| error("invalid offset"))?,
+                    global: *visibility == "global"
+                }),
+                ["extern", name] => object.externs.push(name.to_string()),
+                ["reloc", section, offset, kind, target_kind, target, addend] => object.relocations.push(Relocation {
+                    section: section.to_string(),
+                    offset: parse_address(offset).ok_or_else(|| error("invalid offset"))?,
+                    kind: match *kind {
+                        "word" => RelocationKind::Word,
+                        "hi" => RelocationKind::High,
+                        "lo" => RelocationKind::Low,
+                        _ => return Err(error("invalid relocation kind"))
+                    },
+                    target: match *target_kind {
+                        "section" => Target::Section(target.to_string()),
+                        "symbol" => Target::Symbol(target.to_string()),
+                        _ => return Err(error("invalid relocation target"))
+                    },
+                    addend: addend.parse().map_err(|_| error("invalid addend"))?
+                }),
+                _ => return Err(error("invalid entry"))
+            }
+        }
+        for section in &object.sections {
+            if !section.data.is_empty() && section.data.len() != section.size {
+                return Err(FormatError { line: 0, message: format!("section `{}` is missing data", section.name) })
+            }
+        }
+        Ok(object)
+    }
+
+    pub fn parse(text: &str) -> Result<Self, FormatError> {
+        let mut lines = text.lines().enumerate().map(|(index, it)| (index + 1, it));
+        match lines.next() {
+            Some((_, "object")) => Self::parse_lines(lines),
+            _ => Err(FormatError { line: 1, message: "not an object file".to_string() })
+        }
+    }
+}
+
+impl Archive {
+    /// `archive`, then every member as `member <name>`, the lines of the object without its header and `end`.
+    pub fn write(&self) -> String {
+        let mut result = "archive\n".to_string();
+        for (name, object) in &self.members {
+            result += &format!("member {}\n", name);
+            result += object.write().strip_prefix("object\n").unwrap_or_default();
+            result += "end\n";
+        }
+        result
+    }
+
+    pub fn parse(text: &str) -> Result<Self, FormatError> {
+        let lines: Vec<(usize, &str)> = text.lines().enumerate().map(|(index, it)| (index + 1, it)).collect();
+        if lines.first().map(|it| it.1) != Some("archive") {
+            return Err(FormatError { line: 1, message: "not an archive".to_string() })
+        }
+        let mut archive = Archive::default();
+        let mut index = 1;
+        while index < lines.len() {
+            let (line, text) = lines[index];
+            if text.trim().is_empty() {
+                index += 1;
+                continue
+            }
+            let name = text.strip_prefix("member ").ok_or(FormatError { line, message: format!("expected a member: {}", text) })?;
+            let end = lines[index..].iter().position(|it| it.1 == "end")
+                .ok_or(FormatError { line, message: format!("member `{}` is not terminated", name) })? + index;
+            archive.members.push((name.to_string(), ObjectFile::parse_lines(lines[index + 1..end].iter().copied())?));
+            index = end + 1;
+        }
+        Ok(archive)
+    }
+}
@@ -0,0 +1,55 @@
+use std::str::FromStr;
+
+pub use records::{image, Chunk};
+
+pub enum OutputFormat {
+    Binary,
+    Text,
+    IntelHex,
+    SRecord
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "bin" => Ok(OutputFormat::Binary),
+            "text" => Ok(OutputFormat::Text),
+            "ihex" => Ok(OutputFormat::IntelHex),
+            "srec" => Ok(OutputFormat::SRecord),
+            _ => Err(format!("unknown output format: {}", s))
+        }
+    }
+}
+
+impl OutputFormat {
+    /// Binary and text output are zero filled between chunks, the record formats only contain the chunks.
+    pub fn write(&self, chunks: &[Chunk]) -> Vec<u8> {
+        match self {
+            OutputFormat::Binary => image(chunks).1,
+            OutputFormat::Text => {
+                let mut result = String::new();
+                let (origin, image) = image(chunks);
+                for (index, line) in image.chunks(16).enumerate() {
+                    result += &format!("{:04X}:", origin as usize + index * 16);
+                    for byte in line {
+                        result += &format!(" {:02X}", byte);
+                    }
+                    result += "\n";
+                }
+                result.into_bytes()
+            },
+            OutputFormat::IntelHex => records::write_intel_hex(chunks).into_bytes(),
+            OutputFormat::SRecord => records::write_s_record(chunks).into_bytes()
+        }
+    }
+    /// Extension of the output file if none is given.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            OutputFormat::Binary => "bin",
+            OutputFormat::Text => "txt",
+            OutputFormat::IntelHex => "hex",
+            OutputFormat::SRecord => "srec"
+        }
+    }
+}
@@ -0,0 +1,8 @@
+use std::process::Command;
+
+#[test]
+fn unknown_options_are_rejected() {
+    let output = Command::new(env!("CARGO_BIN_EXE_linker")).args(["--output", "program.bin", "main.o"]).output().unwrap();
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&output.stderr).starts_with("unexpected argument: --output\n"));
+}
@@ -0,0 +1,93 @@
+use std::collections::HashMap;
+use linker::link::{link, Linked, LinkError};
+use linker::object::{Archive, ObjectFile};
+
+fn objects(texts: &[(&str, &str)]) -> Vec<(String, ObjectFile)> {
+    texts.iter().map(|(name, text)| (name.to_string(), ObjectFile::parse(&format!("object\n{}", text)).unwrap())).collect()
+}
+
+fn link_objects(texts: &[(&str, &str)], bases: &[(&str, u16)]) -> Result<Linked, Vec<LinkError>> {
+    let bases: HashMap<String, u16> = bases.iter().map(|it| (it.0.to_string(), it.1)).collect();
+    link(objects(texts), vec![], &bases)
+}
+
+fn errors(result: Result<Linked, Vec<LinkError>>) -> Vec<String> {
+    match result {
+        Ok(_) => panic!("linked"),
+        Err(errors) => errors.iter().map(ToString::to_string).collect()
+    }
+}
+
+const MAIN: &str = "section code 4 1\ndata code 0x0000 78000000\nsymbol global main code 0x0000\nextern helper\nreloc code 0x0001 word symbol helper 0\n";
+const HELPER: &str = "section code 2 1\ndata code 0x0000 0100\nsymbol global helper code 0x0000\n";
+
+#[test]
+fn sections_follow_each_other_in_order() {
+    let linked = link_objects(&[("main.o", MAIN), ("helper.o", HELPER)], &[("code", 0x100)]).unwrap();
+    assert_eq!(linked.sections.len(), 1);
+    assert_eq!((linked.sections[0].origin, linked.sections[0].size), (0x100, 6));
+    assert_eq!(linked.sections[0].data, [0x78, 0x01, 0x04, 0x00, 0x01, 0x00]);
+    assert_eq!(linked.symbols.iter().map(|it| (it.0.as_str(), *it.1)).collect::<Vec<_>>(), [("helper", 0x104), ("main", 0x100)]);
+}
+
+#[test]
+fn relocations_are_patched() {
+    let object = "section code 6 1\ndata code 0x0000 000000000000\nsection data 3 2\ndata data 0x0000 AABBCC\n\
+        symbol local value data 0x0001\n\
+        reloc code 0x0000 word symbol value 1\nreloc code 0x0002 hi section data 0\nreloc code 0x0003 lo section data -2\n\
+        reloc code 0x0004 word section code 0\n";
+    let linked = link_objects(&[("a.o", object)], &[("code", 0x1000), ("data", 0x2100)]).unwrap();
+    assert_eq!(linked.sections[0].data, [0x21, 0x02, 0x21, 0xFE, 0x10, 0x00]);
+    // code ends at 0x1007, the data of the object is aligned to 0x1008
+    let linked = link_objects(&[("a.o", object)], &[("code", 0x1001)]).unwrap();
+    assert_eq!(linked.sections[0].data, [0x10, 0x0A, 0x10, 0x06, 0x10, 0x01]);
+}
+
+#[test]
+fn relocated_values_out_of_range() {
+    let object = "section code 2 1\ndata code 0x0000 0000\nreloc code 0x0000 word section code -1\n";
+    assert_eq!(errors(link_objects(&[("a.o", object)], &[])), ["relocated value -1 does not fit at code+0x0000 of a.o"]);
+}
+
+#[test]
+fn relocations_in_sections_without_data() {
+    let object = "section code 1 1\ndata code 0x0000 00\nsection bss 4 1\nsymbol global buffer bss 0x0000\nreloc bss 0x0000 word symbol buffer 0\n";
+    assert_eq!(errors(link_objects(&[("a.o", object)], &[])), ["a.o relocates bss+0x0000, but section `bss` has no data to patch"]);
+    // relocations may still refer to symbols in bss
+    let object = "section code 2 1\ndata code 0x0000 0000\nsection bss 4 1\nsymbol global buffer bss 0x0002\nreloc code 0x0000 word symbol buffer 0\n";
+    assert_eq!(link_objects(&[("a.o", object)], &[]).unwrap().sections[0].data, [0x00, 0x04]);
+}
+
+#[test]
+fn duplicate_and_undefined_symbols() {
+    assert_eq!(errors(link_objects(&[("main.o", MAIN), ("a.o", HELPER), ("b.o", HELPER)], &[])),
+        ["symbol `helper` is defined by both a.o and b.o"]);
+    assert_eq!(errors(link_objects(&[("main.o", MAIN)], &[])), ["undefined symbol `helper` referenced by main.o"]);
+    // local symbols of different objects do not clash
+    let local = "section code 1 1\ndata code 0x0000 00\nsymbol local loop code 0x0000\n";
+    assert!(link_objects(&[("a.o", local), ("b.o", local)], &[]).is_ok());
+}
+
+#[test]
+fn overlapping_sections() {
+    let code = "section code 4 1\ndata code 0x0000 00000000\n";
+    let data = "section data 4 1\ndata data 0x0000 00000000\n";
+    assert_eq!(errors(link_objects(&[("a.o", code), ("b.o", data)], &[("data", 2)])), ["sections `code` and `data` overlap"]);
+    assert_eq!(errors(link_objects(&[("a.o", code)], &[("code", 0xFFFE)])), ["section `code` extends past 0xFFFF"]);
+}
+
+#[test]
+fn archive_members_are_linked_when_needed() {
+    let archive = |texts: &[(&str, &str)]| ("lib.a".to_string(), Archive { members: objects(texts) });
+    let unused = "section code 1 1\ndata code 0x0000 FF\nsymbol global unused code 0x0000\n";
+    let print = "section code 1 1\ndata code 0x0000 02\nsymbol global print code 0x0000\n";
+    // helper needs print, which comes after it in the archive
+    let helper = "section code 2 1\ndata code 0x0000 0100\nsymbol global helper code 0x0000\nextern print\n";
+    let linked = link(objects(&[("main.o", MAIN)]), vec![archive(&[("unused.o", unused), ("helper.o", helper), ("print.o", print)])], &HashMap::new()).unwrap();
+    assert_eq!(linked.objects, ["main.o", "lib.a(helper.o)", "lib.a(print.o)"]);
+    assert_eq!(linked.sections[0].data, [0x78, 0x00, 0x04, 0x00, 0x01, 0x00, 0x02]);
+    assert!(!linked.symbols.contains_key("unused"));
+    // objects given directly win over archive members
+    let linked = link(objects(&[("main.o", MAIN), ("helper.o", HELPER)]), vec![archive(&[("helper.o", helper)])], &HashMap::new()).unwrap();
+    assert_eq!(linked.objects, ["main.o", "helper.o"]);
+}
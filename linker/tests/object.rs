@@ -0,0 +1,53 @@
+use linker::object::{Archive, ObjectFile, RelocationKind, Target};
+
+const OBJECT: &str = "object
+section code 20 1
+section bss 4 2
+data code 0x0000 000102030405060708090A0B0C0D0E0F
+data code 0x0010 10111213
+symbol global main code 0x0000
+symbol local loop code 0x0004
+symbol global buffer bss 0x0000
+extern print
+reloc code 0x0001 word symbol print 0
+reloc code 0x0004 hi section bss 2
+reloc code 0x0005 lo section bss -1
+";
+
+#[test]
+fn object_round_trip() {
+    let object = ObjectFile::parse(OBJECT).unwrap();
+    assert_eq!(object.write(), OBJECT);
+    let code = object.section("code").unwrap();
+    assert_eq!((code.size, code.align, code.data.len()), (20, 1, 20));
+    let bss = object.section("bss").unwrap();
+    assert_eq!((bss.size, bss.align, bss.data.len()), (4, 2, 0));
+    assert_eq!(object.symbols.iter().map(|it| (it.name.as_str(), it.global)).collect::<Vec<_>>(), [("main", true), ("loop", false), ("buffer", true)]);
+    assert_eq!(object.externs, ["print"]);
+    let relocation = &object.relocations[2];
+    assert_eq!((relocation.offset, relocation.kind, &relocation.target, relocation.addend), (5, RelocationKind::Low, &Target::Section("bss".to_string()), -1));
+}
+
+#[test]
+fn archive_round_trip() {
+    let archive = Archive::parse(&format!("archive\nmember a.o\n{}end\nmember b.o\nsection data 1 1\ndata data 0x0000 FF\nend\n", &OBJECT["object\n".len()..])).unwrap();
+    assert_eq!(archive.members.iter().map(|it| it.0.as_str()).collect::<Vec<_>>(), ["a.o", "b.o"]);
+    assert_eq!(archive.members[0].1.write(), OBJECT);
+    assert_eq!(Archive::parse(&archive.write()).unwrap().write(), archive.write());
+}
+
+#[test]
+fn malformed_objects() {
+    let error = |text: &str| ObjectFile::parse(text).err().map(|it| it.to_string()).unwrap();
+    assert_eq!(error("archive\n"), "line 1: not an object file");
+    assert_eq!(error("object\nsection code x 1\n"), "line 2: invalid size: section code x 1");
+    assert_eq!(error("object\ndata code 0x0000 00\n"), "line 2: unknown section: data code 0x0000 00");
+    assert_eq!(error("object\nsection code 2 1\ndata code 0x0001 00\n"), "line 3: data out of order: data code 0x0001 00");
+    assert_eq!(error("object\nsection code 2 1\ndata code 0x0000 0\n"), "line 3: invalid data: data code 0x0000 0");
+    assert_eq!(error("object\nsection code 2 1\ndata code 0x0000 00\n"), "line 0: section `code` is missing data");
+    assert_eq!(error("object\nreloc code 0x0000 dword symbol x 0\n"), "line 2: invalid relocation kind: reloc code 0x0000 dword symbol x 0");
+    assert_eq!(error("object\nfoo\n"), "line 2: invalid entry: foo");
+    let error = |text: &str| Archive::parse(text).err().map(|it| it.to_string()).unwrap();
+    assert_eq!(error("archive\nmember a.o\nextern x\n"), "line 2: member `a.o` is not terminated");
+    assert_eq!(error("archive\nextern x\n"), "line 2: expected a member: extern x");
+}
@@ -0,0 +1,159 @@
+/// A run of bytes loaded at an address.
+pub type Chunk = (u16, Vec<u8>);
+
+/// Bytes per data record in Intel HEX and S-record output.
+const RECORD_SIZE: usize = 16;
+
+/// Places every chunk into one image starting at the lowest address, gaps are zero filled.
+pub fn image(chunks: &[Chunk]) -> (u16, Vec<u8>) {
+    let origin = chunks.iter().map(|it| it.0 as usize).min().unwrap_or(0);
+    let end = chunks.iter().map(|it| it.0 as usize + it.1.len()).max().unwrap_or(0);
+    let mut image = vec![0; end - origin];
+    for (address, bytes) in chunks {
+        let start = *address as usize - origin;
+        image[start..start + bytes.len()].copy_from_slice(bytes);
+    }
+    (origin as u16, image)
+}
+
+/// Two uppercase hex digits per byte.
+pub fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|it| format!("{:02X}", it)).collect()
+}
+
+/// The bytes of an even number of hex digits.
+pub fn parse_hex(text: &str) -> Result<Vec<u8>, String> {
+    if !text.is_ascii() {
+        return Err(format!("invalid hex digits: {}", text))
+    }
+    if !text.len().is_multiple_of(2) {
+        return Err("odd number of hex digits".to_string())
+    }
+    (0..text.len()).step_by(2)
+        .map(|it| u8::from_str_radix(&text[it..it + 2], 16).map_err(|_| format!("invalid hex digits: {}", &text[it..it + 2])))
+        .collect()
+}
+
+fn sum(bytes: &[u8]) -> u8 {
+    bytes.iter().fold(0, |sum, it| sum.wrapping_add(*it))
+}
+
+/// Every chunk split into records of at most `RECORD_SIZE` bytes.
+fn records(chunks: &[Chunk]) -> impl Iterator<Item = (u16, &[u8])> {
+    chunks.iter().flat_map(|(address, bytes)| {
+        bytes.chunks(RECORD_SIZE).enumerate().map(move |(index, it)| (address + (index * RECORD_SIZE) as u16, it))
+    })
+}
+
+/// `:LLAAAATTDD..CC` with a two's complement checksum.
+fn intel_hex_record(kind: u8, address: u16, data: &[u8]) -> String {
+    let mut bytes = vec![data.len() as u8, (address >> 8) as u8, address as u8, kind];
+    bytes.extend_from_slice(data);
+    bytes.push(sum(&bytes).wrapping_neg());
+    format!(":{}\n", hex(&bytes))
+}
+
+/// `STCCAAAA..DD..CC` with a one's complement checksum.
+fn s_record(kind: char, address: u16, data: &[u8]) -> String {
+    let mut bytes = vec![data.len() as u8 + 3, (address >> 8) as u8, address as u8];
+    bytes.extend_from_slice(data);
+    bytes.push(!sum(&bytes));
+    format!("S{}{}\n", kind, hex(&bytes))
+}
+
+/// Data records for every chunk, followed by an end of file record.
+pub fn write_intel_hex(chunks: &[Chunk]) -> String {
+    let mut result = String::new();
+    for (address, data) in records(chunks) {
+        result += &intel_hex_record(0x00, address, data);
+    }
+    result + &intel_hex_record(0x01, 0, &[])
+}
+
+/// A header, S1 records for every chunk and an S9 record starting at the lowest address.
+pub fn write_s_record(chunks: &[Chunk]) -> String {
+    let mut result = s_record('0', 0, &[]);
+    for (address, data) in records(chunks) {
+        result += &s_record('1', address, data);
+    }
+    result + &s_record('9', image(chunks).0, &[])
+}
+
+/// Runs `parse` on every non-empty line with a running base address, prefixing errors with the line number.
+fn parse_records(
+    text: &str,
+    parse: impl Fn(&str, &mut u32) -> Result<Option<(u32, Vec<u8>)>, String>
+) -> Result<Vec<Chunk>, String> {
+    let mut chunks = vec![];
+    let mut base = 0;
+    for (index, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() { continue }
+        let record = parse(line, &mut base).map_err(|message| format!("line {}: {}", index + 1, message))?;
+        if let Some((address, data)) = record {
+            if address as usize + data.len() > 1 << 16 {
+                return Err(format!("line {}: data at 0x{:X} does not fit into the address space", index + 1, address))
+            }
+            chunks.push((address as u16, data));
+        }
+    }
+    Ok(chunks)
+}
+
+fn address(bytes: &[u8]) -> u32 {
+    bytes.iter().fold(0, |address, byte| address << 8 | *byte as u32)
+}
+
+/// `:LLAAAATTDD..CC`, the checksum makes all bytes sum up to zero.
+fn parse_intel_hex_line(line: &str, base: &mut u32) -> Result<Option<(u32, Vec<u8>)>, String> {
+    let bytes = parse_hex(line.strip_prefix(':').ok_or("record does not start with `:`")?)?;
+    if bytes.len() < 5 || bytes.len() != bytes[0] as usize + 5 {
+        return Err("record length does not match its byte count".to_string())
+    }
+    if sum(&bytes) != 0 {
+        return Err("checksum mismatch".to_string())
+    }
+    let data = &bytes[4..bytes.len() - 1];
+    match bytes[3] {
+        0x00 => Ok(Some((*base + address(&bytes[1..3]), data.to_vec()))),
+        0x02 if data.len() == 2 => { *base = address(data) << 4; Ok(None) },
+        0x04 if data.len() == 2 => { *base = address(data) << 16; Ok(None) },
+        // end of file and start addresses
+        0x01 | 0x03 | 0x05 => Ok(None),
+        kind => Err(format!("unsupported record type {:02X}", kind))
+    }
+}
+
+/// `STCCAAAA..DD..CC`, the checksum is the complement of the sum of count, address and data.
+fn parse_s_record_line(line: &str, _base: &mut u32) -> Result<Option<(u32, Vec<u8>)>, String> {
+    let kind = line.strip_prefix('S').and_then(|it| it.chars().next()).ok_or("record does not start with `S`")?;
+    let bytes = parse_hex(line.get(2..).ok_or("record does not start with `S`")?)?;
+    if bytes.is_empty() || bytes.len() != bytes[0] as usize + 1 {
+        return Err("record length does not match its byte count".to_string())
+    }
+    if sum(&bytes) != 0xFF {
+        return Err("checksum mismatch".to_string())
+    }
+    let address_size = match kind {
+        '1' => 2,
+        '2' => 3,
+        '3' => 4,
+        // header, record counts and start addresses
+        '0' | '5' | '6' | '7' | '8' | '9' => return Ok(None),
+        _ => return Err(format!("unsupported record type S{}", kind))
+    };
+    if bytes.len() < address_size + 2 {
+        return Err("record is too short".to_string())
+    }
+    Ok(Some((address(&bytes[1..1 + address_size]), bytes[1 + address_size..bytes.len() - 1].to_vec())))
+}
+
+/// The data records of Intel HEX `text`, one chunk per record.
+pub fn parse_intel_hex(text: &str) -> Result<Vec<Chunk>, String> {
+    parse_records(text, parse_intel_hex_line)
+}
+
+/// The S1, S2 and S3 records of `text`, one chunk per record.
+pub fn parse_s_record(text: &str) -> Result<Vec<Chunk>, String> {
+    parse_records(text, parse_s_record_line)
+}
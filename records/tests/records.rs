@@ -0,0 +1,49 @@
+use records::{image, parse_intel_hex, parse_s_record, write_intel_hex, write_s_record, Chunk};
+
+/// A chunk longer than one record and a second one after a gap.
+fn chunks() -> Vec<Chunk> {
+    vec![(0x0100, (0..20).collect()), (0xFFF0, vec![0xAA, 0xBB])]
+}
+
+#[test]
+fn image_fills_gaps_with_zeros() {
+    assert_eq!(image(&[(0x10, vec![1, 2]), (0x14, vec![3])]), (0x10, vec![1, 2, 0, 0, 3]));
+    assert_eq!(image(&[]), (0, vec![]));
+}
+
+#[test]
+fn intel_hex_round_trip() {
+    let text = write_intel_hex(&chunks());
+    assert_eq!(text, ":10010000000102030405060708090A0B0C0D0E0F77\n:0401100010111213A5\n:02FFF000AABBAA\n:00000001FF\n");
+    assert_eq!(parse_intel_hex(&text).unwrap(), [(0x0100, (0..16).collect()), (0x0110, vec![16, 17, 18, 19]), (0xFFF0, vec![0xAA, 0xBB])]);
+}
+
+#[test]
+fn s_record_round_trip() {
+    let text = write_s_record(&chunks());
+    assert_eq!(text, "S0030000FC\nS1130100000102030405060708090A0B0C0D0E0F73\nS107011010111213A1\nS105FFF0AABBA6\nS9030100FB\n");
+    assert_eq!(parse_s_record(&text).unwrap(), [(0x0100, (0..16).collect()), (0x0110, vec![16, 17, 18, 19]), (0xFFF0, vec![0xAA, 0xBB])]);
+}
+
+#[test]
+fn intel_hex_extended_addresses() {
+    // segment 0x0010 moves the record to 0x0100, a linear base of 0x0001 moves it out of the address space
+    assert_eq!(parse_intel_hex(":020000020010EC\n:0100000042BD\n").unwrap(), [(0x0100, vec![0x42])]);
+    assert_eq!(parse_intel_hex(":020000040001F9\n:0100000042BD\n").unwrap_err(), "line 2: data at 0x10000 does not fit into the address space");
+}
+
+#[test]
+fn checksum_mismatches() {
+    assert_eq!(parse_intel_hex(":0100000042BE\n").unwrap_err(), "line 1: checksum mismatch");
+    assert_eq!(parse_s_record("S0030000FC\nS104000042B8\n").unwrap_err(), "line 2: checksum mismatch");
+}
+
+#[test]
+fn malformed_records() {
+    assert_eq!(parse_intel_hex("0100000042BD\n").unwrap_err(), "line 1: record does not start with `:`");
+    assert_eq!(parse_intel_hex(":0200000042BD\n").unwrap_err(), "line 1: record length does not match its byte count");
+    assert_eq!(parse_intel_hex(":0100000042B\n").unwrap_err(), "line 1: odd number of hex digits");
+    assert_eq!(parse_intel_hex(":00000006FA\n").unwrap_err(), "line 1: unsupported record type 06");
+    assert_eq!(parse_s_record("S4030000FC\n").unwrap_err(), "line 1: unsupported record type S4");
+    assert_eq!(parse_s_record("S1FFFF0000\n").unwrap_err(), "line 1: record length does not match its byte count");
+}